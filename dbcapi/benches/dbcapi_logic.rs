@@ -153,4 +153,418 @@ mod tests {
             sockdata::types::SubscribeFlag::ALL,
         ));
     }
+
+    #[test]
+    fn dbc_parser_decodes_intel_motorola_and_multiplexed_signals() {
+        use dbcapi::dbcfile::{parse_dbc, verb_name, DbcMux};
+
+        let text = r#"
+BO_ 322 MUX_TEST: 8 Tx
+ SG_ MUX_signal M : 0|2@1+ (1,0) [0|2] "" Rx
+ SG_ open m0 : 3|1@1+ (1,0) [0|1] "" Rx
+ SG_ speed m1 : 3|10@1- (0.1,0) [-51.2|51.1] "m/s" Rx
+ SG_ temperature m1 : 13|8@1- (1,0) [-128|127] "degC" Rx
+
+CM_ BO_ 322 "multi-line comment
+SG_ ghost : 0|8@1+ (1,0) [0|255] "" Rx
+";
+BO_ 641 Main_Param_Bat: 8 Vector__XXX
+ SG_ SWMajorVer : 7|10@0+ (1,0) [0|1023] "" Vector__XXX
+"#;
+        let dbc = parse_dbc(text).unwrap();
+        assert_eq!(dbc.messages.len(), 2);
+        let mux = &dbc.messages[0];
+        assert_eq!((mux.id, mux.dlc, mux.signals.len()), (322, 8, 4));
+        assert_eq!(mux.signals[2].mux, DbcMux::Multiplexed(1));
+        assert_eq!(mux.signals[2].unit, "m/s");
+        assert!(mux.comment.as_deref().unwrap().starts_with("multi-line comment\n"));
+        assert_eq!(verb_name(&mux.name), "MuxTest");
+
+        // Page 1: speed raw -5 (x0.1), temperature -2.
+        let data = [0xD9, 0xDF, 0x1F, 0, 0, 0, 0, 0];
+        let page = mux.mux_value(&data);
+        assert_eq!(page, Some(1));
+        assert!(!mux.is_active(&mux.signals[1], page));
+        assert!(mux.is_active(&mux.signals[2], page));
+        assert!((mux.signals[2].decode(&data).unwrap() + 0.5).abs() < 1e-9);
+        assert_eq!(mux.signals[3].decode(&data), Some(-2.0));
+
+        // Motorola: MSB at bit 7, sawtooth into the next byte.
+        let bat = &dbc.messages[1];
+        assert_eq!(bat.signals[0].decode(&[0xAA, 0xC0]), Some(683.0));
+        assert_eq!(bat.signals[0].decode(&[0xAA]), None);
+
+        assert!(parse_dbc("BO_ 1 Msg: 8 Tx\n SG_ Bad : 3|x@1+ (1,0) [0|1] \"\" Rx").is_err());
+        let extended = parse_dbc("BO_ 1 Msg: 8 Tx\n SG_ Page m1M : 2|2@1+ (1,0) [0|3] \"\" Rx");
+        assert!(extended.unwrap_err().contains("extended multiplexing unsupported"));
+    }
+
+    #[test]
+    fn dbc_encoder_selects_mux_page_and_checks_range() {
+        use dbcapi::dbcfile::parse_dbc;
+
+        let text = r#"
+BO_ 322 MUX_TEST: 8 Tx
+ SG_ MUX_signal M : 0|2@1+ (1,0) [0|2] "" Rx
+ SG_ open m0 : 3|1@1+ (1,0) [0|1] "" Rx
+ SG_ speed m1 : 3|10@1- (0.1,0) [-51.2|51.1] "m/s" Rx
+ SG_ temperature m1 : 13|8@1- (1,0) [-128|127] "degC" Rx
+ SG_ counter : 56|8@1+ (1,0) [0|255] "" Rx
+BO_ 641 Main_Param_Bat: 2 Vector__XXX
+ SG_ SWMajorVer : 7|10@0+ (1,0) [0|1023] "" Vector__XXX
+BA_ "GenSigStartValue" SG_ 322 temperature 254;
+BA_ "GenSigStartValue" SG_ 322 counter 7;
+BA_ "GenMsgCycleTime" BO_ 322 0;
+BA_ "GenMsgCycleTime" BO_ 641 100;
+VAL_ 322 open 0 "Closed" 1 "Open" ;
+CM_ SG_ 322 open "Door state";
+"#;
+        let dbc = parse_dbc(text).unwrap();
+        let mux = &dbc.messages[0];
+        assert_eq!(mux.signals[3].initial, Some(254));
+        assert_eq!((mux.cycle, dbc.messages[1].cycle), (None, Some(100)));
+
+        // Start values only apply to the default (0) multiplexer page.
+        let mut data = mux.default_frame();
+        assert_eq!(data, vec![0, 0, 0, 0, 0, 0, 0, 7]);
+
+        // Writing page 1 signals switches the multiplexer and keeps the other bytes.
+        let values = [("Speed".to_string(), -0.5), ("temperature".to_string(), -2.0)];
+        mux.encode(&mut data, &values).unwrap();
+        assert_eq!(data, vec![0xD9, 0xDF, 0x1F, 0, 0, 0, 0, 7]);
+        assert!((mux.signals[2].decode(&data).unwrap() + 0.5).abs() < 1e-9);
+
+        // Nothing is written when one value is rejected.
+        assert!(mux.encode(&mut data, &[("speed".to_string(), 60.0)]).is_err());
+        assert!(mux
+            .encode(&mut data, &[("open".to_string(), 1.0), ("speed".to_string(), 1.0)])
+            .is_err());
+        assert_eq!(data, vec![0xD9, 0xDF, 0x1F, 0, 0, 0, 0, 7]);
+
+        // Motorola round trip.
+        let bat = &dbc.messages[1];
+        let mut data = bat.default_frame();
+        bat.encode(&mut data, &[("SWMajorVer".to_string(), 683.0)]).unwrap();
+        assert_eq!(data, vec![0xAA, 0xC0]);
+
+        // Value table labels are accepted wherever a number is.
+        let open = &mux.signals[1];
+        assert_eq!((open.label(1.0), open.label(2.0)), (Some("Open"), None));
+        assert_eq!(open.comment.as_deref(), Some("Door state"));
+        assert_eq!(
+            dbcapi::logic::parse_write_values(
+                "{\"values\":{\"open\":\"closed\"}}",
+                None,
+                Some(mux)
+            ),
+            Ok(vec![("open".to_string(), 0.0)])
+        );
+        assert_eq!(
+            dbcapi::logic::parse_write_values("{\"value\":true}", Some("Open"), None),
+            Ok(vec![("Open".to_string(), 1.0)])
+        );
+        assert!(dbcapi::logic::parse_write_values("{\"value\":\"Ajar\"}", Some("Open"), Some(mux))
+            .is_err());
+        assert_eq!(
+            dbcapi::logic::parse_match_filter("{\"match\":[\"Open\",0]}", "match", Some(open)),
+            Ok(Some(vec![1.0, 0.0]))
+        );
+    }
+
+    #[test]
+    fn dbc_dyn_pool_applies_frames_to_signals() {
+        use dbcapi::DbcDynPool;
+        use sockcan::prelude::{
+            CanBcmOpCode, CanDataStatus, CanDbcMessage, CanDbcPool, CanDbcType, CanMsgData,
+        };
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let text = r#"
+BO_ 322 MUX_TEST: 8 Tx
+ SG_ MUX_signal M : 0|2@1+ (1,0) [0|2] "" Rx
+ SG_ open m0 : 3|1@1+ (1,0) [0|1] "" Rx
+ SG_ speed m1 : 3|10@1- (0.1,0) [-51.2|51.1] "m/s" Rx
+ SG_ temperature m1 : 13|8@1- (1,0) [-128|127] "degC" Rx
+"#;
+        let pool = DbcDynPool::new("dbc-test", text).unwrap();
+        let frame = |canid: u32, stamp: u64, data: &'static [u8; 8]| CanMsgData {
+            canid,
+            stamp,
+            opcode: CanBcmOpCode::RxChanged,
+            len: 8,
+            data,
+        };
+        let signal = |message: &Rc<RefCell<Box<dyn CanDbcMessage>>>, idx: usize| {
+            let message = message.borrow();
+            let signal = message.get_signals()[idx].borrow();
+            let value = match signal.get_value() {
+                CanDbcType::F64(value) => value,
+                CanDbcType::Bool(value) => f64::from(u8::from(value)),
+                _ => f64::NAN,
+            };
+            (value, signal.get_status(), signal.get_stamp())
+        };
+
+        // Page 1: speed -0.5, temperature -2; the page 0 signal is left untouched.
+        let message = pool.update(&frame(322, 10, &[0xD9, 0xDF, 0x1F, 0, 0, 0, 0, 0])).unwrap();
+        let (speed, status, _) = signal(&message, 2);
+        assert!((speed + 0.5).abs() < 1e-9 && status == CanDataStatus::Updated);
+        assert_eq!(signal(&message, 3).0, -2.0);
+        assert!(signal(&message, 1).1 == CanDataStatus::Unset);
+
+        // Same speed, new temperature.
+        let message = pool.update(&frame(322, 20, &[0xD9, 0xBF, 0x1F, 0, 0, 0, 0, 0])).unwrap();
+        assert!(signal(&message, 2).1 == CanDataStatus::Unchanged);
+        let (temperature, status, stamp) = signal(&message, 3);
+        assert!(temperature == -3.0 && status == CanDataStatus::Updated && stamp == 20);
+
+        // Page 0 only updates `open`, page 1 signals keep their value and stamp.
+        let message = pool.update(&frame(322, 30, &[0x08, 0xA0, 0x1F, 0, 0, 0, 0, 0])).unwrap();
+        let (open, status, _) = signal(&message, 1);
+        assert!(open == 1.0 && status == CanDataStatus::Updated);
+        let (speed, _, stamp) = signal(&message, 2);
+        assert!((speed + 0.5).abs() < 1e-9 && stamp == 20);
+        assert_eq!(message.borrow().get_stamp(), 30);
+
+        assert!(pool.update(&frame(999, 40, &[0; 8])).is_err());
+    }
+
+    #[test]
+    fn subscribe_set_patterns_match_with_wildcards() {
+        use dbcapi::logic::{glob_match, parse_set_patterns, set_selects};
+
+        assert!(glob_match("*Volt*", "CellVoltMax"));
+        assert!(glob_match("cell?olt*", "CellVoltMax"));
+        assert!(glob_match("*a*b", "xaxxab"));
+        assert!(!glob_match("*Volt", "CellVoltMax"));
+        assert!(!glob_match("Cell", "CellVoltMax"));
+
+        assert!(set_selects("BmsStatus/*", "BmsStatus", "PackVoltage"));
+        assert!(set_selects("BmsStatus", "BmsStatus", "PackVoltage"));
+        assert!(set_selects("*/Pack*", "BmsLimits", "PackCurrentMax"));
+        assert!(!set_selects("BmsStatus/Cell*", "BmsStatus", "PackVoltage"));
+
+        assert_eq!(
+            parse_set_patterns("{\"signals\":[\"BmsStatus/*\",\"*/Soc\"]}"),
+            Ok(vec!["BmsStatus/*".to_string(), "*/Soc".to_string()])
+        );
+        assert!(parse_set_patterns("{\"signals\":[]}").is_err());
+        assert!(parse_set_patterns("{\"signals\":[42]}").is_err());
+    }
+
+    #[test]
+    fn value_filter_applies_deadband_and_hysteresis() {
+        use dbcapi::logic::{parse_value_filter, Zone};
+
+        // Absolute deadband, measured from the last pushed value.
+        let mut filter = parse_value_filter("{\"deadband\":0.5}", None).unwrap().unwrap();
+        let pushed: Vec<bool> =
+            [10.0, 10.2, 10.4, 10.5, 10.1, 9.9].iter().map(|v| filter.accept(*v)).collect();
+        assert_eq!(pushed, vec![true, false, false, true, false, true]);
+
+        // Relative deadband.
+        let mut filter = parse_value_filter("{\"deadband\":\"10%\"}", None).unwrap().unwrap();
+        let pushed: Vec<bool> = [100.0, 105.0, 111.0].iter().map(|v| filter.accept(*v)).collect();
+        assert_eq!(pushed, vec![true, false, true]);
+
+        // Threshold crossings only, with 2 units of hysteresis.
+        let query = "{\"above\":80,\"below\":20,\"hysteresis\":2}";
+        let mut filter = parse_value_filter(query, None).unwrap().unwrap();
+        let pushed: Vec<bool> = [50.0, 81.0, 79.0, 85.0, 77.0, 19.0, 21.0, 23.0]
+            .iter()
+            .map(|v| filter.accept(*v))
+            .collect();
+        assert_eq!(pushed, vec![false, true, false, false, true, true, false, true]);
+        assert_eq!(filter.zone(), Zone::Normal);
+
+        assert_eq!(parse_value_filter("{\"rate\":100}", None), Ok(None));
+        assert!(parse_value_filter("{\"above\":10,\"below\":20}", None).is_err());
+        assert!(parse_value_filter("{\"deadband\":\"ten%\"}", None).is_err());
+    }
+
+    #[test]
+    fn alarm_rule_raises_after_delay_and_latches() {
+        use dbcapi::logic::{parse_alarm_rule, AlarmChange, AlarmOp, AlarmState};
+
+        let rule = parse_alarm_rule("BmsStatus/PackTemp > 55 for 2s").unwrap();
+        assert_eq!(rule.signal, "BmsStatus/PackTemp");
+        assert_eq!((rule.op, rule.value, rule.delay), (AlarmOp::Gt, 55.0, 2000));
+        assert_eq!(parse_alarm_rule("BmsStatus/Soc <= 10 for 500ms").unwrap().delay, 500);
+        assert!(parse_alarm_rule("PackTemp > 55").is_err());
+        assert!(parse_alarm_rule("BmsStatus/PackTemp => 55").is_err());
+        assert!(parse_alarm_rule("BmsStatus/PackTemp > 55 during 2s").is_err());
+
+        // Stamps are in µs: the condition must hold 2s before the alarm is raised.
+        let mut state = AlarmState::default();
+        assert_eq!(state.update(&rule, 60.0, 1_000_000), None);
+        assert_eq!(state.update(&rule, 50.0, 2_000_000), None);
+        assert_eq!(state.update(&rule, 60.0, 3_000_000), None);
+        assert_eq!(state.update(&rule, 61.0, 5_000_000), Some(AlarmChange::Raised));
+        assert_eq!(state.update(&rule, 62.0, 6_000_000), None);
+        assert_eq!(state.update(&rule, 40.0, 7_000_000), Some(AlarmChange::Cleared));
+        assert!(!state.active && state.latched);
+        assert!(state.acknowledge());
+        assert!(!state.acknowledge());
+    }
+
+    #[test]
+    fn virtual_expression_computes_from_inputs() {
+        use dbcapi::logic::{parse_expr, MovingAverage};
+
+        let (expr, inputs) =
+            parse_expr("BmsStatus/PackVoltage * -BmsStatus/PackCurrent/1000 + 0.5").unwrap();
+        assert_eq!(inputs, vec!["BmsStatus/PackVoltage", "BmsStatus/PackCurrent"]);
+        assert_eq!(expr.eval(&[Some(400.0), None]), None);
+        assert_eq!(expr.eval(&[Some(400.0), Some(-20.0)]), Some(8.5));
+
+        // Unit conversion, inputs are shared case-insensitively.
+        let (expr, inputs) = parse_expr("(BmsStatus/Temp * 1.8) + 32 - bmsstatus/temp").unwrap();
+        assert_eq!(inputs.len(), 1);
+        assert_eq!(expr.eval(&[Some(100.0)]), Some(112.0));
+        let (expr, _) = parse_expr("1 / BmsStatus/Soc").unwrap();
+        assert_eq!(expr.eval(&[Some(0.0)]), None);
+
+        assert!(parse_expr("PackVoltage * 2").is_err());
+        assert!(parse_expr("(BmsStatus/Soc + 1").is_err());
+        assert!(parse_expr("BmsStatus/Soc 2").is_err());
+        assert!(parse_expr("BmsStatus/Soc % 2").is_err());
+
+        let mut average = MovingAverage::new(3);
+        let means: Vec<f64> = [3.0, 6.0, 9.0, 12.0].iter().map(|v| average.push(*v)).collect();
+        assert_eq!(means, vec![3.0, 4.5, 6.0, 9.0]);
+    }
+
+    #[test]
+    fn history_keeps_samples_by_count_and_age() {
+        use dbcapi::logic::{parse_history_query, History, HistoryQuery};
+
+        let mut history = History::new(Some(4), None);
+        for (idx, value) in [1.0, 2.0, 3.0, 4.0, 5.0].iter().enumerate() {
+            history.push(idx as u64 * 100_000, *value);
+        }
+        let all = HistoryQuery::default();
+        assert_eq!(
+            history.query(&all),
+            vec![(100_000, 2.0), (200_000, 3.0), (300_000, 4.0), (400_000, 5.0)]
+        );
+        let range = HistoryQuery { start: Some(200_000), end: Some(300_000), interval: None };
+        assert_eq!(history.query(&range).len(), 2);
+        // 200ms windows: (2+3)/2 and (4+5)/2.
+        let query = HistoryQuery { interval: Some(200), ..HistoryQuery::default() };
+        assert_eq!(history.query(&query), vec![(100_000, 2.5), (300_000, 4.5)]);
+
+        let mut history = History::new(None, Some(1));
+        history.push(0, 1.0);
+        history.push(600_000, 2.0);
+        history.push(1_500_000, 3.0);
+        assert_eq!(history.query(&all), vec![(600_000, 2.0), (1_500_000, 3.0)]);
+
+        assert_eq!(parse_history_query("{\"action\":\"history\"}"), Ok(all));
+        assert_eq!(
+            parse_history_query("{\"start\":10,\"end\":20,\"interval\":5}"),
+            Ok(HistoryQuery { start: Some(10), end: Some(20), interval: Some(5) })
+        );
+        assert!(parse_history_query("{\"start\":20,\"end\":10}").is_err());
+        assert!(parse_history_query("{\"interval\":0}").is_err());
+        assert!(parse_history_query("{\"start\":-1}").is_err());
+    }
+
+    #[test]
+    fn stats_window_aggregates_recent_samples() {
+        use dbcapi::logic::{parse_stats_query, StatsQuery, StatsWindow};
+
+        let mut window = StatsWindow::new(1000);
+        for (idx, value) in [10.0, 2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0].iter().enumerate() {
+            window.push(idx as u64 * 250_000, *value);
+        }
+        // 1s window up to the newest sample at 2s: the first 4 samples were dropped.
+        let stats = window.compute(1000);
+        assert_eq!((stats.count, stats.min, stats.max), (5, 4.0, 9.0));
+        assert!((stats.mean - 6.0).abs() < 1e-9);
+        assert!((stats.stddev - 1.7888543819998317).abs() < 1e-9);
+        assert_eq!(stats.rate, 4.0);
+
+        let stats = window.compute(500);
+        assert_eq!((stats.count, stats.min), (3, 5.0));
+        assert_eq!(StatsWindow::new(1000).compute(1000).count, 0);
+
+        assert_eq!(
+            parse_stats_query("{\"stats\":1000,\"window\":5000}"),
+            Ok(StatsQuery { window: Some(5000), period: Some(1000) })
+        );
+        assert!(parse_stats_query("{\"window\":0}").is_err());
+    }
+
+    #[test]
+    fn merge_demands_keeps_the_most_demanding_subscriber() {
+        use dbcapi::logic::{merge_demands, Demand};
+        use sockdata::types::SubscribeFlag;
+
+        let slow = Demand { rate: 1000, watchdog: 5000, flag: SubscribeFlag::NEW };
+        let fast = Demand { rate: 100, watchdog: 10_000, flag: SubscribeFlag::ALL };
+        assert_eq!(merge_demands(&[]), None);
+        assert_eq!(merge_demands([&slow]), Some(slow.clone()));
+        assert_eq!(
+            merge_demands([&slow, &fast]),
+            Some(Demand { rate: 100, watchdog: 5000, flag: SubscribeFlag::ALL })
+        );
+
+        // A subscriber without watchdog does not disable the one asked by another.
+        let unwatched = Demand { rate: 10, watchdog: 0, flag: SubscribeFlag::NEW };
+        assert_eq!(
+            merge_demands([&unwatched, &slow]),
+            Some(Demand { rate: 10, watchdog: 5000, flag: SubscribeFlag::NEW })
+        );
+        assert_eq!(merge_demands([&slow, &unwatched]), merge_demands([&unwatched, &slow]));
+        assert_eq!(merge_demands([&unwatched, &unwatched]), Some(unwatched.clone()));
+
+        // Releasing demands one by one falls back to the remaining one, then to none.
+        let mut demands = std::collections::HashMap::from([(1, slow.clone()), (2, fast)]);
+        demands.remove(&2);
+        assert_eq!(merge_demands(demands.values()), Some(slow));
+        demands.remove(&1);
+        assert_eq!(merge_demands(demands.values()), None);
+    }
+
+    #[test]
+    fn backend_subscription_follows_two_sessions_leaving_in_either_order() {
+        use dbcapi::logic::{backend_change, merge_demands, Demand};
+        use sockdata::types::SubscribeFlag;
+
+        let slow = Demand { rate: 1000, watchdog: 0, flag: SubscribeFlag::NEW };
+        let fast = Demand { rate: 100, watchdog: 0, flag: SubscribeFlag::NEW };
+
+        // Sessions 1 and 2 subscribe, then leave in `order`: return the backend calls made.
+        let run = |order: [u64; 2]| {
+            let mut demands = std::collections::HashMap::new();
+            let mut current: Option<Demand> = None;
+            let mut calls = Vec::new();
+            let steps = [(1, Some(slow.clone())), (2, Some(fast.clone()))]
+                .into_iter()
+                .chain(order.map(|session| (session, None)));
+            for (session, demand) in steps {
+                match demand {
+                    Some(demand) => demands.insert(session, demand),
+                    None => demands.remove(&session),
+                };
+                let merged = merge_demands(demands.values());
+                if let Some(change) = backend_change(current.as_ref(), merged.as_ref()) {
+                    calls.push(change.clone());
+                    current = change;
+                }
+            }
+            calls
+        };
+
+        // The first leaver never drops the filter the other session still needs, the last
+        // one always removes it.
+        assert_eq!(run([1, 2]), vec![Some(slow.clone()), Some(fast.clone()), None]);
+        assert_eq!(
+            run([2, 1]),
+            vec![Some(slow.clone()), Some(fast.clone()), Some(slow.clone()), None]
+        );
+        assert_eq!(backend_change(None, None), None);
+        assert_eq!(backend_change(Some(&slow), Some(&slow)), None);
+    }
 }
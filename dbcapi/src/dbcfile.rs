@@ -0,0 +1,603 @@
+/*
+ * Copyright (C) 2015-2023 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * $RP_BEGIN_LICENSE$
+ * Commercial License Usage
+ *  Licensees holding valid commercial IoT.bzh licenses may use this file in
+ *  accordance with the commercial license agreement provided with the
+ *  Software or, alternatively, in accordance with the terms contained in
+ *  a written agreement between you and The IoT.bzh Company. For licensing terms
+ *  and conditions see https://www.iot.bzh/terms-conditions. For further
+ *  information use the contact form at https://www.iot.bzh/contact.
+ *
+ * GNU General Public License Usage
+ *  Alternatively, this file may be used under the terms of the GNU General
+ *  Public license version 3. This license is as published by the Free Software
+ *  Foundation and appearing in the file LICENSE.GPLv3 included in the packaging
+ *  of this file. Please review the following information to ensure the GNU
+ *  General Public License requirements will be met
+ *  https://www.gnu.org/licenses/gpl-3.0.html.
+ * $RP_END_LICENSE$
+ */
+
+//! Runtime DBC file parser and signal codec.
+//!
+//! Only the statements needed to decode and encode frames are interpreted: messages (`BO_`),
+//! signals (`SG_`, including simple multiplexing), IEEE float signal types
+//! (`SIG_VALTYPE_`), signal start values (`BA_ "GenSigStartValue"`), message cycle
+//! times (`BA_ "GenMsgCycleTime"`), signal value tables (`VAL_`) and message and signal
+//! comments (`CM_`). Every other statement is skipped.
+
+// Keep this module pure: no Afb types, no I/O.
+
+/// Pseudo message used by DBC editors to hold signals not attached to any frame.
+const DBC_INDEPENDENT_SIG_MSG: u32 = 0xC000_0000;
+
+/// Multiplexing role of a signal inside its message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbcMux {
+    /// Always present in the frame.
+    Plain,
+    /// Multiplexer selector (`M`).
+    Multiplexor,
+    /// Only present when the multiplexer holds this value (`mN`).
+    Multiplexed(u64),
+}
+
+/// One `SG_` signal definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbcSignalDef {
+    pub name: String,
+    pub start: u16,
+    pub size: u16,
+    pub little_endian: bool,
+    pub signed: bool,
+    pub float: bool,
+    pub factor: f64,
+    pub offset: f64,
+    pub min: f64,
+    pub max: f64,
+    pub unit: String,
+    pub mux: DbcMux,
+    pub initial: Option<u64>,
+    /// Value table (`VAL_`): raw value and its label.
+    pub labels: Vec<(i64, String)>,
+    pub comment: Option<String>,
+}
+
+/// One `BO_` message definition with its signals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbcMessageDef {
+    pub id: u32,
+    pub name: String,
+    pub dlc: u8,
+    pub sender: String,
+    pub signals: Vec<DbcSignalDef>,
+    /// Transmission period in ms (`GenMsgCycleTime`), None for event driven messages.
+    pub cycle: Option<u64>,
+    pub comment: Option<String>,
+}
+
+/// Messages defined by a DBC file, in file order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DbcFile {
+    pub messages: Vec<DbcMessageDef>,
+}
+
+impl DbcSignalDef {
+    /// Extract the raw (unscaled, zero-extended) value of this signal from `data`.
+    ///
+    /// Returns None when the signal does not fit in the frame.
+    pub fn raw(&self, data: &[u8]) -> Option<u64> {
+        let mut raw: u64 = 0;
+        for (idx, bit) in self.bits().into_iter().enumerate() {
+            let byte = *data.get(bit / 8)?;
+            if byte & (1 << (bit % 8)) != 0 {
+                raw |= 1 << idx;
+            }
+        }
+        Some(raw)
+    }
+
+    /// Decode the physical value of this signal from `data` (`raw * factor + offset`).
+    pub fn decode(&self, data: &[u8]) -> Option<f64> {
+        let raw = self.raw(data)?;
+        let value = match (self.float, self.size) {
+            (true, 32) => f32::from_bits(raw as u32) as f64,
+            (true, 64) => f64::from_bits(raw),
+            _ if self.signed && self.size < 64 && raw & (1 << (self.size - 1)) != 0 => {
+                (raw | !raw_mask(self.size)) as i64 as f64
+            },
+            _ if self.signed => raw as i64 as f64,
+            _ => raw as f64,
+        };
+        Some(value * self.factor + self.offset)
+    }
+
+    /// Convert a physical value into the raw value of this signal (`(value - offset) / factor`).
+    ///
+    /// The value must stay within the DBC `[min|max]` range (unless both are 0) and the
+    /// rounded raw value must fit in the signal size.
+    pub fn to_raw(&self, value: f64) -> Result<u64, String> {
+        if !value.is_finite() {
+            return Err(format!("{}: invalid value {}", self.name, value));
+        }
+        if (self.min != 0.0 || self.max != 0.0) && (value < self.min || value > self.max) {
+            return Err(format!(
+                "{}: {} out of range [{}|{}]",
+                self.name, value, self.min, self.max
+            ));
+        }
+
+        let scaled = (value - self.offset) / self.factor;
+        match (self.float, self.size) {
+            (true, 32) => return Ok((scaled as f32).to_bits() as u64),
+            (true, 64) => return Ok(scaled.to_bits()),
+            _ => {},
+        }
+
+        let raw = scaled.round();
+        let (low, high) = if self.signed {
+            (-((1u64 << (self.size - 1)) as f64), ((1u64 << (self.size - 1)) - 1) as f64)
+        } else {
+            (0.0, raw_mask(self.size) as f64)
+        };
+        if raw < low || raw > high {
+            return Err(format!("{}: {} does not fit in {} bits", self.name, value, self.size));
+        }
+        if self.signed {
+            Ok(raw as i64 as u64 & raw_mask(self.size))
+        } else {
+            Ok(raw as u64)
+        }
+    }
+
+    /// Value table label of the physical `value`, if any.
+    pub fn label(&self, value: f64) -> Option<&str> {
+        if self.labels.is_empty() {
+            return None;
+        }
+        let raw = ((value - self.offset) / self.factor).round() as i64;
+        self.labels.iter().find(|(key, _)| *key == raw).map(|(_, label)| label.as_str())
+    }
+
+    /// Physical value of a value table `label` (case-insensitive).
+    pub fn label_value(&self, label: &str) -> Option<f64> {
+        self.labels
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(label))
+            .map(|(raw, _)| *raw as f64 * self.factor + self.offset)
+    }
+
+    /// Store the raw value of this signal into `data`, leaving the other bits untouched.
+    pub fn insert(&self, raw: u64, data: &mut [u8]) -> Result<(), String> {
+        for (idx, bit) in self.bits().into_iter().enumerate() {
+            let byte = match data.get_mut(bit / 8) {
+                Some(byte) => byte,
+                None => {
+                    return Err(format!(
+                        "{}: does not fit in a {} bytes frame",
+                        self.name,
+                        data.len()
+                    ))
+                },
+            };
+            if raw & (1 << idx) != 0 {
+                *byte |= 1 << (bit % 8);
+            } else {
+                *byte &= !(1 << (bit % 8));
+            }
+        }
+        Ok(())
+    }
+
+    /// Frame bit positions of the signal, least significant bit first.
+    ///
+    /// Intel signals start at their LSB and grow upward. Motorola signals start at their MSB
+    /// and follow the DBC sawtooth numbering (7..0, 15..8, ...).
+    fn bits(&self) -> Vec<usize> {
+        let mut bits = Vec::with_capacity(self.size as usize);
+        let mut pos = self.start as usize;
+        for _ in 0..self.size {
+            bits.push(pos);
+            if self.little_endian {
+                pos += 1;
+            } else if pos.is_multiple_of(8) {
+                pos += 15;
+            } else {
+                pos -= 1;
+            }
+        }
+        if !self.little_endian {
+            bits.reverse();
+        }
+        bits
+    }
+}
+
+impl DbcMessageDef {
+    /// Value of the multiplexer selector in `data`, if the message is multiplexed.
+    pub fn mux_value(&self, data: &[u8]) -> Option<u64> {
+        self.signals.iter().find(|signal| signal.mux == DbcMux::Multiplexor)?.raw(data)
+    }
+
+    /// Find a signal by its DBC name or by its verb name (see `verb_name`).
+    pub fn signal(&self, name: &str) -> Option<&DbcSignalDef> {
+        self.signals
+            .iter()
+            .find(|signal| signal.name == name || verb_name(&signal.name) == name)
+    }
+
+    /// Frame holding every signal at its DBC start value (`GenSigStartValue`, 0 otherwise).
+    pub fn default_frame(&self) -> Vec<u8> {
+        let mut data = vec![0; self.dlc as usize];
+        let mux = self
+            .signals
+            .iter()
+            .find(|signal| signal.mux == DbcMux::Multiplexor)
+            .map(|signal| signal.initial.unwrap_or(0));
+        for signal in &self.signals {
+            if let (Some(raw), true) = (signal.initial, self.is_active(signal, mux)) {
+                let _ = signal.insert(raw, &mut data);
+            }
+        }
+        data
+    }
+
+    /// Encode physical `values` (signal name, value) into `data`.
+    ///
+    /// Multiplexed signals also select their page on the multiplexer; values from
+    /// different pages are rejected. Nothing is written when one value is invalid.
+    pub fn encode(&self, data: &mut [u8], values: &[(String, f64)]) -> Result<(), String> {
+        let mut page: Option<u64> = None;
+        let mut raws = Vec::with_capacity(values.len());
+        for (name, value) in values {
+            let signal = match self.signal(name) {
+                Some(signal) => signal,
+                None => return Err(format!("unknown signal '{}' in {}", name, self.name)),
+            };
+            let raw = signal.to_raw(*value)?;
+            let needed = match signal.mux {
+                DbcMux::Plain => None,
+                DbcMux::Multiplexor => Some(raw),
+                DbcMux::Multiplexed(value) => Some(value),
+            };
+            if let Some(needed) = needed {
+                if page.is_some_and(|page| page != needed) {
+                    return Err(format!(
+                        "signals from different multiplexer pages in {}",
+                        self.name
+                    ));
+                }
+                page = Some(needed);
+            }
+            raws.push((signal, raw));
+        }
+
+        let multiplexor = self.signals.iter().find(|signal| signal.mux == DbcMux::Multiplexor);
+        if let (Some(page), Some(multiplexor)) = (page, multiplexor) {
+            multiplexor.insert(page, data)?;
+        }
+        for (signal, raw) in raws {
+            signal.insert(raw, data)?;
+        }
+        Ok(())
+    }
+
+    /// Return true when `signal` is carried by a frame whose multiplexer holds `mux`.
+    pub fn is_active(&self, signal: &DbcSignalDef, mux: Option<u64>) -> bool {
+        match signal.mux {
+            DbcMux::Multiplexed(value) => mux == Some(value),
+            _ => true,
+        }
+    }
+}
+
+/// Convert a DBC identifier into the verb name used by generated pools
+/// (`MUX_signal` -> `MuxSignal`).
+pub fn verb_name(name: &str) -> String {
+    name.split(|chr: char| chr == '_' || chr == '-' || chr.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => {
+                    first.to_uppercase().chain(chars.as_str().to_lowercase().chars()).collect()
+                },
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Parse the text of a DBC file.
+///
+/// Errors name the offending line (e.g. `line 42: invalid signal layout '3|x@1+'`).
+pub fn parse_dbc(text: &str) -> Result<DbcFile, String> {
+    let mut dbc = DbcFile::default();
+    let mut in_string = false;
+    let mut comment: Option<(DbcComment, String)> = None;
+
+    for (lineno, line) in text.lines().enumerate() {
+        let lineno = lineno + 1;
+        let line = line.trim();
+
+        // Skip the continuation lines of multi-line strings (attributes), collect comments.
+        let quotes = line.matches('"').count();
+        if in_string {
+            in_string = quotes % 2 == 0;
+            if let Some((_, text)) = comment.as_mut() {
+                text.push('\n');
+                text.push_str(line.split('"').next().unwrap_or_default());
+            }
+            if !in_string {
+                if let Some((target, text)) = comment.take() {
+                    set_comment(&mut dbc, target, text);
+                }
+            }
+            continue;
+        }
+
+        if let Some(rest) = line.strip_prefix("CM_ ") {
+            let (target, text) =
+                parse_comment(rest).map_err(|info| format!("line {}: {}", lineno, info))?;
+            in_string = quotes % 2 == 1;
+            match target {
+                Some(target) if in_string => comment = Some((target, text)),
+                Some(target) => set_comment(&mut dbc, target, text),
+                None => {},
+            }
+        } else if let Some(rest) = line.strip_prefix("BO_ ") {
+            let message =
+                parse_message(rest).map_err(|info| format!("line {}: {}", lineno, info))?;
+            dbc.messages.push(message);
+        } else if let Some(rest) = line.strip_prefix("SG_ ") {
+            let signal = parse_signal(rest).map_err(|info| format!("line {}: {}", lineno, info))?;
+            match dbc.messages.last_mut() {
+                Some(message) => message.signals.push(signal),
+                None => return Err(format!("line {}: signal outside of any message", lineno)),
+            }
+        } else if let Some(rest) = line.strip_prefix("SIG_VALTYPE_ ") {
+            parse_valtype(&mut dbc, rest).map_err(|info| format!("line {}: {}", lineno, info))?;
+        } else if let Some(rest) = line.strip_prefix("BA_ \"GenSigStartValue\" SG_ ") {
+            parse_start_value(&mut dbc, rest)
+                .map_err(|info| format!("line {}: {}", lineno, info))?;
+        } else if let Some(rest) = line.strip_prefix("BA_ \"GenMsgCycleTime\" BO_ ") {
+            parse_cycle_time(&mut dbc, rest)
+                .map_err(|info| format!("line {}: {}", lineno, info))?;
+        } else if let Some(rest) = line.strip_prefix("VAL_ ") {
+            parse_value_table(&mut dbc, rest)
+                .map_err(|info| format!("line {}: {}", lineno, info))?;
+        } else {
+            in_string = quotes % 2 == 1;
+        }
+    }
+
+    dbc.messages.retain(|message| message.id != DBC_INDEPENDENT_SIG_MSG);
+    Ok(dbc)
+}
+
+/// `BO_ <id> <name>: <dlc> <sender>`
+fn parse_message(rest: &str) -> Result<DbcMessageDef, String> {
+    let (head, tail) = rest.split_once(':').ok_or("missing ':' in message")?;
+    let mut head = head.split_whitespace();
+    let mut tail = tail.split_whitespace();
+
+    let id = head.next().ok_or("missing message id")?;
+    let id = id.parse::<u32>().map_err(|_| format!("invalid message id '{}'", id))?;
+    let name = head.next().ok_or("missing message name")?.to_string();
+    let dlc = tail.next().ok_or("missing message dlc")?;
+    let dlc = dlc.parse::<u8>().map_err(|_| format!("invalid message dlc '{}'", dlc))?;
+    let sender = tail.next().unwrap_or_default().to_string();
+
+    Ok(DbcMessageDef { id, name, dlc, sender, signals: Vec::new(), cycle: None, comment: None })
+}
+
+/// `SG_ <name> [M|mN] : <start>|<size>@<order><sign> (<factor>,<offset>) [<min>|<max>] "<unit>" <receivers>`
+///
+/// Extended multiplexing (`mNM`, a multiplexed multiplexor) is rejected: pages are only
+/// selected by the single top-level multiplexor.
+fn parse_signal(rest: &str) -> Result<DbcSignalDef, String> {
+    let (head, tail) = rest.split_once(':').ok_or("missing ':' in signal")?;
+    let mut head = head.split_whitespace();
+    let name = head.next().ok_or("missing signal name")?.to_string();
+    let mux = match head.next() {
+        None => DbcMux::Plain,
+        Some("M") => DbcMux::Multiplexor,
+        Some(token) if token.starts_with('m') && token.ends_with('M') => {
+            return Err(format!("extended multiplexing unsupported '{}'", token))
+        },
+        Some(token) => {
+            let value = token
+                .strip_prefix('m')
+                .and_then(|value| value.parse::<u64>().ok())
+                .ok_or(format!("invalid multiplexer '{}'", token))?;
+            DbcMux::Multiplexed(value)
+        },
+    };
+
+    let mut tail = tail.trim().splitn(2, ' ');
+    let layout = tail.next().unwrap_or_default();
+    let tail = tail.next().unwrap_or_default().trim();
+    let invalid_layout = || format!("invalid signal layout '{}'", layout);
+    let (start, layout_rest) = layout.split_once('|').ok_or_else(invalid_layout)?;
+    let (size, order) = layout_rest.split_once('@').ok_or_else(invalid_layout)?;
+    let start = start.parse::<u16>().map_err(|_| invalid_layout())?;
+    let size = size.parse::<u16>().map_err(|_| invalid_layout())?;
+    let (little_endian, signed) = match order {
+        "1+" => (true, false),
+        "1-" => (true, true),
+        "0+" => (false, false),
+        "0-" => (false, true),
+        _ => return Err(invalid_layout()),
+    };
+    if size == 0 || size > 64 {
+        return Err(invalid_layout());
+    }
+
+    let (scaling, tail) = between(tail, '(', ')').ok_or("missing signal (factor,offset)")?;
+    let (factor, offset) = pair(scaling, ',').ok_or(format!("invalid scaling '({})'", scaling))?;
+    let (range, tail) = between(tail, '[', ']').ok_or("missing signal [min|max]")?;
+    let (min, max) = pair(range, '|').ok_or(format!("invalid range '[{}]'", range))?;
+    let (unit, _receivers) = between(tail, '"', '"').unwrap_or(("", tail));
+
+    Ok(DbcSignalDef {
+        name,
+        start,
+        size,
+        little_endian,
+        signed,
+        float: false,
+        factor,
+        offset,
+        min,
+        max,
+        unit: unit.to_string(),
+        mux,
+        initial: None,
+        labels: Vec::new(),
+        comment: None,
+    })
+}
+
+/// `SIG_VALTYPE_ <id> <signal> : <1|2>;` marks IEEE float (32 bits) or double (64 bits) signals.
+fn parse_valtype(dbc: &mut DbcFile, rest: &str) -> Result<(), String> {
+    let rest = rest.trim_end_matches(';');
+    let (head, kind) = rest.split_once(':').ok_or("missing ':' in signal value type")?;
+    let mut head = head.split_whitespace();
+    let id = head.next().and_then(|id| id.parse::<u32>().ok()).ok_or("invalid message id")?;
+    let name = head.next().ok_or("missing signal name")?;
+    let float = matches!(kind.trim(), "1" | "2");
+
+    if let Some(signal) = find_signal(dbc, id, name) {
+        signal.float = float;
+    }
+    Ok(())
+}
+
+/// `BA_ "GenSigStartValue" SG_ <id> <signal> <raw value>;`
+fn parse_start_value(dbc: &mut DbcFile, rest: &str) -> Result<(), String> {
+    let mut rest = rest.trim_end_matches(';').split_whitespace();
+    let id = rest.next().and_then(|id| id.parse::<u32>().ok()).ok_or("invalid message id")?;
+    let name = rest.next().ok_or("missing signal name")?;
+    let value = rest.next().ok_or("missing start value")?;
+    let value = value.parse::<f64>().map_err(|_| format!("invalid start value '{}'", value))?;
+
+    if let Some(signal) = find_signal(dbc, id, name) {
+        signal.initial = Some(value as i64 as u64 & raw_mask(signal.size));
+    }
+    Ok(())
+}
+
+/// `BA_ "GenMsgCycleTime" BO_ <id> <ms>;`
+fn parse_cycle_time(dbc: &mut DbcFile, rest: &str) -> Result<(), String> {
+    let mut rest = rest.trim_end_matches(';').split_whitespace();
+    let id = rest.next().and_then(|id| id.parse::<u32>().ok()).ok_or("invalid message id")?;
+    let value = rest.next().ok_or("missing cycle time")?;
+    let value = value.parse::<u64>().map_err(|_| format!("invalid cycle time '{}'", value))?;
+
+    for message in dbc.messages.iter_mut().filter(|message| message.id == id) {
+        // A 0 ms cycle is what DBC editors write for event driven messages.
+        message.cycle = if value > 0 { Some(value) } else { None };
+    }
+    Ok(())
+}
+
+/// Object a `CM_` comment is attached to.
+enum DbcComment {
+    Message(u32),
+    Signal(u32, String),
+}
+
+/// `CM_ [BO_ <id>|SG_ <id> <signal>|BU_ <node>] "<text>";`
+///
+/// Returns the target (None for network and node comments) and the text found on this
+/// line; the caller appends the following lines of multi-line comments.
+fn parse_comment(rest: &str) -> Result<(Option<DbcComment>, String), String> {
+    let (head, text) = rest.split_once('"').ok_or("missing comment text")?;
+    let text = text.split('"').next().unwrap_or_default().to_string();
+    let mut head = head.split_whitespace();
+    let target = match head.next() {
+        Some("BO_") => {
+            let id = head.next().and_then(|id| id.parse::<u32>().ok());
+            Some(DbcComment::Message(id.ok_or("invalid message id")?))
+        },
+        Some("SG_") => {
+            let id = head.next().and_then(|id| id.parse::<u32>().ok());
+            let name = head.next().ok_or("missing signal name")?;
+            Some(DbcComment::Signal(id.ok_or("invalid message id")?, name.to_string()))
+        },
+        _ => None,
+    };
+    Ok((target, text))
+}
+
+fn set_comment(dbc: &mut DbcFile, target: DbcComment, text: String) {
+    match target {
+        DbcComment::Message(id) => {
+            for message in dbc.messages.iter_mut().filter(|message| message.id == id) {
+                message.comment = Some(text.clone());
+            }
+        },
+        DbcComment::Signal(id, name) => {
+            if let Some(signal) = find_signal(dbc, id, &name) {
+                signal.comment = Some(text);
+            }
+        },
+    }
+}
+
+/// `VAL_ <id> <signal> <raw> "<label>" ... ;`
+fn parse_value_table(dbc: &mut DbcFile, rest: &str) -> Result<(), String> {
+    let rest = rest.trim().trim_end_matches(';');
+    let mut head = rest.splitn(3, char::is_whitespace);
+    let id = head.next().and_then(|id| id.parse::<u32>().ok()).ok_or("invalid message id")?;
+    let name = head.next().ok_or("missing signal name")?;
+
+    let mut labels = Vec::new();
+    let mut tail = head.next().unwrap_or_default().trim();
+    while !tail.is_empty() {
+        let (raw, rest) = tail.split_once('"').ok_or("missing value label")?;
+        let raw = raw.trim();
+        let raw = raw.parse::<i64>().map_err(|_| format!("invalid table value '{}'", raw))?;
+        let (label, rest) = rest.split_once('"').ok_or("unterminated value label")?;
+        labels.push((raw, label.to_string()));
+        tail = rest.trim();
+    }
+
+    if let Some(signal) = find_signal(dbc, id, name) {
+        signal.labels = labels;
+    }
+    Ok(())
+}
+
+fn find_signal<'a>(dbc: &'a mut DbcFile, id: u32, name: &str) -> Option<&'a mut DbcSignalDef> {
+    dbc.messages
+        .iter_mut()
+        .filter(|message| message.id == id)
+        .flat_map(|message| message.signals.iter_mut())
+        .find(|signal| signal.name == name)
+}
+
+/// Mask covering the `size` low bits of a raw value.
+fn raw_mask(size: u16) -> u64 {
+    if size >= 64 {
+        u64::MAX
+    } else {
+        (1 << size) - 1
+    }
+}
+
+/// Split `text` on the first `open`..`close` pair, returning the inner text and the remainder.
+fn between(text: &str, open: char, close: char) -> Option<(&str, &str)> {
+    let start = text.find(open)? + open.len_utf8();
+    let end = start + text[start..].find(close)?;
+    Some((&text[start..end], &text[end + close.len_utf8()..]))
+}
+
+/// Parse `a<sep>b` as two floats.
+fn pair(text: &str, sep: char) -> Option<(f64, f64)> {
+    let (first, second) = text.split_once(sep)?;
+    Some((first.trim().parse().ok()?, second.trim().parse().ok()?))
+}
@@ -0,0 +1,297 @@
+/*
+ * Copyright (C) 2015-2023 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * $RP_BEGIN_LICENSE$
+ * Commercial License Usage
+ *  Licensees holding valid commercial IoT.bzh licenses may use this file in
+ *  accordance with the commercial license agreement provided with the
+ *  Software or, alternatively, in accordance with the terms contained in
+ *  a written agreement between you and The IoT.bzh Company. For licensing terms
+ *  and conditions see https://www.iot.bzh/terms-conditions. For further
+ *  information use the contact form at https://www.iot.bzh/contact.
+ *
+ * GNU General Public License Usage
+ *  Alternatively, this file may be used under the terms of the GNU General
+ *  Public license version 3. This license is as published by the Free Software
+ *  Foundation and appearing in the file LICENSE.GPLv3 included in the packaging
+ *  of this file. Please review the following information to ensure the GNU
+ *  General Public License requirements will be met
+ *  https://www.gnu.org/licenses/gpl-3.0.html.
+ * $RP_END_LICENSE$
+ */
+
+//! `CanDbcPool` built at runtime from a DBC file.
+//!
+//! Generated pools hold one Rust type per message; here every message and signal shares
+//! the same types and carries its `dbcfile` definition. Mutable state lives behind
+//! `Cell`/`RefCell` so a frame can be applied while `create_pool_verbs` holds shared
+//! borrows of the pool cells, exactly like the notification callbacks expect.
+
+use crate::dbcfile::{self, DbcFile, DbcMessageDef, DbcSignalDef};
+use afbv4::prelude::*;
+
+use sockcan::prelude::{
+    CanDataStatus, CanDbcMessage, CanDbcPool, CanDbcSignal, CanDbcType, CanError, CanMsgCtrl,
+    CanMsgData, CanSigCtrl,
+};
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Read and parse the DBC file at `path`.
+pub(crate) fn load_dbc(path: &str) -> Result<DbcFile, AfbError> {
+    let text = match std::fs::read(path) {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(error) => {
+            return Err(AfbError::new("fail-dbc-read", 0, format!("{}: {}", path, error)))
+        },
+    };
+    match dbcfile::parse_dbc(&text) {
+        Ok(dbc) => Ok(dbc),
+        Err(info) => Err(AfbError::new("fail-dbc-parse", 0, format!("{}: {}", path, info))),
+    }
+}
+
+/// Mutable state of one signal, shared between its pool cell and its message.
+struct SignalState {
+    def: DbcSignalDef,
+    value: f64,
+    stamp: u64,
+    status: CanDataStatus,
+    callback: Option<Box<dyn CanSigCtrl>>,
+}
+
+/// Signal handle stored in the pool cells.
+struct DynSignal {
+    name: &'static str,
+    state: Rc<RefCell<SignalState>>,
+}
+
+impl CanDbcSignal for DynSignal {
+    fn get_name(&self) -> &'static str {
+        self.name
+    }
+
+    fn get_stamp(&self) -> u64 {
+        self.state.borrow().stamp
+    }
+
+    fn get_status(&self) -> CanDataStatus {
+        self.state.borrow().status
+    }
+
+    /// One bit unscaled signals are reported as booleans, every other one as a float.
+    fn get_value(&self) -> CanDbcType {
+        let state = self.state.borrow();
+        let def = &state.def;
+        if def.size == 1 && !def.signed && def.factor == 1.0 && def.offset == 0.0 {
+            CanDbcType::Bool(state.value != 0.0)
+        } else {
+            CanDbcType::F64(state.value)
+        }
+    }
+
+    fn reset(&mut self) {
+        let mut state = self.state.borrow_mut();
+        state.value = 0.0;
+        state.stamp = 0;
+        state.status = CanDataStatus::Unset;
+    }
+
+    fn set_callback(&mut self, callback: Box<dyn CanSigCtrl>) {
+        self.state.borrow_mut().callback = Some(callback);
+    }
+}
+
+/// Mutable state of one message, shared between its pool cell and the pool index.
+struct MessageState {
+    name: &'static str,
+    def: DbcMessageDef,
+    stamp: Cell<u64>,
+    status: Cell<CanDataStatus>,
+    signals: Vec<Rc<RefCell<Box<dyn CanDbcSignal>>>>,
+    states: Vec<Rc<RefCell<SignalState>>>,
+    callback: RefCell<Option<Box<dyn CanMsgCtrl>>>,
+}
+
+impl MessageState {
+    fn new(def: DbcMessageDef) -> Self {
+        let mut signals: Vec<Rc<RefCell<Box<dyn CanDbcSignal>>>> = Vec::new();
+        let mut states = Vec::new();
+        for sigdef in &def.signals {
+            let state = Rc::new(RefCell::new(SignalState {
+                def: sigdef.clone(),
+                value: 0.0,
+                stamp: 0,
+                status: CanDataStatus::Unset,
+                callback: None,
+            }));
+            let name = to_static_str(dbcfile::verb_name(&sigdef.name));
+            signals.push(Rc::new(RefCell::new(Box::new(DynSignal { name, state: state.clone() }))));
+            states.push(state);
+        }
+
+        MessageState {
+            name: to_static_str(dbcfile::verb_name(&def.name)),
+            def,
+            stamp: Cell::new(0),
+            status: Cell::new(CanDataStatus::Unset),
+            signals,
+            states,
+            callback: RefCell::new(None),
+        }
+    }
+
+    /// Decode `frame` into the signals carried by its multiplexer page, then notify the
+    /// signal controllers and the message controller with `handle`.
+    fn apply(&self, handle: &dyn CanDbcMessage, frame: &CanMsgData) {
+        let mux = self.def.mux_value(frame.data);
+        for (signal, state) in self.signals.iter().zip(self.states.iter()) {
+            {
+                let mut state = state.borrow_mut();
+                if !self.def.is_active(&state.def, mux) {
+                    continue;
+                }
+                let value = match state.def.decode(frame.data) {
+                    Some(value) => value,
+                    None => continue, // signal beyond a short frame
+                };
+                state.status = if state.status == CanDataStatus::Unset || value != state.value {
+                    CanDataStatus::Updated
+                } else {
+                    CanDataStatus::Unchanged
+                };
+                state.value = value;
+                state.stamp = frame.stamp;
+            }
+
+            let state = state.borrow();
+            if let (Some(callback), Ok(signal)) = (&state.callback, signal.try_borrow()) {
+                callback.sig_notification(&**signal);
+            }
+        }
+
+        self.stamp.set(frame.stamp);
+        self.status.set(CanDataStatus::Updated);
+        if let Some(callback) = self.callback.borrow().as_ref() {
+            callback.msg_notification(handle);
+        }
+    }
+}
+
+/// Message handle stored in the pool cells.
+struct DynMessage {
+    state: Rc<MessageState>,
+}
+
+impl CanDbcMessage for DynMessage {
+    fn get_name(&self) -> &'static str {
+        self.state.name
+    }
+
+    fn get_id(&self) -> u32 {
+        self.state.def.id
+    }
+
+    fn get_stamp(&self) -> u64 {
+        self.state.stamp.get()
+    }
+
+    fn get_status(&self) -> CanDataStatus {
+        self.state.status.get()
+    }
+
+    fn get_signals(&self) -> &[Rc<RefCell<Box<dyn CanDbcSignal>>>] {
+        &self.state.signals
+    }
+
+    fn set_callback(&mut self, callback: Box<dyn CanMsgCtrl>) {
+        self.state.callback.replace(Some(callback));
+    }
+
+    fn reset(&mut self) -> Result<(), CanError> {
+        for signal in &self.state.signals {
+            match signal.try_borrow_mut() {
+                Ok(mut signal) => signal.reset(),
+                Err(_) => return Err(CanError::new("dbc-reset-fail", "signal cell already used")),
+            }
+        }
+        self.state.stamp.set(0);
+        self.state.status.set(CanDataStatus::Unset);
+        Ok(())
+    }
+}
+
+/// Message pool loaded from a DBC file when the binding starts.
+///
+/// It is a drop-in replacement for the `CanMsgPool` generated by `dbcparser`: pass it to
+/// `create_pool_verbs` and the same verbs, groups and events are registered.
+pub struct DbcDynPool {
+    uid: &'static str,
+    messages: Vec<Rc<RefCell<Box<dyn CanDbcMessage>>>>,
+    states: Vec<Rc<MessageState>>,
+    index: HashMap<u32, usize>,
+}
+
+impl DbcDynPool {
+    /// Build the pool from the text of a DBC file.
+    pub fn new(uid: &'static str, text: &str) -> Result<Self, AfbError> {
+        match dbcfile::parse_dbc(text) {
+            Ok(dbc) => DbcDynPool::build(uid, dbc),
+            Err(info) => Err(AfbError::new("fail-dbc-parse", 0, info)),
+        }
+    }
+
+    /// Load and parse the DBC file at `path`.
+    pub fn from_file(uid: &'static str, path: &str) -> Result<Self, AfbError> {
+        DbcDynPool::build(uid, load_dbc(path)?)
+    }
+
+    pub(crate) fn build(uid: &'static str, dbc: DbcFile) -> Result<Self, AfbError> {
+        let mut pool =
+            DbcDynPool { uid, messages: Vec::new(), states: Vec::new(), index: HashMap::new() };
+        for def in dbc.messages {
+            if pool.index.insert(def.id, pool.messages.len()).is_some() {
+                return Err(AfbError::new(
+                    "fail-dbc-parse",
+                    0,
+                    format!("duplicated message canid:{}", def.id),
+                ));
+            }
+            let state = Rc::new(MessageState::new(def));
+            let message: Box<dyn CanDbcMessage> = Box::new(DynMessage { state: state.clone() });
+            pool.messages.push(Rc::new(RefCell::new(message)));
+            pool.states.push(state);
+        }
+        Ok(pool)
+    }
+}
+
+impl CanDbcPool for DbcDynPool {
+    fn get_messages(&self) -> &[Rc<RefCell<Box<dyn CanDbcMessage>>>] {
+        &self.messages
+    }
+
+    fn update(&self, data: &CanMsgData) -> Result<Rc<RefCell<Box<dyn CanDbcMessage>>>, CanError> {
+        let idx = match self.index.get(&data.canid) {
+            Some(idx) => *idx,
+            None => {
+                return Err(CanError::new(
+                    self.uid,
+                    format!("unknown message canid:{}", data.canid),
+                ))
+            },
+        };
+
+        // Frames are applied through a shared borrow: message state uses interior mutability,
+        // and a concurrent `reset` (mutable borrow) makes the frame fail instead of panicking.
+        let cell = &self.messages[idx];
+        match cell.try_borrow() {
+            Ok(message) => self.states[idx].apply(&**message, data),
+            Err(_) => return Err(CanError::new(self.uid, "message cell already used")),
+        }
+        Ok(cell.clone())
+    }
+}
@@ -26,6 +26,13 @@
     html_favicon_url = "https://iot.bzh/images/defaults/favicon.ico"
 )]
 
+pub mod dbcfile;
+mod dynpool;
+
+pub use dynpool::DbcDynPool;
+
+use dbcfile::{DbcFile, DbcMessageDef, DbcMux, DbcSignalDef};
+
 // TODO: explain – clarify units for rate/watchdog (milliseconds vs microseconds) and rationale behind default values.
 const MSG_DFT_RATE: u64 = 500;
 const MSG_DFT_WATCHDOG: u64 = 10000;
@@ -34,15 +41,22 @@ const MSG_DFT_WATCHDOG: u64 = 10000;
 use afbv4::prelude::*;
 
 use sockcan::prelude::{
-    CanDataStatus, CanDbcMessage, CanDbcPool, CanDbcSignal, CanMsgCtrl, CanMsgData, CanSigCtrl,
+    CanBcmFlag, CanBcmOpCode, CanDataStatus, CanDbcMessage, CanDbcPool, CanDbcSignal, CanDbcType,
+    CanError, CanMsgCtrl, CanMsgData, CanSigCtrl, CanTimeStamp, SockBcmCmd, SockCanHandle,
 };
 
 use sockdata::types::{
-    sockdata_register, CanBcmData, DataBcmMsg, DataBcmSig, SubscribeFlag, SubscribeParam,
+    sockdata_register, CanAlarmStatus, CanBcmAlive, CanBcmData, CanBcmTimeout, DataBcmAlarm,
+    DataBcmAlarms, DataBcmCatalog, DataBcmHistory, DataBcmMsg, DataBcmMsgInfo, DataBcmSample,
+    DataBcmSig, DataBcmSigInfo, DataBcmSigSet, DataBcmSigStats, SendParam, SockcanAlarmSeverity,
+    SockcanBindingConfig, SockcanHistoryConfig, SockcanMsgConfig, SockcanTxConfig, SubscribeFlag,
+    SubscribeParam, UnSubscribeParam,
 };
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 /// Runtime info associated with a pool of signals/messages.
 ///
@@ -55,15 +69,326 @@ struct PoolInfoCtx {
     watchdog: u64,
     listeners: i32,
     flag: SubscribeFlag,
+    matches: Option<Vec<f64>>,
+    filter: Option<logic::ValueFilter>,
+}
+
+/// Event and throttling state of one subscriber session.
+///
+/// Each session gets its own event, named like the signal event so clients keep listening
+/// to the same name, and a client asking for fast updates does not speed up the others.
+/// The event is released with the subscription.
+struct Subscriber {
+    event: &'static AfbEvent,
+    info: PoolInfoCtx,
+}
+
+impl Subscriber {
+    /// Apply the `match`, throttling and value filters of this subscriber to one update.
+    fn accept(&mut self, status: CanDataStatus, stamp: u64, value: Option<f64>) -> bool {
+        let info = &mut self.info;
+        if status == CanDataStatus::Updated
+            && !logic::value_selected(info.matches.as_deref(), value)
+        {
+            return false;
+        }
+
+        let mut emit = logic::should_emit(
+            status,
+            stamp,
+            info.stamp,
+            info.rate,
+            info.watchdog,
+            info.flag.clone(),
+        );
+
+        // Deadband and threshold filters only see updates that passed the rate gate.
+        if let (true, CanDataStatus::Updated, Some(filter), Some(value)) =
+            (emit, status, info.filter.as_mut(), value)
+        {
+            emit = filter.accept(value);
+        }
+        if emit {
+            info.stamp = stamp;
+        }
+        emit
+    }
+}
+
+/// Subscribers of a signal, keyed by session id.
+type Subscribers = RefCell<HashMap<u64, Subscriber>>;
+
+/// Register a runtime event, created by a verb for its caller.
+fn runtime_event(request: &AfbRequest, uid: &'static str) -> Result<&'static AfbEvent, AfbError> {
+    let event = AfbEvent::new(uid);
+    if event.register(request.get_api().get_apiv4()) < 0 {
+        let error =
+            AfbError::new("evt-fail-registration", 0, format!("evt-fail-registration uid:{}", uid));
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
+    event.finalize()
+}
+
+/// Subscribe the caller to its own `uid` event, created on first use, and (re)set its
+/// throttling and filters.
+fn subscribe_session(
+    request: &AfbRequest,
+    subscribers: &Subscribers,
+    session: u64,
+    uid: &'static str,
+    demand: logic::Demand,
+    matches: Option<Vec<f64>>,
+    filter: Option<logic::ValueFilter>,
+) -> Result<(), AfbError> {
+    let mut subscribers = match subscribers.try_borrow_mut() {
+        Ok(value) => value,
+        Err(_) => {
+            let error = AfbError::new(
+                "fail-borrow-subscribers",
+                0,
+                "internal pool error (subscribers cell already used)",
+            );
+            return Err(afb_add_trace!(error));
+        },
+    };
+    let event = match subscribers.get(&session) {
+        Some(subscriber) => subscriber.event,
+        None => runtime_event(request, uid)?,
+    };
+    event.subscribe(request)?;
+
+    // A new subscribe of the same session replaces its previous parameters.
+    let info = PoolInfoCtx {
+        stamp: 0,
+        rate: demand.rate,
+        watchdog: demand.watchdog,
+        listeners: 0,
+        flag: demand.flag,
+        matches,
+        filter,
+    };
+    subscribers.insert(session, Subscriber { event, info });
+    Ok(())
+}
+
+/// Unsubscribe the caller from its own event, if it has one, and release the event.
+fn unsubscribe_session(
+    request: &AfbRequest,
+    subscribers: &Subscribers,
+    session: u64,
+) -> Result<(), AfbError> {
+    let subscriber = match subscribers.try_borrow_mut() {
+        Ok(mut subscribers) => subscribers.remove(&session),
+        Err(_) => {
+            let error = AfbError::new(
+                "fail-borrow-subscribers",
+                0,
+                "internal pool error (subscribers cell already used)",
+            );
+            return Err(afb_add_trace!(error));
+        },
+    };
+    if let Some(subscriber) = subscriber {
+        let status = subscriber.event.unsubscribe(request);
+        subscriber.event.unref();
+        status?;
+    }
+    Ok(())
+}
+
+/// Release the event of a session closing without unsubscribing.
+fn release_session(subscribers: &Subscribers, session: u64) {
+    if let Ok(mut subscribers) = subscribers.try_borrow_mut() {
+        if let Some(subscriber) = subscribers.remove(&session) {
+            subscriber.event.unref();
+        }
+    }
+}
+
+/// Owner of a subscription: client session id (0 for the binding itself) and the uid of
+/// the verb or event it subscribed through.
+type DemandKey = (u64, &'static str);
+
+// Register the dbcapi session context, `dbc_session_closing` drops what a session leaves.
+AfbSessionRegister!(DbcSessionCtx, dbc_session_closing);
+
+/// Client session of the DBC API.
+///
+/// `leases` releases the subscriptions of the session, keyed by verb, when it closes
+/// without unsubscribing.
+struct DbcSessionCtx {
+    id: u64,
+    leases: HashMap<&'static str, Box<dyn Fn(u64)>>,
+}
+
+/// Session closing callback.
+///
+/// Closing callbacks cannot subcall: released messages are flagged, and the next frame of
+/// each one aligns its backend subscription (`MessageDataCtx::resync_demand`).
+fn dbc_session_closing(session: &mut DbcSessionCtx) {
+    let id = session.id;
+    for (_, release) in session.leases.drain() {
+        release(id);
+    }
+}
+
+static SESSION_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Return the context of the caller session, creating it on first use.
+fn dbc_session(request: &AfbRequest) -> Result<&mut DbcSessionCtx, AfbError> {
+    match DbcSessionCtx::get(request) {
+        Ok(session) => Ok(session),
+        Err(_) => {
+            let id = SESSION_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+            DbcSessionCtx::set(request, DbcSessionCtx { id, leases: HashMap::new() })
+        },
+    }
 }
 
-/// Per-signal runtime data (throttle, watchdog, subscribers, event handle).
+/// Per-signal runtime data (subscribers, event handle).
 ///
-/// This structure is shared between the signal PoolInfoCtxpool controller and the verb
-/// handlers to coordinate throttling and subscription state.
+/// This structure is shared between the signal pool controller and the verb handlers.
+/// `event` is the API event of the signal; clients receive updates on per-session events
+/// of the same name held by `subscribers`.
 struct SigDataCtx {
-    info: RefCell<PoolInfoCtx>,
+    uid: &'static str,
+    subscribers: Subscribers,
+    event: &'static AfbEvent,
+    def: Option<DbcSignalDef>,
+    alarms: Vec<Rc<Alarm>>,
+    virtuals: Vec<(Rc<VirtualSignal>, usize)>,
+    history: Option<RefCell<logic::History>>,
+    stats: RefCell<SigStats>,
+}
+
+/// Statistics event of one subscriber session, pushed every `period` ms with the
+/// aggregates of the last `span` ms.
+struct StatsSubscriber {
+    event: &'static AfbEvent,
+    period: u64,
+    span: u64,
+    stamp: u64,
+}
+
+/// Sliding window statistics of a signal, collected once a client asked for them.
+///
+/// Each stats subscription gets its own `uid` event in `subscribers`, with its period and
+/// window; records are named after the signal event (`name`).
+struct SigStats {
+    name: &'static str,
+    uid: &'static str,
+    samples: Option<logic::StatsWindow>,
+    subscribers: HashMap<u64, StatsSubscriber>,
+}
+
+/// Aggregates of the last `window` ms of `samples`, reported as `name`.
+fn stats_snapshot(
+    samples: Option<&logic::StatsWindow>,
+    name: &str,
+    window: u64,
+) -> DataBcmSigStats {
+    let (stamp, stats) = match samples {
+        Some(samples) => (samples.newest(), samples.compute(window)),
+        None => (0, logic::Stats::default()),
+    };
+    DataBcmSigStats {
+        name: name.to_string(),
+        stamp,
+        window,
+        count: stats.count,
+        min: stats.min,
+        max: stats.max,
+        mean: stats.mean,
+        stddev: stats.stddev,
+        rate: stats.rate,
+    }
+}
+
+impl SigStats {
+    fn snapshot(&self, name: &str, window: u64) -> DataBcmSigStats {
+        stats_snapshot(self.samples.as_ref(), name, window)
+    }
+
+    /// Record a received value and push the statistics of every subscriber once per period.
+    fn push(&mut self, value: f64, stamp: u64) {
+        let samples = match self.samples.as_mut() {
+            Some(samples) => samples,
+            None => return,
+        };
+        samples.push(stamp, value);
+        for subscriber in self.subscribers.values_mut() {
+            if stamp.saturating_sub(subscriber.stamp) >= subscriber.period.saturating_mul(1000) {
+                subscriber.stamp = stamp;
+                subscriber
+                    .event
+                    .push(stats_snapshot(Some(&*samples), self.name, subscriber.span));
+            }
+        }
+    }
+
+    /// Drop the statistics event of `session`, returning it for a final unsubscribe.
+    fn release(&mut self, session: u64) -> Option<&'static AfbEvent> {
+        self.subscribers.remove(&session).map(|subscriber| subscriber.event)
+    }
+}
+
+/// Alarm rule from the `alarms` config section, attached to the signal it watches.
+struct Alarm {
+    uid: &'static str,
+    msg: RefCell<Option<Rc<MessageDataCtx>>>,
+    severity: SockcanAlarmSeverity,
+    info: &'static str,
+    rule: logic::AlarmRule,
+    state: RefCell<logic::AlarmState>,
     event: &'static AfbEvent,
+    attached: Cell<bool>,
+}
+
+impl Alarm {
+    fn snapshot(&self, state: &logic::AlarmState, status: CanAlarmStatus) -> DataBcmAlarm {
+        DataBcmAlarm {
+            uid: self.uid.to_string(),
+            signal: self.rule.signal.clone(),
+            severity: self.severity,
+            status,
+            active: state.active,
+            latched: state.latched,
+            value: state.value,
+            stamp: state.stamp,
+            info: self.info.to_string(),
+        }
+    }
+
+    /// Evaluate the rule on a new signal value and push the resulting state change.
+    fn evaluate(&self, value: f64, stamp: u64) {
+        let mut state = match self.state.try_borrow_mut() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        let status = match state.update(&self.rule, value, stamp) {
+            Some(logic::AlarmChange::Raised) => CanAlarmStatus::RAISED,
+            Some(logic::AlarmChange::Cleared) => CanAlarmStatus::CLEARED,
+            None => return,
+        };
+        self.event.push(self.snapshot(&state, status));
+    }
+}
+
+/// Snapshot of a signal, labelled with the value table of `def` when it has one.
+fn sig_snapshot(sig: &dyn CanDbcSignal, def: Option<&DbcSignalDef>) -> DataBcmSig {
+    let value = sig.get_value();
+    let label = match (def, logic::dbc_number(&value)) {
+        (Some(def), Some(number)) => def.label(number).map(str::to_string),
+        _ => None,
+    };
+    DataBcmSig {
+        name: sig.get_name().to_owned(),
+        stamp: sig.get_stamp(),
+        status: sig.get_status(),
+        value,
+        label,
+    }
 }
 
 /// Controller passed to the DBC signal to push notifications into AFB events.
@@ -76,43 +401,262 @@ struct SigPoolCtx {
 
 impl CanSigCtrl for SigPoolCtx {
     fn sig_notification(&self, sig: &dyn CanDbcSignal) -> i32 {
-        // Try to borrow mutably the per-signal info; if already borrowed, report error.
-        let mut info = match self.data.info.try_borrow_mut() {
+        // Try to borrow mutably the signal subscribers; if already borrowed, report error.
+        let mut subscribers = match self.data.subscribers.try_borrow_mut() {
             Err(_) => {
                 afb_log_msg!(
                     Critical,
                     self.data.event,
-                    "pool-sig-notification: failed to get event info"
+                    "pool-sig-notification: failed to get event subscribers"
                 );
                 return -1;
             },
-            Ok(info) => info,
+            Ok(subscribers) => subscribers,
+        };
+        let value = logic::dbc_number(&sig.get_value());
+
+        // Alarm rules watch every update, subscribed or not.
+        if sig.get_status() == CanDataStatus::Updated && !self.data.alarms.is_empty() {
+            if let Some(value) = value {
+                for alarm in &self.data.alarms {
+                    alarm.evaluate(value, sig.get_stamp());
+                }
+            }
+        }
+
+        // The history buffer keeps every value change, subscribed or not.
+        if let (CanDataStatus::Updated, Some(history), Some(value)) =
+            (sig.get_status(), &self.data.history, value)
+        {
+            if let Ok(mut history) = history.try_borrow_mut() {
+                history.push(sig.get_stamp(), value);
+            }
+        }
+
+        // Statistics count every received value, changed or not.
+        if sig.get_status() != CanDataStatus::Unset {
+            if let (Ok(mut stats), Some(value)) = (self.data.stats.try_borrow_mut(), value) {
+                stats.push(value, sig.get_stamp());
+            }
+        }
+
+        // Virtual signals read every decoded value; their message recomputes them.
+        if sig.get_status() != CanDataStatus::Unset {
+            if let Some(value) = value {
+                for (virt, slot) in &self.data.virtuals {
+                    virt.set_input(*slot, value);
+                }
+            }
+        }
+
+        // Every subscriber session gets the updates its own filters and throttling let through.
+        let mut listeners = 0;
+        for subscriber in subscribers.values_mut() {
+            if subscriber.accept(sig.get_status(), sig.get_stamp(), value) {
+                let signal = sig_snapshot(sig, self.data.def.as_ref());
+                subscriber.info.listeners = subscriber.event.push(signal);
+            }
+            listeners += subscriber.info.listeners;
+        }
+        listeners
+    }
+}
+
+/// DBC definition of a signal as returned by the `info` action.
+fn sig_info(def: &DbcSignalDef) -> DataBcmSigInfo {
+    let (multiplexor, multiplexed) = match def.mux {
+        DbcMux::Plain => (false, None),
+        DbcMux::Multiplexor => (true, None),
+        DbcMux::Multiplexed(value) => (false, Some(value)),
+    };
+    DataBcmSigInfo {
+        name: def.name.clone(),
+        verb: dbcfile::verb_name(&def.name),
+        start: def.start,
+        size: def.size,
+        byte_order: if def.little_endian { "intel" } else { "motorola" }.to_string(),
+        signed: def.signed,
+        float: def.float,
+        factor: def.factor,
+        offset: def.offset,
+        min: def.min,
+        max: def.max,
+        unit: def.unit.clone(),
+        multiplexor,
+        multiplexed,
+        values: def.labels.clone(),
+        comment: def.comment.clone(),
+    }
+}
+
+/// DBC definition of a message exposed by the `verb` message verb.
+fn msg_info(verb: &str, def: &DbcMessageDef) -> DataBcmMsgInfo {
+    DataBcmMsgInfo {
+        name: def.name.clone(),
+        verb: verb.to_string(),
+        canid: def.id,
+        dlc: def.dlc,
+        sender: def.sender.clone(),
+        cycle: def.cycle,
+        comment: def.comment.clone(),
+        signals: def.signals.iter().map(sig_info).collect(),
+    }
+}
+
+/// Error returned by `info` and `catalog` when no DBC layout is known.
+fn layout_error(what: impl std::fmt::Display) -> AfbError {
+    AfbError::new(
+        "fail-info-layout",
+        0,
+        format!("{} info requires the 'dbc' file in binding config", what),
+    )
+}
+
+/// Signal computed from the `virtuals` config section.
+///
+/// Input signals store their value in `values` (`SigDataCtx::virtuals` gives the slot), then
+/// the messages carrying them recompute the expression once the whole frame is decoded.
+struct VirtualSignal {
+    uid: &'static str,
+    expr: logic::Expr,
+    inputs: Vec<String>,
+    attached: RefCell<Vec<bool>>,
+    messages: RefCell<Vec<Rc<MessageDataCtx>>>,
+    default: logic::Demand,
+    values: RefCell<Vec<Option<f64>>>,
+    average: RefCell<Option<logic::MovingAverage>>,
+    subscribers: Subscribers,
+    value: Cell<Option<f64>>,
+    stamp: Cell<u64>,
+    status: Cell<CanDataStatus>,
+    event: &'static AfbEvent,
+}
+
+impl VirtualSignal {
+    fn set_input(&self, slot: usize, value: f64) {
+        if let Ok(mut values) = self.values.try_borrow_mut() {
+            values[slot] = Some(value);
+        }
+    }
+
+    fn snapshot(&self) -> DataBcmSig {
+        DataBcmSig {
+            name: self.uid.to_string(),
+            stamp: self.stamp.get(),
+            status: self.status.get(),
+            value: CanDbcType::F64(self.value.get().unwrap_or(0.0)),
+            label: None,
+        }
+    }
+
+    /// Recompute the value from the current inputs and push it like a real signal.
+    fn compute(&self, stamp: u64) {
+        let (values, mut subscribers) =
+            match (self.values.try_borrow(), self.subscribers.try_borrow_mut()) {
+                (Ok(values), Ok(subscribers)) => (values, subscribers),
+                _ => return,
+            };
+        let mut value = match self.expr.eval(&values) {
+            Some(value) => value,
+            None => return, // some input not received yet
         };
-        // Build a snapshot of the signal for publication on the event bus.
-        let signal = DataBcmSig {
-            name: sig.get_name().to_owned(),
-            status: sig.get_status(),
-            stamp: sig.get_stamp(),
-            value: sig.get_value(),
+        if let Ok(mut average) = self.average.try_borrow_mut() {
+            if let Some(average) = average.as_mut() {
+                value = average.push(value);
+            }
+        }
+
+        let status = if self.value.get() == Some(value) {
+            CanDataStatus::Unchanged
+        } else {
+            CanDataStatus::Updated
         };
-        // Push event depending on update status and throttling policy, update listeners count.
-        // `rate` and `watchdog` are scaled by 1000, but the underlying time unit is not explicitly documented.
-        let now = sig.get_stamp();
+        self.value.set(Some(value));
+        self.stamp.set(stamp);
+        self.status.set(status);
 
-        if logic::should_emit(
-            sig.get_status(),
-            now,
-            info.stamp,
-            info.rate,
-            info.watchdog,
-            info.flag.clone(),
-        ) {
-            info.stamp = now;
-            info.listeners = self.data.event.push(signal);
+        for subscriber in subscribers.values_mut() {
+            if subscriber.accept(status, stamp, Some(value)) {
+                subscriber.info.listeners = subscriber.event.push(self.snapshot());
+            }
         }
+    }
+}
+
+/// Verb callback context for a virtual signal.
+struct VirtualVerbCtx {
+    data: Rc<VirtualSignal>,
+}
 
-        info.listeners
+/// Verb for virtual signal operations: subscribe/unsubscribe/read.
+///
+/// Subscribe takes the same `rate`/`watchdog`/`flag` keys as real signals and subscribes
+/// the backend to the messages carrying the inputs.
+fn virtual_vcb(request: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<VirtualVerbCtx>()?;
+    let jquery = args.get::<JsoncObj>(0)?;
+    let jaction = jquery.get::<String>("action")?;
+
+    match logic::parse_action(&jaction) {
+        Some(logic::Action::Subscribe) => {
+            let default = &ctx.data.default;
+            let demand = logic::Demand {
+                rate: jquery.get::<u64>("rate").unwrap_or(default.rate),
+                watchdog: jquery.get::<u64>("watchdog").unwrap_or(default.watchdog),
+                flag: jquery
+                    .get::<String>("flag")
+                    .ok()
+                    .and_then(|v| logic::parse_subscribe_flag(&v))
+                    .unwrap_or_else(|| default.flag.clone()),
+            };
+            let session = dbc_session(request)?;
+            subscribe_session(
+                request,
+                &ctx.data.subscribers,
+                session.id,
+                ctx.data.uid,
+                demand.clone(),
+                None,
+                None,
+            )?;
+            let messages = ctx.data.messages.borrow().clone();
+            for msg_ctx in &messages {
+                msg_ctx.set_demand(request, (session.id, ctx.data.uid), Some(demand.clone()))?;
+            }
+
+            let data = ctx.data.clone();
+            session.leases.insert(
+                ctx.data.uid,
+                Box::new(move |id| {
+                    release_session(&data.subscribers, id);
+                    for msg_ctx in data.messages.borrow().iter() {
+                        msg_ctx.drop_demand((id, data.uid));
+                    }
+                }),
+            );
+            request.reply(format!("Subscribe virtual:{} OK", ctx.data.uid), 0);
+        },
+        Some(logic::Action::Unsubscribe) => {
+            let session = dbc_session(request)?;
+            session.leases.remove(ctx.data.uid);
+            unsubscribe_session(request, &ctx.data.subscribers, session.id)?;
+            let messages = ctx.data.messages.borrow().clone();
+            for msg_ctx in &messages {
+                msg_ctx.set_demand(request, (session.id, ctx.data.uid), None)?;
+            }
+            request.reply(format!("Unsubscribe virtual:{} OK", ctx.data.uid), 0);
+        },
+        Some(logic::Action::Read) => {
+            let mut params = AfbParams::new();
+            params.push(ctx.data.snapshot())?;
+            request.reply(params, 0);
+        },
+        _ => {
+            let error = AfbError::new("invalid-action", 0, "expect: SUBSCRIBE|UNSUBSCRIBE|READ");
+            return Err(error);
+        },
     }
+    Ok(())
 }
 
 /// Verb callback context for a single signal.
@@ -126,13 +670,21 @@ struct SigVerbCtx {
     data: Rc<SigDataCtx>,
 }
 
-/// Verb for signal operations: subscribe/unsubscribe/read/reset.
+/// Verb for signal operations: subscribe/unsubscribe/read/reset/write/info/history/stats.
 ///
 /// The verb expects a JSON object with at least:
-/// - `"action"`: one of SUBSCRIBE | UNSUBSCRIBE | READ | RESET (case-insensitive),
-///   and optionally:
+/// - `"action"`: one of SUBSCRIBE | UNSUBSCRIBE | READ | RESET | WRITE (alias SET) |
+///   INFO (alias DESCRIBE) | HISTORY | STATS, case-insensitive, and optionally:
 /// - `"rate"` / `"watchdog"`: overriding throttling thresholds,
-/// - `"flag"`: "NEW" or "ALL" for event publication policy.
+/// - `"flag"`: "NEW" or "ALL" for event publication policy,
+/// - `"match"`: values (numbers or value table labels) an update must hold to be pushed,
+/// - `"deadband"`: minimal change since the last pushed value, absolute or `"N%"`,
+/// - `"above"` / `"below"` / `"hysteresis"`: only push threshold crossings,
+/// - `"value"`: physical value or value table label sent by WRITE,
+/// - `"start"` / `"end"` / `"interval"`: HISTORY stamp range and averaging window (ms),
+/// - `"window"`: STATS sliding window (ms),
+/// - `"stats"`: SUBSCRIBE to the `message/signal/stats` event pushed every `stats` ms
+///   instead of raw samples, `true` to UNSUBSCRIBE from it.
 ///
 fn signal_vcb(request: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
     let ctx = ctx.get_ref::<SigVerbCtx>()?;
@@ -141,157 +693,265 @@ fn signal_vcb(request: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Resu
     let action = match logic::parse_action(&jaction) {
         Some(action) => action,
         None => {
-            let error =
-                AfbError::new("invalid-action", 0, "expect: SUBSCRIBE|UNSUBSCRIBE|READ|RESET");
+            let error = AfbError::new(
+                "invalid-action",
+                0,
+                "expect: SUBSCRIBE|UNSUBSCRIBE|READ|RESET|WRITE|INFO|HISTORY|STATS",
+            );
             return Err(error);
         },
     };
 
     match action {
+        logic::Action::Subscribe if jquery.get::<u64>("stats").is_ok() => {
+            subscribe_stats(request, ctx, &jquery)?;
+        },
+        logic::Action::Unsubscribe if jquery.get::<bool>("stats").unwrap_or(false) => {
+            let session = dbc_session(request)?;
+            let (event, uid) = match ctx.data.stats.try_borrow_mut() {
+                Ok(mut stats) => (stats.release(session.id), stats.uid),
+                Err(_) => {
+                    let error = AfbError::new(
+                        "fail-borrow-stats",
+                        0,
+                        "internal pool error (stats cell already used)",
+                    );
+                    return Err(afb_add_trace!(error));
+                },
+            };
+            if let Some(event) = event {
+                session.leases.remove(uid);
+                let status = event.unsubscribe(request);
+                event.unref();
+                status?;
+                ctx.msg_ctx.set_demand(request, (session.id, uid), None)?;
+            }
+            request.reply(AFB_NO_DATA, 0);
+        },
+
         logic::Action::Subscribe => {
-            // Subscribe the requester to the signal's event stream.
-            ctx.data.event.subscribe(request)?;
+            let matches = match logic::parse_match_filter(
+                &jquery.to_string(),
+                "match",
+                ctx.data.def.as_ref(),
+            ) {
+                Ok(matches) => matches,
+                Err(info) => return Err(AfbError::new("invalid-match-filter", 0, info)),
+            };
+            let filter = match logic::parse_value_filter(&jquery.to_string(), ctx.data.def.as_ref())
+            {
+                Ok(filter) => filter,
+                Err(info) => return Err(AfbError::new("invalid-value-filter", 0, info)),
+            };
 
-            // Take a short immutable snapshot of message-level defaults.
-            let (msg_canid, msg_flag_cur, msg_rate_cur, msg_watchdog_cur, msg_stamp_cur) = {
-                let msg = match ctx.msg_rfc.try_borrow() {
+            // Omitted parameters default to the message configuration.
+            let default = &ctx.msg_ctx.default;
+            let demand = logic::Demand {
+                rate: jquery.get::<u64>("rate").unwrap_or(default.rate),
+                watchdog: jquery.get::<u64>("watchdog").unwrap_or(default.watchdog),
+                flag: jquery
+                    .get::<String>("flag")
+                    .ok()
+                    .and_then(|v| logic::parse_subscribe_flag(&v))
+                    .unwrap_or_else(|| default.flag.clone()),
+            };
+            let session = dbc_session(request)?;
+
+            // The session gets its own event: throttling and filters only apply to it.
+            subscribe_session(
+                request,
+                &ctx.data.subscribers,
+                session.id,
+                ctx.data.uid,
+                demand.clone(),
+                matches,
+                filter,
+            )?;
+            // The backend runs at the fastest rate asked on the message.
+            ctx.msg_ctx.set_demand(request, (session.id, ctx.data.uid), Some(demand))?;
+
+            let (data, msg_ctx) = (ctx.data.clone(), ctx.msg_ctx.clone());
+            session.leases.insert(
+                ctx.data.uid,
+                Box::new(move |id| {
+                    release_session(&data.subscribers, id);
+                    msg_ctx.drop_demand((id, data.uid));
+                }),
+            );
+
+            request.reply(
+                format!("Subscribe (canid:{}) sig:{} OK", ctx.msg_ctx.canid, ctx.data.uid),
+                0,
+            );
+        },
+        logic::Action::Unsubscribe => {
+            let session = dbc_session(request)?;
+            session.leases.remove(ctx.data.uid);
+            unsubscribe_session(request, &ctx.data.subscribers, session.id)?;
+            // The last subscriber of the message also removes the backend subscription.
+            ctx.msg_ctx.set_demand(request, (session.id, ctx.data.uid), None)?;
+
+            request.reply(
+                format!("UnSubscribe (canid:{}) sig:{} OK", ctx.msg_ctx.canid, ctx.data.uid),
+                0,
+            );
+        },
+
+        logic::Action::Read => {
+            let sig_data = {
+                let sig = match ctx.sig_rfc.try_borrow() {
                     Ok(value) => value,
                     Err(_) => {
                         let error = AfbError::new(
-                            "fail-borrow-msg",
+                            "fail-borrow-sig",
                             0,
-                            "internal pool error (msg rfc cell already used)",
+                            "internal pool error (sig rfc cell already used)",
                         );
                         return Err(afb_add_trace!(error));
                     },
                 };
 
-                let msg_info = match ctx.msg_ctx.info.try_borrow() {
+                sig_snapshot(&**sig, ctx.data.def.as_ref())
+            };
+
+            let mut params = AfbParams::new();
+            params.push(sig_data)?;
+            request.reply(params, 0);
+        },
+
+        logic::Action::Reset => {
+            let (msg_canid, sig_name) = {
+                let mut sig = match ctx.sig_rfc.try_borrow_mut() {
                     Ok(value) => value,
                     Err(_) => {
                         let error = AfbError::new(
-                            "fail-borrow-info",
+                            "fail-borrow-sig",
                             0,
-                            "internal pool error (msg info cell already used)",
+                            "internal pool error (sig rfc cell already used)",
                         );
                         return Err(afb_add_trace!(error));
                     },
                 };
 
-                (
-                    msg.get_id(),
-                    msg_info.flag.clone(),
-                    msg_info.rate,
-                    msg_info.watchdog,
-                    msg_info.stamp,
-                )
-            };
-
-            let rate = jquery.get::<u64>("rate").unwrap_or(msg_rate_cur);
-            let watchdog = jquery.get::<u64>("watchdog").unwrap_or(msg_watchdog_cur);
-            let flag = jquery
-                .get::<String>("flag")
-                .ok()
-                .and_then(|v| logic::parse_subscribe_flag(&v))
-                .unwrap_or_else(|| msg_flag_cur.clone());
-
-            // Update signal-level state in a short mutable scope.
-            {
-                let mut sig_info = match ctx.data.info.try_borrow_mut() {
+                let msg = match ctx.msg_rfc.try_borrow() {
                     Ok(value) => value,
                     Err(_) => {
                         let error = AfbError::new(
-                            "fail-borrow-info",
+                            "fail-borrow-msg",
                             0,
-                            "internal pool error (sig info cell already used)",
+                            "internal pool error (msg rfc cell already used)",
                         );
                         return Err(afb_add_trace!(error));
                     },
                 };
 
-                // Persist the effective subscribe flag for this signal (used by should_emit()).
-                if flag == SubscribeFlag::ALL {
-                    sig_info.flag = SubscribeFlag::ALL;
-                }
-
-                // Update signal throttling if tighter.
-                if rate < sig_info.rate {
-                    sig_info.rate = rate;
-                }
-                if watchdog < sig_info.watchdog {
-                    sig_info.watchdog = watchdog;
-                }
+                sig.reset();
+                (msg.get_id(), sig.get_name().to_string())
+            };
 
-                sig_info.listeners += 1; // We have at least one listener now.
-            }
+            request.reply(format!("Reset (canid:{}) sig:{} OK", msg_canid, sig_name), 0);
+        },
 
-            // Decide whether message-level subscription parameters must be tightened.
-            let need_backend_subscribe = msg_stamp_cur == 0
-                || watchdog < msg_watchdog_cur
-                || rate < msg_rate_cur
-                || (flag == SubscribeFlag::ALL && msg_flag_cur != SubscribeFlag::ALL);
+        logic::Action::Stats => {
+            let query = match logic::parse_stats_query(&jquery.to_string()) {
+                Ok(query) => query,
+                Err(info) => return Err(AfbError::new("invalid-stats", 0, info)),
+            };
+            let window = query.window.unwrap_or(logic::STATS_DFT_WINDOW);
+            let name = match ctx.sig_rfc.try_borrow() {
+                Ok(sig) => sig.get_name(),
+                Err(_) => {
+                    let error = AfbError::new(
+                        "fail-borrow-sig",
+                        0,
+                        "internal pool error (sig rfc cell already used)",
+                    );
+                    return Err(afb_add_trace!(error));
+                },
+            };
+            let mut stats = match ctx.data.stats.try_borrow_mut() {
+                Ok(stats) => stats,
+                Err(_) => {
+                    let error = AfbError::new(
+                        "fail-borrow-stats",
+                        0,
+                        "internal pool error (stats cell already used)",
+                    );
+                    return Err(afb_add_trace!(error));
+                },
+            };
+            // Collection starts with the first request, then keeps the widest window asked.
+            stats
+                .samples
+                .get_or_insert_with(|| logic::StatsWindow::new(window))
+                .widen(window);
 
-            if need_backend_subscribe {
-                let (backend_watchdog, backend_rate, backend_flag) = {
-                    let mut msg_info = match ctx.msg_ctx.info.try_borrow_mut() {
-                        Ok(value) => value,
-                        Err(_) => {
-                            let error = AfbError::new(
-                                "fail-borrow-info",
-                                0,
-                                "internal pool error (msg info cell already used)",
-                            );
-                            return Err(afb_add_trace!(error));
-                        },
-                    };
+            let mut params = AfbParams::new();
+            params.push(stats.snapshot(name, window))?;
+            request.reply(params, 0);
+        },
 
-                    if flag == SubscribeFlag::ALL {
-                        msg_info.flag = SubscribeFlag::ALL;
-                    }
-                    if rate < msg_info.rate {
-                        msg_info.rate = rate;
-                    }
-                    if watchdog < msg_info.watchdog {
-                        msg_info.watchdog = watchdog;
-                    }
-                    msg_info.stamp = 1;
-
-                    (msg_info.watchdog, msg_info.rate, msg_info.flag.clone())
-                };
+        logic::Action::History => {
+            let history = match &ctx.data.history {
+                Some(history) => history,
+                None => {
+                    let error = AfbError::new(
+                        "invalid-history",
+                        0,
+                        "signal history requires a 'history' entry in binding config",
+                    );
+                    return Err(error);
+                },
+            };
+            let query = match logic::parse_history_query(&jquery.to_string()) {
+                Ok(query) => query,
+                Err(info) => return Err(AfbError::new("invalid-history", 0, info)),
+            };
+            let samples = match history.try_borrow() {
+                Ok(history) => history.query(&query),
+                Err(_) => {
+                    let error = AfbError::new(
+                        "fail-borrow-history",
+                        0,
+                        "internal pool error (history cell already used)",
+                    );
+                    return Err(afb_add_trace!(error));
+                },
+            };
+            let name = match ctx.sig_rfc.try_borrow() {
+                Ok(sig) => sig.get_name().to_string(),
+                Err(_) => {
+                    let error = AfbError::new(
+                        "fail-borrow-sig",
+                        0,
+                        "internal pool error (sig rfc cell already used)",
+                    );
+                    return Err(afb_add_trace!(error));
+                },
+            };
 
-                AfbSubCall::call_sync(
-                    request,
-                    ctx.msg_ctx.bcm,
-                    "subscribe",
-                    SubscribeParam::new(
-                        vec![msg_canid],
-                        backend_watchdog,
-                        backend_rate,
-                        backend_flag,
-                    ),
-                )?;
-            }
+            let mut params = AfbParams::new();
+            params.push(DataBcmHistory {
+                name,
+                samples: samples
+                    .into_iter()
+                    .map(|(stamp, value)| DataBcmSample { stamp, value })
+                    .collect(),
+            })?;
+            request.reply(params, 0);
+        },
 
-            let sig_name = {
-                let sig = match ctx.sig_rfc.try_borrow() {
-                    Ok(value) => value,
-                    Err(_) => {
-                        let error = AfbError::new(
-                            "fail-borrow-sig",
-                            0,
-                            "internal pool error (sig rfc cell already used)",
-                        );
-                        return Err(afb_add_trace!(error));
-                    },
-                };
-                sig.get_name().to_string()
+        logic::Action::Info => {
+            let def = match &ctx.data.def {
+                Some(def) => def,
+                None => return Err(layout_error("signal")),
             };
-
-            request.reply(format!("Subscribe (canid:{}) sig:{} OK", msg_canid, sig_name), 0);
+            let mut params = AfbParams::new();
+            params.push(sig_info(def))?;
+            request.reply(params, 0);
         },
-        logic::Action::Unsubscribe => {
-            ctx.data.event.unsubscribe(request)?;
 
+        logic::Action::Write => {
             let (msg_canid, sig_name) = {
                 let msg = match ctx.msg_rfc.try_borrow() {
                     Ok(value) => value,
@@ -304,7 +964,6 @@ fn signal_vcb(request: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Resu
                         return Err(afb_add_trace!(error));
                     },
                 };
-
                 let sig = match ctx.sig_rfc.try_borrow() {
                     Ok(value) => value,
                     Err(_) => {
@@ -316,73 +975,90 @@ fn signal_vcb(request: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Resu
                         return Err(afb_add_trace!(error));
                     },
                 };
-
-                (msg.get_id(), sig.get_name().to_string())
+                (msg.get_id(), sig.get_name())
             };
 
-            request.reply(format!("UnSubscribe (canid:{}) sig:{} OK", msg_canid, sig_name), 0);
-        },
-
-        logic::Action::Read => {
-            let sig_data = {
-                let sig = match ctx.sig_rfc.try_borrow() {
-                    Ok(value) => value,
-                    Err(_) => {
-                        let error = AfbError::new(
-                            "fail-borrow-sig",
-                            0,
-                            "internal pool error (sig rfc cell already used)",
-                        );
-                        return Err(afb_add_trace!(error));
-                    },
+            let layout = ctx.msg_ctx.layout.as_ref();
+            let values =
+                match logic::parse_write_values(&jquery.to_string(), Some(sig_name), layout) {
+                    Ok(values) => values,
+                    Err(info) => return Err(AfbError::new("invalid-write-value", 0, info)),
                 };
+            write_message(request, &ctx.msg_ctx, msg_canid, &values)?;
 
-                DataBcmSig {
-                    name: sig.get_name().to_owned(),
-                    stamp: sig.get_stamp(),
-                    status: sig.get_status(),
-                    value: sig.get_value(),
-                }
-            };
-
-            let mut params = AfbParams::new();
-            params.push(sig_data)?;
-            request.reply(params, 0);
+            request.reply(format!("Write (canid:{}) sig:{} OK", msg_canid, sig_name), 0);
         },
+    };
+    Ok(())
+}
 
-        logic::Action::Reset => {
-            let (msg_canid, sig_name) = {
-                let mut sig = match ctx.sig_rfc.try_borrow_mut() {
-                    Ok(value) => value,
-                    Err(_) => {
-                        let error = AfbError::new(
-                            "fail-borrow-sig",
-                            0,
-                            "internal pool error (sig rfc cell already used)",
-                        );
-                        return Err(afb_add_trace!(error));
-                    },
-                };
-
-                let msg = match ctx.msg_rfc.try_borrow() {
-                    Ok(value) => value,
-                    Err(_) => {
-                        let error = AfbError::new(
-                            "fail-borrow-msg",
-                            0,
-                            "internal pool error (msg rfc cell already used)",
-                        );
-                        return Err(afb_add_trace!(error));
-                    },
-                };
-
-                sig.reset();
-                (msg.get_id(), sig.get_name().to_string())
-            };
+/// Subscribe to the statistics event of a signal (`{'action':'subscribe','stats':ms}`).
+///
+/// The session gets its own `<signal>/stats` event with its own period and window,
+/// created on first use. Statistics count every received frame, so the backend is asked for
+/// all of them on this message until the session unsubscribes.
+fn subscribe_stats(
+    request: &AfbRequest,
+    ctx: &SigVerbCtx,
+    jquery: &JsoncObj,
+) -> Result<(), AfbError> {
+    let query = match logic::parse_stats_query(&jquery.to_string()) {
+        Ok(query) => query,
+        Err(info) => return Err(AfbError::new("invalid-stats", 0, info)),
+    };
+    let period = match query.period {
+        Some(period) => period,
+        None => return Err(AfbError::new("invalid-stats", 0, "stats: expect a period in ms")),
+    };
+    let window = query.window.unwrap_or(logic::STATS_DFT_WINDOW);
 
-            request.reply(format!("Reset (canid:{}) sig:{} OK", msg_canid, sig_name), 0);
-        },
+    let session = dbc_session(request)?;
+    let (event, uid) = {
+        let mut stats = match ctx.data.stats.try_borrow_mut() {
+            Ok(stats) => stats,
+            Err(_) => {
+                let error = AfbError::new(
+                    "fail-borrow-stats",
+                    0,
+                    "internal pool error (stats cell already used)",
+                );
+                return Err(afb_add_trace!(error));
+            },
+        };
+        let event = match stats.subscribers.get(&session.id) {
+            Some(subscriber) => subscriber.event,
+            None => runtime_event(request, stats.uid)?,
+        };
+        // A new stats subscribe of the same session replaces its period and window.
+        stats
+            .subscribers
+            .insert(session.id, StatsSubscriber { event, period, span: window, stamp: 0 });
+        stats
+            .samples
+            .get_or_insert_with(|| logic::StatsWindow::new(window))
+            .widen(window);
+        (event, stats.uid)
     };
+    event.subscribe(request)?;
+
+    // Ask the backend for every frame while the session listens to the statistics.
+    let demand =
+        logic::Demand { rate: 0, watchdog: ctx.msg_ctx.default.watchdog, flag: SubscribeFlag::ALL };
+    ctx.msg_ctx.set_demand(request, (session.id, uid), Some(demand))?;
+    let (data, msg_ctx) = (ctx.data.clone(), ctx.msg_ctx.clone());
+    session.leases.insert(
+        uid,
+        Box::new(move |id| {
+            if let Ok(mut stats) = data.stats.try_borrow_mut() {
+                if let Some(event) = stats.release(id) {
+                    event.unref();
+                }
+            }
+            msg_ctx.drop_demand((id, uid));
+        }),
+    );
+
+    request.reply(format!("Subscribe (canid:{}) stats:{} OK", ctx.msg_ctx.canid, uid), 0);
     Ok(())
 }
 
@@ -393,10 +1069,9 @@ fn signal_vcb(request: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Resu
 ///
 /// Returns the verb and event handles used by the API.
 fn register_signal(
-    _config: &SockBcmConfig,
+    config: &SockBcmConfig,
     msg_ctx: &Rc<MessageDataCtx>,
     msg_name: &'static str,
-    _msg_id_dbg: u32,
     msg_rfc: &Rc<RefCell<Box<dyn CanDbcMessage>>>,
     sig_rfc: &Rc<RefCell<Box<dyn CanDbcSignal>>>,
 ) -> Result<(&'static AfbVerb, &'static AfbEvent), AfbError> {
@@ -411,15 +1086,58 @@ fn register_signal(
     let sig_evt_uid = to_static_str(format!("{}/{}", msg_name, sig_name));
     let sig_event = AfbEvent::new(sig_evt_uid).finalize()?;
 
-    // Initialize per-signal runtime context.
-    let info = PoolInfoCtx {
-        rate: MSG_DFT_RATE,
-        watchdog: MSG_DFT_WATCHDOG,
-        stamp: 0,
-        listeners: 0,
-        flag: SubscribeFlag::NEW,
-    };
-    let sigdata = Rc::new(SigDataCtx { event: sig_event, info: RefCell::new(info) });
+    let def = msg_ctx.layout.as_ref().and_then(|layout| layout.signal(sig_name)).cloned();
+    let alarms: Vec<Rc<Alarm>> = config
+        .alarms
+        .iter()
+        .filter(|alarm| alarm.rule.signal.eq_ignore_ascii_case(sig_evt_uid))
+        .cloned()
+        .collect();
+    for alarm in &alarms {
+        alarm.attached.set(true);
+        alarm.msg.replace(Some(msg_ctx.clone()));
+    }
+    let stats = RefCell::new(SigStats {
+        name: sig_evt_uid,
+        uid: to_static_str(format!("{}/stats", sig_evt_uid)),
+        samples: None,
+        subscribers: HashMap::new(),
+    });
+    let history = config
+        .history
+        .iter()
+        .find(|history| {
+            history
+                .signals
+                .iter()
+                .any(|pattern| logic::set_selects(pattern, msg_name, sig_name))
+        })
+        .map(|history| RefCell::new(logic::History::new(history.samples, history.seconds)));
+    let mut virtuals = Vec::new();
+    for virt in &config.virtuals {
+        for (slot, input) in virt.inputs.iter().enumerate() {
+            if !input.eq_ignore_ascii_case(sig_evt_uid) {
+                continue;
+            }
+            virtuals.push((virt.clone(), slot));
+            virt.attached.borrow_mut()[slot] = true;
+            let mut messages = virt.messages.borrow_mut();
+            if !messages.iter().any(|msg| Rc::ptr_eq(msg, msg_ctx)) {
+                messages.push(msg_ctx.clone());
+                msg_ctx.virtuals.borrow_mut().push(virt.clone());
+            }
+        }
+    }
+    let sigdata = Rc::new(SigDataCtx {
+        uid: sig_evt_uid,
+        event: sig_event,
+        subscribers: RefCell::new(HashMap::new()),
+        def,
+        alarms,
+        virtuals,
+        history,
+        stats,
+    });
 
     // Attach controller to push updates into the event.
     sig_ref.set_callback(Box::new(SigPoolCtx { data: sigdata.clone() }));
@@ -427,10 +1145,24 @@ fn register_signal(
     // Build and finalize the verb for this signal.
     let mut sig_verb = AfbVerb::new(sig_name);
 
-    sig_verb = sig_verb.set_actions("['reset','read','subscribe','unsubscribe']")?;
+    sig_verb = sig_verb.set_actions(
+        "['reset','read','subscribe','unsubscribe','write','info','history','stats']",
+    )?;
 
     sig_verb =
         sig_verb.add_sample("{'action':'subscribe','rate':250,'watchdog':5000,'flag':'all'}")?;
+    sig_verb =
+        sig_verb.add_sample("{'action':'subscribe','deadband':'2%','above':80,'hysteresis':2}")?;
+    sig_verb = sig_verb.add_sample("{'action':'write','value':1}")?;
+    sig_verb = sig_verb.add_sample("{'action':'info'}")?;
+    sig_verb = sig_verb.add_sample("{'action':'subscribe','stats':1000,'window':10000}")?;
+    if sigdata.history.is_some() {
+        sig_verb = sig_verb.add_sample("{'action':'history','interval':1000}")?;
+    }
+    if let Some((_, label)) = sigdata.def.as_ref().and_then(|def| def.labels.first()) {
+        let sample = format!("{{'action':'subscribe','match':['{}']}}", label);
+        sig_verb = sig_verb.add_sample(to_static_str(sample))?;
+    }
 
     sig_verb = sig_verb.set_callback(signal_vcb);
 
@@ -447,10 +1179,175 @@ fn register_signal(
 }
 
 /// Per-message runtime data (throttle/flag) + event + backend API name.
+///
+/// `layout` is the DBC definition used to encode `write` requests into `frame`, the last
+/// received or written payload. `tx` is set for messages the binding sends itself, their
+/// `frame` is only changed by `write`. `resync` flags demands dropped without backend call.
 struct MessageDataCtx {
     info: RefCell<PoolInfoCtx>,
     event: &'static AfbEvent,
     bcm: &'static str,
+    layout: Option<DbcMessageDef>,
+    frame: RefCell<Option<Vec<u8>>>,
+    tx: Option<TxCycle>,
+    sets: RefCell<Vec<Rc<SignalSet>>>,
+    virtuals: RefCell<Vec<Rc<VirtualSignal>>>,
+    canid: u32,
+    default: logic::Demand,
+    demands: RefCell<HashMap<DemandKey, logic::Demand>>,
+    resync: Cell<bool>,
+}
+
+impl MessageDataCtx {
+    /// Set (`Some`) or drop (`None`) one demand on this message, then align the backend
+    /// subscription on the most demanding remaining one, unsubscribing once none is left.
+    ///
+    /// A demand the backend refused is not kept, a refused release is retried with the next
+    /// frame of the message (`resync_demand`).
+    fn set_demand(
+        &self,
+        request: &AfbRequest,
+        key: DemandKey,
+        demand: Option<logic::Demand>,
+    ) -> Result<(), AfbError> {
+        let (previous, merged) = match self.demands.try_borrow_mut() {
+            Ok(mut demands) => {
+                let previous = match demand.clone() {
+                    Some(demand) => demands.insert(key, demand),
+                    None => demands.remove(&key),
+                };
+                (previous, logic::merge_demands(demands.values()))
+            },
+            Err(_) => {
+                let error = AfbError::new(
+                    "fail-borrow-demands",
+                    0,
+                    "internal pool error (msg demands cell already used)",
+                );
+                return Err(afb_add_trace!(error));
+            },
+        };
+
+        if let Err(error) = self.sync_backend(request.get_api(), merged) {
+            match (demand, self.demands.try_borrow_mut()) {
+                (Some(_), Ok(mut demands)) => {
+                    match previous {
+                        Some(previous) => demands.insert(key, previous),
+                        None => demands.remove(&key),
+                    };
+                },
+                _ => self.resync.set(true),
+            }
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Apply the demands dropped by `drop_demand` to the backend subscription.
+    ///
+    /// Called from the frame handler of this message: closing sessions cannot subcall, the
+    /// next frame of the canid recomputes the merged demand or unsubscribes.
+    fn resync_demand(&self, api: &AfbApi) -> Result<(), AfbError> {
+        if !self.resync.get() {
+            return Ok(());
+        }
+        let merged = match self.demands.try_borrow() {
+            Ok(demands) => logic::merge_demands(demands.values()),
+            Err(_) => return Ok(()), // retried on the next frame
+        };
+        self.sync_backend(api, merged)
+    }
+
+    /// Align the backend subscription on the `merged` demand.
+    ///
+    /// The backend keeps its filters per caller session, so every subscribe and unsubscribe
+    /// of a message is an API-level subcall: one session owns the backend subscription
+    /// whichever client changed the demands. The message info only follows once the backend
+    /// accepted the change, `resync` stays set until then.
+    fn sync_backend(&self, api: &AfbApi, merged: Option<logic::Demand>) -> Result<(), AfbError> {
+        let current = match self.info.try_borrow() {
+            Ok(msg_info) if msg_info.stamp != 0 => Some(logic::Demand {
+                rate: msg_info.rate,
+                watchdog: msg_info.watchdog,
+                flag: msg_info.flag.clone(),
+            }),
+            Ok(_) => None,
+            Err(_) => {
+                let error = AfbError::new(
+                    "fail-borrow-info",
+                    0,
+                    "internal pool error (msg info cell already used)",
+                );
+                return Err(afb_add_trace!(error));
+            },
+        };
+
+        // No borrow is kept alive across the backend call.
+        match logic::backend_change(current.as_ref(), merged.as_ref()) {
+            Some(Some(demand)) => AfbSubCall::call_sync(
+                api,
+                self.bcm,
+                "subscribe",
+                SubscribeParam::new(vec![self.canid], demand.watchdog, demand.rate, demand.flag),
+            )?,
+            Some(None) => AfbSubCall::call_sync(
+                api,
+                self.bcm,
+                "unsubscribe",
+                UnSubscribeParam::new(vec![self.canid]),
+            )?,
+            None => {},
+        };
+
+        if let Ok(mut msg_info) = self.info.try_borrow_mut() {
+            let demand = merged.as_ref().unwrap_or(&self.default);
+            msg_info.stamp = merged.is_some() as u64;
+            msg_info.rate = demand.rate;
+            msg_info.watchdog = demand.watchdog;
+            msg_info.flag = demand.flag.clone();
+        }
+        self.resync.set(false);
+        Ok(())
+    }
+
+    /// Drop one demand without a backend call (closing sessions and sets), the backend
+    /// subscription follows at the next frame of the message (`resync_demand`).
+    fn drop_demand(&self, key: DemandKey) {
+        if let Ok(mut demands) = self.demands.try_borrow_mut() {
+            if demands.remove(&key).is_some() {
+                self.resync.set(true);
+            }
+        }
+    }
+}
+
+/// Cyclic transmission run by the kernel BCM on the binding own socket.
+struct TxCycle {
+    sockfd: Rc<SockCanHandle>,
+    period: u64,
+}
+
+/// Install (`period` set) or update the content of the cyclic transmission of `canid`.
+///
+/// Without a period the running timer is kept and only the next frames change.
+fn tx_setup(
+    sockfd: &SockCanHandle,
+    canid: u32,
+    period: Option<u64>,
+    data: &[u8],
+) -> Result<(), CanError> {
+    let mut frame = match period {
+        Some(period) => {
+            let flags = CanBcmFlag::SET_TIMER | CanBcmFlag::START_TIMER;
+            let mut frame = SockBcmCmd::new(CanBcmOpCode::TxSetup, flags, canid);
+            // set_timers(rate, watchdog) maps to ival2 and ival1 respectively.
+            frame.set_timers(period, 0);
+            frame
+        },
+        None => SockBcmCmd::new(CanBcmOpCode::TxSetup, CanBcmFlag::NONE, canid),
+    };
+    frame.add_frame(canid, data);
+    frame.apply(sockfd)
 }
 
 /// Verb callback context for a message.
@@ -483,6 +1380,7 @@ impl CanMsgCtrl for MessagePoolCtx {
         };
 
         // Build a parameter pack containing the message snapshot + selected signals.
+        let layout = self.data.layout.as_ref();
         let params =
             |msg: &dyn CanDbcMessage, current_flag: SubscribeFlag| -> Result<AfbParams, AfbError> {
                 let mut args = AfbParams::new();
@@ -501,12 +1399,8 @@ impl CanMsgCtrl for MessagePoolCtx {
                         },
                     };
 
-                    let sig_value = DataBcmSig {
-                        name: sig.get_name().to_string(),
-                        status: sig.get_status(),
-                        stamp: sig.get_stamp(),
-                        value: sig.get_value(),
-                    };
+                    let def = layout.and_then(|layout| layout.signal(sig.get_name()));
+                    let sig_value = sig_snapshot(&**sig, def);
 
                     match current_flag {
                         SubscribeFlag::NEW => {
@@ -538,10 +1432,266 @@ impl CanMsgCtrl for MessagePoolCtx {
         // Do not infer durable subscriptions from the current frame.
         // Multiplexed messages may update a mux branch with no subscribed signal.
         let _listener = self.data.event.push(args);
+
+        // Feed the `subscribe_set` sets selecting signals of this message.
+        if let Ok(mut sets) = self.data.sets.try_borrow_mut() {
+            sets.retain(|set| {
+                if set.closed.get() {
+                    set.release();
+                }
+                !set.closed.get()
+            });
+            for set in sets.iter() {
+                set.notify(msg, layout);
+            }
+        }
+
+        // Recompute the virtual signals reading this message.
+        if let Ok(virtuals) = self.data.virtuals.try_borrow() {
+            for virt in virtuals.iter() {
+                virt.compute(msg.get_stamp());
+            }
+        }
+    }
+}
+
+/// Signals selected by one `subscribe_set` request, pushed together on the set event.
+///
+/// Updates are queued per signal (latest value wins) and pushed at most every `rate` ms.
+/// The set is closed by `unsubscribe_set`, by the end of its session, or by itself once its
+/// event has no listener left; `messages` are the member messages holding its demand.
+struct SignalSet {
+    uid: &'static str,
+    key: DemandKey,
+    event: &'static AfbEvent,
+    messages: Vec<Rc<MessageDataCtx>>,
+    rate: u64,
+    flag: SubscribeFlag,
+    members: HashMap<u32, Vec<&'static str>>,
+    pending: RefCell<Vec<DataBcmSig>>,
+    stamp: Cell<u64>,
+    closed: Cell<bool>,
+    released: Cell<bool>,
+}
+
+impl SignalSet {
+    /// Close the set: unref its event and drop its demand on the member messages, once.
+    fn release(&self) {
+        self.closed.set(true);
+        if self.released.replace(true) {
+            return;
+        }
+        self.event.unref();
+        for data in &self.messages {
+            data.drop_demand(self.key);
+        }
+    }
+
+    fn notify(&self, msg: &dyn CanDbcMessage, layout: Option<&DbcMessageDef>) {
+        let members = match self.members.get(&msg.get_id()) {
+            Some(members) => members,
+            None => return,
+        };
+        let mut pending = match self.pending.try_borrow_mut() {
+            Ok(pending) => pending,
+            Err(_) => return,
+        };
+
+        for sig_rfc in msg.get_signals() {
+            let sig = match sig_rfc.try_borrow() {
+                Ok(sig) => sig,
+                Err(_) => continue,
+            };
+            if !members.contains(&sig.get_name())
+                || (self.flag == SubscribeFlag::NEW && sig.get_status() != CanDataStatus::Updated)
+            {
+                continue;
+            }
+            let def = layout.and_then(|layout| layout.signal(sig.get_name()));
+            let mut snapshot = sig_snapshot(&**sig, def);
+            snapshot.name = format!("{}/{}", msg.get_name(), snapshot.name);
+            pending.retain(|item| item.name != snapshot.name);
+            pending.push(snapshot);
+        }
+
+        let now = msg.get_stamp();
+        if pending.is_empty()
+            || now.saturating_sub(self.stamp.get()) < self.rate.saturating_mul(1000)
+        {
+            return;
+        }
+        self.stamp.set(now);
+        let signals = std::mem::take(&mut *pending);
+        let set = DataBcmSigSet { uid: self.uid.to_string(), stamp: now, signals };
+        if self.event.push(set) == 0 {
+            self.closed.set(true);
+        }
     }
 }
 
-/// Verb for message operations: subscribe/unsubscribe/read/reset.
+/// One pool message as seen by the `subscribe_set` verb.
+struct SetEntry {
+    name: &'static str,
+    canid: u32,
+    signals: Vec<&'static str>,
+    data: Rc<MessageDataCtx>,
+}
+
+/// `subscribe_set` verb context.
+struct SetVerbCtx {
+    messages: Vec<SetEntry>,
+    count: Cell<u32>,
+}
+
+/// Pool-wide `subscribe_set` verb: one event for many signals.
+///
+/// `{'signals':['Message/Signal','Message/*','*/Cell*'],'rate':250,'watchdog':5000,'flag':'new'}`
+/// selects signals with `*`/`?` wildcards (a pattern without `/` selects whole messages).
+/// Every selected message is subscribed with the set parameters and the requester
+/// receives one `set/N` event per `rate` window holding the changed signals only
+/// (every selected signal with `'flag':'all'`).
+fn subscribe_set_vcb(
+    request: &AfbRequest,
+    args: &AfbRqtData,
+    ctx: &AfbCtxData,
+) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<SetVerbCtx>()?;
+    let jquery = args.get::<JsoncObj>(0)?;
+
+    let patterns = match logic::parse_set_patterns(&jquery.to_string()) {
+        Ok(patterns) => patterns,
+        Err(info) => return Err(AfbError::new("invalid-set-signals", 0, info)),
+    };
+    let rate = jquery.get::<u64>("rate").unwrap_or(MSG_DFT_RATE);
+    let watchdog = jquery.get::<u64>("watchdog").unwrap_or(MSG_DFT_WATCHDOG);
+    let flag = jquery
+        .get::<String>("flag")
+        .ok()
+        .and_then(|v| logic::parse_subscribe_flag(&v))
+        .unwrap_or(SubscribeFlag::NEW);
+
+    let mut members = HashMap::new();
+    let mut count = 0;
+    for entry in &ctx.messages {
+        let selected: Vec<&'static str> = entry
+            .signals
+            .iter()
+            .copied()
+            .filter(|sig| {
+                patterns.iter().any(|pattern| logic::set_selects(pattern, entry.name, sig))
+            })
+            .collect();
+        if !selected.is_empty() {
+            count += selected.len();
+            members.insert(entry.canid, selected);
+        }
+    }
+    if members.is_empty() {
+        return Err(AfbError::new(
+            "invalid-set-signals",
+            0,
+            format!("no signal matches {:?}", patterns),
+        ));
+    }
+
+    ctx.count.set(ctx.count.get() + 1);
+    let uid = to_static_str(format!("set/{}", ctx.count.get()));
+    let event = runtime_event(request, uid)?;
+    event.subscribe(request)?;
+
+    let key = (dbc_session(request)?.id, uid);
+    let demand = logic::Demand { rate, watchdog, flag: flag.clone() };
+    for entry in ctx.messages.iter().filter(|entry| members.contains_key(&entry.canid)) {
+        entry.data.set_demand(request, key, Some(demand.clone()))?;
+    }
+
+    let set = Rc::new(SignalSet {
+        uid,
+        key,
+        event,
+        messages: ctx
+            .messages
+            .iter()
+            .filter(|entry| members.contains_key(&entry.canid))
+            .map(|entry| entry.data.clone())
+            .collect(),
+        rate,
+        flag,
+        members,
+        pending: RefCell::new(Vec::new()),
+        stamp: Cell::new(0),
+        closed: Cell::new(false),
+        released: Cell::new(false),
+    });
+    for data in &set.messages {
+        data.sets.borrow_mut().push(set.clone());
+    }
+
+    // Sessions closing without `unsubscribe_set` release their sets.
+    let lease = set.clone();
+    dbc_session(request)?.leases.insert(uid, Box::new(move |_| lease.release()));
+
+    request.reply(format!("SubscribeSet event:{} signals:{} OK", uid, count), 0);
+    Ok(())
+}
+
+/// `unsubscribe_set` verb context: the messages `subscribe_set` may attach sets to.
+struct UnsetVerbCtx {
+    messages: Vec<Rc<MessageDataCtx>>,
+}
+
+/// Pool-wide `unsubscribe_set` verb: `{'uid':'set/N'}` closes a set of the caller session.
+///
+/// The caller leaves the set event, the set demand is dropped from its messages (updating
+/// the backend subscriptions) and the event is released.
+fn unsubscribe_set_vcb(
+    request: &AfbRequest,
+    args: &AfbRqtData,
+    ctx: &AfbCtxData,
+) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<UnsetVerbCtx>()?;
+    let jquery = args.get::<JsoncObj>(0)?;
+    let uid = jquery.get::<String>("uid")?;
+    let session = dbc_session(request)?;
+
+    let set = ctx.messages.iter().find_map(|data| {
+        data.sets
+            .borrow()
+            .iter()
+            .find(|set| set.uid == uid && set.key.0 == session.id && !set.closed.get())
+            .cloned()
+    });
+    let set = match set {
+        Some(set) => set,
+        None => {
+            return Err(AfbError::new(
+                "invalid-set-uid",
+                0,
+                format!("no set '{}' subscribed by this session", uid),
+            ))
+        },
+    };
+    session.leases.remove(set.uid);
+
+    set.event.unsubscribe(request)?;
+    let mut status = Ok(());
+    for data in &set.messages {
+        data.sets.borrow_mut().retain(|item| !Rc::ptr_eq(item, &set));
+        if let Err(error) = data.set_demand(request, set.key, None) {
+            status = Err(error);
+        }
+    }
+    set.release();
+    status?;
+
+    request.reply(format!("UnsubscribeSet event:{} OK", set.uid), 0);
+    Ok(())
+}
+
+/// Verb for message operations: subscribe/unsubscribe/read/reset/write/info.
+///
+/// WRITE (alias SET) takes physical signal values as `{'values':{'Signal':x,...}}`.
+/// INFO (alias DESCRIBE) returns the DBC definition of the message and its signals.
 fn message_vcb(request: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
     let ctx = ctx.get_ref::<MessageVerbCtx>()?;
     let jquery = args.get::<JsoncObj>(0)?;
@@ -551,9 +1701,14 @@ fn message_vcb(request: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Res
         "UNSUBSCRIBE" => logic::Action::Unsubscribe,
         "READ" => logic::Action::Read,
         "RESET" => logic::Action::Reset,
+        "WRITE" | "SET" => logic::Action::Write,
+        "INFO" | "DESCRIBE" => logic::Action::Info,
         _ => {
-            let error =
-                AfbError::new("invalid-action", 0, "expect: SUBSCRIBE|UNSUBSCRIBE|READ|RESET");
+            let error = AfbError::new(
+                "invalid-action",
+                0,
+                "expect: SUBSCRIBE|UNSUBSCRIBE|READ|RESET|WRITE|INFO",
+            );
             return Err(error);
         },
     };
@@ -562,126 +1717,61 @@ fn message_vcb(request: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Res
         logic::Action::Subscribe => {
             ctx.data.event.subscribe(request)?;
 
-            // Take a short immutable snapshot of current message subscription state.
-            let (msg_canid, msg_name, cur_rate, cur_watchdog, cur_flag, cur_stamp) = {
-                let msg = match ctx.msg_rfc.try_borrow() {
-                    Ok(value) => value,
-                    Err(_) => {
-                        let error = AfbError::new(
-                            "fail-borrow-msg",
-                            0,
-                            "internal pool error (msg cell already used)",
-                        );
-                        return Err(afb_add_trace!(error));
-                    },
-                };
+            let msg_name = match ctx.msg_rfc.try_borrow() {
+                Ok(msg) => msg.get_name(),
+                Err(_) => {
+                    let error = AfbError::new(
+                        "fail-borrow-msg",
+                        0,
+                        "internal pool error (msg cell already used)",
+                    );
+                    return Err(afb_add_trace!(error));
+                },
+            };
 
-                let msg_info = match ctx.data.info.try_borrow() {
-                    Ok(value) => value,
-                    Err(_) => {
-                        let error = AfbError::new(
-                            "fail-borrow-info",
-                            0,
-                            "internal pool error (msg info cell already used)",
-                        );
-                        return Err(afb_add_trace!(error));
-                    },
-                };
-
-                (
-                    msg.get_id(),
-                    msg.get_name().to_string(),
-                    msg_info.rate,
-                    msg_info.watchdog,
-                    msg_info.flag.clone(),
-                    msg_info.stamp,
-                )
+            // Omitted parameters default to the message configuration.
+            let default = &ctx.data.default;
+            let demand = logic::Demand {
+                rate: jquery.get::<u64>("rate").unwrap_or(default.rate),
+                watchdog: jquery.get::<u64>("watchdog").unwrap_or(default.watchdog),
+                flag: jquery
+                    .get::<String>("flag")
+                    .ok()
+                    .and_then(|v| logic::parse_subscribe_flag(&v))
+                    .unwrap_or_else(|| default.flag.clone()),
             };
 
-            let rate = jquery.get::<u64>("rate").unwrap_or(cur_rate);
-            let watchdog = jquery.get::<u64>("watchdog").unwrap_or(cur_watchdog);
-            let flag = jquery
-                .get::<String>("flag")
-                .ok()
-                .and_then(|v| match v.to_uppercase().as_str() {
-                    "NEW" => Some(SubscribeFlag::NEW),
-                    "ALL" => Some(SubscribeFlag::ALL),
-                    _ => None,
-                })
-                .unwrap_or_else(|| cur_flag.clone());
-
-            // If we need to tighten backend subscription, update internal state first
-            // in a short mutable scope, then call backend with no borrow alive.
-            let need_backend_subscribe =
-                cur_stamp == 0 || watchdog < cur_watchdog || rate < cur_rate || cur_flag != flag;
-
-            if need_backend_subscribe {
-                let (backend_watchdog, backend_rate, backend_flag) = {
-                    let mut msg_info = match ctx.data.info.try_borrow_mut() {
-                        Ok(value) => value,
-                        Err(_) => {
-                            let error = AfbError::new(
-                                "fail-borrow-info",
-                                0,
-                                "internal pool error (msg info cell already used)",
-                            );
-                            return Err(afb_add_trace!(error));
-                        },
-                    };
-
-                    if flag == SubscribeFlag::ALL {
-                        msg_info.flag = SubscribeFlag::ALL;
-                    } else {
-                        msg_info.flag = flag.clone();
-                    }
-
-                    if rate < msg_info.rate {
-                        msg_info.rate = rate;
-                    }
-                    if watchdog < msg_info.watchdog {
-                        msg_info.watchdog = watchdog;
-                    }
-
-                    msg_info.stamp = 1;
-
-                    (msg_info.watchdog, msg_info.rate, msg_info.flag.clone())
-                };
-
-                AfbSubCall::call_sync(
-                    request,
-                    ctx.data.bcm,
-                    "subscribe",
-                    SubscribeParam::new(
-                        vec![msg_canid],
-                        backend_watchdog,
-                        backend_rate,
-                        backend_flag,
-                    ),
-                )?;
-            }
+            // The backend runs at the most demanding parameters of all subscribers.
+            let session = dbc_session(request)?;
+            ctx.data.set_demand(request, (session.id, msg_name), Some(demand))?;
+            let msg_ctx = ctx.data.clone();
+            session
+                .leases
+                .insert(msg_name, Box::new(move |id| msg_ctx.drop_demand((id, msg_name))));
 
-            request.reply(format!("Subscribe (canid:{}) msg:{} OK", msg_canid, msg_name), 0);
+            request.reply(format!("Subscribe (canid:{}) msg:{} OK", ctx.data.canid, msg_name), 0);
         },
         logic::Action::Unsubscribe => {
             ctx.data.event.unsubscribe(request)?;
 
-            let (msg_canid, msg_name) = {
-                let msg = match ctx.msg_rfc.try_borrow() {
-                    Ok(value) => value,
-                    Err(_) => {
-                        let error = AfbError::new(
-                            "fail-borrow-msg",
-                            0,
-                            "internal pool error (msg cell already used)",
-                        );
-                        return Err(afb_add_trace!(error));
-                    },
-                };
-
-                (msg.get_id(), msg.get_name().to_string())
+            let msg_name = match ctx.msg_rfc.try_borrow() {
+                Ok(msg) => msg.get_name(),
+                Err(_) => {
+                    let error = AfbError::new(
+                        "fail-borrow-msg",
+                        0,
+                        "internal pool error (msg cell already used)",
+                    );
+                    return Err(afb_add_trace!(error));
+                },
             };
 
-            request.reply(format!("UnSubscribe (canid:{}) msg:{} OK", msg_canid, msg_name), 0);
+            // The last subscriber of the message also removes the backend subscription.
+            let session = dbc_session(request)?;
+            session.leases.remove(msg_name);
+            ctx.data.set_demand(request, (session.id, msg_name), None)?;
+
+            request.reply(format!("UnSubscribe (canid:{}) msg:{} OK", ctx.data.canid, msg_name), 0);
         },
 
         logic::Action::Read => {
@@ -734,7 +1824,143 @@ fn message_vcb(request: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Res
 
             request.reply(format!("Reset (canid:{}) msg:{} OK", msg_canid, msg_name), 0);
         },
+
+        // Signal only actions, never produced by the mapping above.
+        logic::Action::History | logic::Action::Stats => {
+            return Err(AfbError::new("invalid-action", 0, "history/stats: signal verbs only"));
+        },
+
+        logic::Action::Info => {
+            let def = match &ctx.data.layout {
+                Some(def) => def,
+                None => return Err(layout_error("message")),
+            };
+            let verb = match ctx.msg_rfc.try_borrow() {
+                Ok(msg) => msg.get_name(),
+                Err(_) => {
+                    let error = AfbError::new(
+                        "fail-borrow-msg",
+                        0,
+                        "internal pool error (msg cell already used)",
+                    );
+                    return Err(afb_add_trace!(error));
+                },
+            };
+            let mut params = AfbParams::new();
+            params.push(msg_info(verb, def))?;
+            request.reply(params, 0);
+        },
+
+        logic::Action::Write => {
+            let (msg_canid, msg_name) = {
+                let msg = match ctx.msg_rfc.try_borrow() {
+                    Ok(value) => value,
+                    Err(_) => {
+                        let error = AfbError::new(
+                            "fail-borrow-msg",
+                            0,
+                            "internal pool error (msg cell already used)",
+                        );
+                        return Err(afb_add_trace!(error));
+                    },
+                };
+                (msg.get_id(), msg.get_name())
+            };
+
+            // Without `values` the last known frame is sent again as is.
+            let layout = ctx.data.layout.as_ref();
+            let values = match logic::parse_write_values(&jquery.to_string(), None, layout) {
+                Ok(values) => values,
+                Err(info) => return Err(AfbError::new("invalid-write-value", 0, info)),
+            };
+            write_message(request, &ctx.data, msg_canid, &values)?;
+
+            request.reply(format!("Write (canid:{}) msg:{} OK", msg_canid, msg_name), 0);
+        },
+    };
+    Ok(())
+}
+
+/// Encode physical `values` into the message frame and send it through the sockcan binding.
+///
+/// Signals not written keep their last received value, or their DBC start value when the
+/// message was never received. The message layout comes from the `dbc` configuration key.
+/// Messages from the `transmit` section only get their cyclic frame updated.
+fn write_message(
+    request: &AfbRequest,
+    msg_ctx: &MessageDataCtx,
+    canid: u32,
+    values: &[(String, f64)],
+) -> Result<(), AfbError> {
+    let layout = match &msg_ctx.layout {
+        Some(layout) => layout,
+        None => {
+            return Err(AfbError::new(
+                "fail-write-layout",
+                0,
+                format!("canid:{} write requires the 'dbc' file in binding config", canid),
+            ))
+        },
+    };
+
+    let mut data = match msg_ctx.frame.borrow().as_ref() {
+        Some(frame) => frame.clone(),
+        None => layout.default_frame(),
     };
+    data.resize(layout.dlc as usize, 0);
+    if let Err(info) = layout.encode(&mut data, values) {
+        return Err(AfbError::new("invalid-write-value", 0, info));
+    }
+
+    if let Some(tx) = &msg_ctx.tx {
+        if let Err(canerr) = tx_setup(&tx.sockfd, canid, None, &data) {
+            let error = AfbError::new("fail-sockbcm-send", 0, canerr.to_string());
+            afb_log_msg!(Warning, request, &error);
+            return Err(error);
+        }
+        msg_ctx.frame.replace(Some(data));
+        return Ok(());
+    }
+
+    AfbSubCall::call_sync(
+        request,
+        msg_ctx.bcm,
+        "send",
+        SendParam {
+            canid,
+            data: data.clone(),
+            rtr: false,
+            len: 0,
+            period: 0,
+            count: 0,
+            stop: false,
+        },
+    )?;
+    msg_ctx.frame.replace(Some(data));
+    Ok(())
+}
+
+/// `catalog` verb context: message verbs with their runtime data, in pool order.
+struct CatalogCtx {
+    messages: Vec<(&'static str, Rc<MessageDataCtx>)>,
+}
+
+/// Pool-wide `catalog` verb: DBC definition of every message and signal.
+fn catalog_vcb(request: &AfbRequest, _args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<CatalogCtx>()?;
+
+    let messages: Vec<DataBcmMsgInfo> = ctx
+        .messages
+        .iter()
+        .filter_map(|(verb, msg_ctx)| msg_ctx.layout.as_ref().map(|def| msg_info(verb, def)))
+        .collect();
+    if messages.is_empty() && !ctx.messages.is_empty() {
+        return Err(layout_error("catalog"));
+    }
+
+    let mut params = AfbParams::new();
+    params.push(DataBcmCatalog { messages })?;
+    request.reply(params, 0);
     Ok(())
 }
 
@@ -743,15 +1969,163 @@ struct SockBcmConfig {
     _uid: &'static str,
     bcm: &'static str,
     _evt: &'static str,
-    jconf: JsoncObj,
+    messages: HashMap<String, SockcanMsgConfig>,
+    layouts: HashMap<u32, DbcMessageDef>,
+    transmit: HashMap<String, SockcanTxConfig>,
+    txsock: Option<Rc<SockCanHandle>>,
+    alarms: Vec<Rc<Alarm>>,
+    virtuals: Vec<Rc<VirtualSignal>>,
+    history: Vec<SockcanHistoryConfig>,
+}
+
+/// `alarms` verb context.
+///
+/// `messages` carry the signals of the rules, `listeners` the sessions subscribed to the
+/// `alarms` event. `watched` is set while the binding owned demand is installed.
+struct AlarmsVerbCtx {
+    alarms: Vec<Rc<Alarm>>,
+    event: &'static AfbEvent,
+    messages: Vec<Rc<MessageDataCtx>>,
+    watched: Rc<Cell<bool>>,
+    listeners: Rc<RefCell<HashSet<u64>>>,
+}
+
+impl AlarmsVerbCtx {
+    /// Ask the backend for every update of the watched messages, so rules run without
+    /// waiting for a signal subscriber.
+    fn watch(&self, request: &AfbRequest) -> Result<(), AfbError> {
+        if self.watched.get() {
+            return Ok(());
+        }
+        for msg_ctx in &self.messages {
+            let demand = logic::Demand {
+                rate: 0,
+                watchdog: msg_ctx.default.watchdog,
+                flag: SubscribeFlag::NEW,
+            };
+            msg_ctx.set_demand(request, (0, "alarms"), Some(demand))?;
+        }
+        self.watched.set(true);
+        Ok(())
+    }
+}
+
+/// Alarm verb: list, acknowledge and subscribe to the alarms of the `alarms` config.
+///
+/// - `{'action':'list'}` (default): active and latched alarms, every one with `'all':true`,
+/// - `{'action':'ack','uid':'name'}`: acknowledge a latched alarm (`'*'` for all of them),
+/// - `{'action':'subscribe'}` / `{'action':'unsubscribe'}`: RAISED/CLEARED/ACKED events.
+///
+/// Rules only see frames the backend forwards: the first `subscribe` asks the backend for
+/// every update of the watched messages until the last `alarms` listener leaves.
+fn alarms_vcb(request: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<AlarmsVerbCtx>()?;
+    let jquery = args.get::<JsoncObj>(0)?;
+    let action = jquery.get::<String>("action").unwrap_or_else(|_| "list".to_string());
+    let action = action.to_uppercase();
+
+    match action.as_str() {
+        "LIST" => {
+            let all = jquery.get::<bool>("all").unwrap_or(false);
+            let mut alarms = Vec::new();
+            for alarm in &ctx.alarms {
+                let state = match alarm.state.try_borrow() {
+                    Ok(state) => state,
+                    Err(_) => continue,
+                };
+                if all || state.active || state.latched {
+                    let status = if state.latched {
+                        CanAlarmStatus::RAISED
+                    } else if state.active {
+                        CanAlarmStatus::ACKED
+                    } else {
+                        CanAlarmStatus::CLEARED
+                    };
+                    alarms.push(alarm.snapshot(&state, status));
+                }
+            }
+            let mut params = AfbParams::new();
+            params.push(DataBcmAlarms { alarms })?;
+            request.reply(params, 0);
+        },
+        "ACK" => {
+            let uid = jquery.get::<String>("uid")?;
+            let mut count = 0;
+            for alarm in ctx.alarms.iter().filter(|alarm| uid == "*" || alarm.uid == uid) {
+                let mut state = match alarm.state.try_borrow_mut() {
+                    Ok(state) => state,
+                    Err(_) => continue,
+                };
+                if state.acknowledge() {
+                    count += 1;
+                    ctx.event.push(alarm.snapshot(&state, CanAlarmStatus::ACKED));
+                }
+            }
+            if count == 0 && uid != "*" && !ctx.alarms.iter().any(|alarm| alarm.uid == uid) {
+                let error =
+                    AfbError::new("invalid-alarm-uid", 0, format!("unknown alarm uid:{}", uid));
+                afb_log_msg!(Warning, request, &error);
+                return Err(error);
+            }
+            request.reply(format!("Ack alarms:{} OK", count), 0);
+        },
+        "SUBSCRIBE" => {
+            if let Err(error) = ctx.watch(request) {
+                afb_log_msg!(Warning, request, &error);
+            }
+            ctx.event.subscribe(request)?;
+            let session = dbc_session(request)?;
+            ctx.listeners.borrow_mut().insert(session.id);
+
+            // A session closing without unsubscribing leaves the listeners the same way.
+            let listeners = ctx.listeners.clone();
+            let watched = ctx.watched.clone();
+            let messages = ctx.messages.clone();
+            session.leases.insert(
+                "alarms",
+                Box::new(move |id| {
+                    let mut listeners = listeners.borrow_mut();
+                    if listeners.remove(&id) && listeners.is_empty() && watched.replace(false) {
+                        for msg_ctx in &messages {
+                            msg_ctx.drop_demand((0, "alarms"));
+                        }
+                    }
+                }),
+            );
+            request.reply(AFB_NO_DATA, 0);
+        },
+        "UNSUBSCRIBE" => {
+            ctx.event.unsubscribe(request)?;
+            let session = dbc_session(request)?;
+            session.leases.remove("alarms");
+            let last = {
+                let mut listeners = ctx.listeners.borrow_mut();
+                listeners.remove(&session.id) && listeners.is_empty()
+            };
+            if last && ctx.watched.replace(false) {
+                for msg_ctx in &ctx.messages {
+                    msg_ctx.set_demand(request, (0, "alarms"), None)?;
+                }
+            }
+            request.reply(AFB_NO_DATA, 0);
+        },
+        _ => {
+            let error =
+                AfbError::new("invalid-action", 0, "expect: LIST|ACK|SUBSCRIBE|UNSUBSCRIBE");
+            return Err(error);
+        },
+    }
+    Ok(())
 }
 
 /// Create a verb for a message, its event, and a group for its signals.
+///
+/// Returns the message runtime context so backend notifications can reach its event.
 fn register_msg(
     api: &mut afbv4::apiv4::AfbApi,
     config: &SockBcmConfig,
     msg_rfc: &Rc<RefCell<Box<dyn CanDbcMessage>>>,
-) -> Result<(), AfbError> {
+) -> Result<Rc<MessageDataCtx>, AfbError> {
     let msg_ref = msg_rfc.clone();
     let mut msg = match msg_ref.try_borrow_mut() {
         Err(_) => {
@@ -775,19 +2149,21 @@ fn register_msg(
         stamp: 0,
         listeners: 0,
         flag: SubscribeFlag::NEW,
+        matches: None,
+        filter: None,
     };
 
-    // Optional extra verb parameters from JSON (rate/watchdog/acls/info).
-    if let Ok(jverb) = config.jconf.get::<JsoncObj>(msg_name) {
-        if let Ok(value) = jverb.get::<String>("info") {
-            msg_verb.set_info(to_static_str(value));
+    // Optional extra verb parameters from the `messages` config section (rate/watchdog/info).
+    if let Some(msg_config) = config.messages.get(msg_name) {
+        if let Some(value) = &msg_config.info {
+            msg_verb.set_info(to_static_str(value.clone()));
         } else {
             msg_verb.set_info(to_static_str(format!("(canid:{})", msg.get_id())));
         }
-        if let Ok(rate) = jverb.get::<u64>("rate") {
+        if let Some(rate) = msg_config.rate {
             info.rate = rate
         }
-        if let Ok(watchdog) = jverb.get::<u64>("watchdog") {
+        if let Some(watchdog) = msg_config.watchdog {
             info.watchdog = watchdog
         }
     } else {
@@ -797,15 +2173,64 @@ fn register_msg(
     // Create a message-wide event and its runtime context.
     let event = AfbEvent::new(msg_name).finalize()?;
 
-    let vcbdata = Rc::new(MessageDataCtx { bcm: config.bcm, event, info: RefCell::new(info) });
+    let layout = config.layouts.get(&msg_id).cloned();
+    let tx = match (config.transmit.get(msg_name), &config.txsock) {
+        (Some(transmit), Some(sockfd)) => {
+            let period = match (transmit.period, layout.as_ref().and_then(|def| def.cycle)) {
+                (Some(period), _) | (None, Some(period)) => period,
+                (None, None) => {
+                    return Err(AfbError::new(
+                        "invalid-config",
+                        0,
+                        format!("transmit.{}: no GenMsgCycleTime in DBC, set 'period'", msg_name),
+                    ))
+                },
+            };
+            Some(TxCycle { sockfd: sockfd.clone(), period })
+        },
+        _ => None,
+    };
+
+    // Subscribers omitting rate/watchdog/flag get the configured message defaults.
+    let default =
+        logic::Demand { rate: info.rate, watchdog: info.watchdog, flag: info.flag.clone() };
+    let vcbdata = Rc::new(MessageDataCtx {
+        bcm: config.bcm,
+        event,
+        info: RefCell::new(info),
+        layout,
+        frame: RefCell::new(None),
+        tx,
+        sets: RefCell::new(Vec::new()),
+        virtuals: RefCell::new(Vec::new()),
+        canid: msg_id,
+        default,
+        demands: RefCell::new(HashMap::new()),
+        resync: Cell::new(false),
+    });
+
+    // Emulated ECU: the kernel sends the DBC start values until a `write` changes them.
+    if let (Some(tx), Some(layout)) = (&vcbdata.tx, &vcbdata.layout) {
+        let data = layout.default_frame();
+        if let Err(canerr) = tx_setup(&tx.sockfd, msg_id, Some(tx.period), &data) {
+            return Err(AfbError::new(
+                "fail-transmit-start",
+                0,
+                format!("transmit.{}: {}", msg_name, canerr),
+            ));
+        }
+        vcbdata.frame.replace(Some(data));
+    }
 
     // Attach controller so pool updates push to this event.
     msg.set_callback(Box::new(MessagePoolCtx { data: vcbdata.clone() }));
 
     // Finalize and register the message verb.
     msg_verb
-        .set_actions("['reset','read','subscribe','unsubscribe']")?
+        .set_actions("['reset','read','subscribe','unsubscribe','write','info']")?
         .add_sample("{'action':'subscribe','rate':250,'watchdog':5000,'flag':'new'}")?
+        .add_sample("{'action':'write','values':{'signal':1}}")?
+        .add_sample("{'action':'info'}")?
         .set_callback(message_vcb)
         .set_context(MessageVerbCtx { msg_rfc: msg_rfc.clone(), data: vcbdata.clone() })
         .finalize()?;
@@ -824,20 +2249,16 @@ fn register_msg(
     for (sidx, sig_rfc) in sigs.iter().enumerate() {
         // Now actually register the signal; borrow from above is dropped
 
-        let (sverb, sevent) =
-            match register_signal(config, &vcbdata, msg_name, msg_id, msg_rfc, sig_rfc) {
-                Ok(tuple) => tuple,
-                Err(err) => {
-                    println!(
+        let (sverb, sevent) = match register_signal(config, &vcbdata, msg_name, msg_rfc, sig_rfc) {
+            Ok(tuple) => tuple,
+            Err(err) => {
+                println!(
                     "register_msg:   register_signal FAILED for msg='{}' (canid={}) ='{}' -> {:?}",
-                    msg_name,
-                    msg_id,
-                    sidx,
-                    err
+                    msg_name, msg_id, sidx, err
                 );
-                    return Err(err);
-                },
-            };
+                return Err(err);
+            },
+        };
 
         group = group.add_verb(sverb);
         group = group.add_event(sevent);
@@ -845,18 +2266,38 @@ fn register_msg(
     group.finalize()?;
     api.add_group(group);
 
-    Ok(())
+    Ok(vcbdata)
 }
 
 /// Context passed to the low-level event handler (backend → pool).
+///
+/// `msgs` maps each CAN ID to its message context, used to forward watchdog notifications.
 struct EvtUserData {
     pool: &'static mut dyn CanDbcPool,
+    msgs: HashMap<u32, Rc<MessageDataCtx>>,
 }
 
 /// Handler for raw BCM frames coming from the backend; updates the pool.
+///
+/// Backend watchdog notifications (`CanBcmTimeout` / `CanBcmAlive`) are not decoded;
+/// they are forwarded as-is to the event of the matching message.
 fn bcm_event_cb(event: &AfbEventMsg, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
     let ctx: &EvtUserData = ctx.get_ref::<EvtUserData>()?;
 
+    if let Ok(timeout) = args.get::<&CanBcmTimeout>(0) {
+        if let Some(msg_ctx) = ctx.msgs.get(&timeout.canid) {
+            msg_ctx.event.push(timeout.clone());
+        }
+        return Ok(());
+    }
+
+    if let Ok(alive) = args.get::<&CanBcmAlive>(0) {
+        if let Some(msg_ctx) = ctx.msgs.get(&alive.canid) {
+            msg_ctx.event.push(alive.clone());
+        }
+        return Ok(());
+    }
+
     // Extract backend CAN frame as CanBcmData.
     let bcm_frame = match args.get::<&CanBcmData>(0) {
         Err(_) => {
@@ -889,6 +2330,18 @@ fn bcm_event_cb(event: &AfbEventMsg, args: &AfbRqtData, ctx: &AfbCtxData) -> Res
         return Ok(());
     }
 
+    // Keep the last payload so `write` only changes the requested signals. Messages the
+    // binding transmits keep their own frame, a received copy must not rewrite the cycle.
+    if let Some(msg_ctx) = ctx.msgs.get(&pool_frame.canid) {
+        if msg_ctx.layout.is_some() && msg_ctx.tx.is_none() {
+            msg_ctx.frame.replace(Some(pool_frame.data.to_vec()));
+        }
+        // Demands released by closed sessions are applied from here.
+        if let Err(error) = msg_ctx.resync_demand(event.get_api()) {
+            afb_log_msg!(Warning, event, &error);
+        }
+    }
+
     match ctx.pool.update(&pool_frame) {
         Err(_) => {
             let error = AfbError::new(
@@ -909,33 +2362,172 @@ fn bcm_event_cb(event: &AfbEventMsg, args: &AfbRqtData, ctx: &AfbCtxData) -> Res
 /// This wires:
 /// - verbs per signal and per message,
 /// - a backend event handler that receives raw BCM frames and updates the pool.
+///
+/// `config` is the binding configuration validated by `parse_dbc_config`:
+/// `uid`, `sock_api`, `sock_evt` and the optional `messages` section are used here.
+/// When `dbc` is set, its message layouts enable the `write` action and the `transmit`
+/// section, whose messages are cyclically sent on `dev` by the binding itself.
 pub fn create_pool_verbs(
     api_root: AfbApiV4,
     api: &mut afbv4::apiv4::AfbApi,
-    jconf: JsoncObj,
+    config: &SockcanBindingConfig,
+    pool_box: Box<dyn CanDbcPool>,
+) -> Result<(), AfbError> {
+    let layouts = match config.dbc {
+        Some(path) => dynpool::load_dbc(path)?,
+        None => DbcFile::default(),
+    };
+    register_pool(api_root, api, config, pool_box, layouts)
+}
+
+fn register_pool(
+    api_root: AfbApiV4,
+    api: &mut afbv4::apiv4::AfbApi,
+    config: &SockcanBindingConfig,
     pool_box: Box<dyn CanDbcPool>,
+    layouts: DbcFile,
 ) -> Result<(), AfbError> {
     // Register data converters for sockdata <-> afb types.
     sockdata_register(api_root)?;
 
-    // Read from `args` subobject if present, otherwise fall back to the root object (compat).
-    let conf = match jconf.get::<JsoncObj>("args") {
-        Ok(a) => a,
-        Err(_) => jconf.clone(),
-    };
-
     // Basic runtime configuration.
-    let uid = to_static_str(conf.get::<String>("uid")?);
-    let bcm = to_static_str(conf.get::<String>("sock_api")?);
-    let evt = to_static_str(conf.get::<String>("sock_evt")?);
+    let uid = config.api_uid;
+    let bcm = config.sock_api;
+    if bcm.is_empty() {
+        return Err(AfbError::new(
+            "invalid-config",
+            0,
+            "sock_api: DBC verbs need the sockcan API name (use parse_dbc_config)",
+        ));
+    }
+    let evt = config.event_uid;
 
     // Leak the pool to bind its lifetime to the API (intended design in this binding).
     let pool = Box::leak(pool_box);
 
-    let bcm_config = SockBcmConfig { _uid: uid, bcm, _evt: evt, jconf };
-
     let msgs = pool.get_messages();
 
+    // Messages sent by the binding need their DBC layout and one BCM socket for all cycles.
+    let mut txsock = None;
+    for name in config.transmit.keys() {
+        let layout = msgs.iter().find_map(|msg_rfc| match msg_rfc.try_borrow() {
+            Ok(msg) if msg.get_name() == name => {
+                Some(layouts.messages.iter().any(|def| def.id == msg.get_id()))
+            },
+            _ => None,
+        });
+        match layout {
+            Some(true) => {},
+            Some(false) => {
+                return Err(AfbError::new(
+                    "invalid-config",
+                    0,
+                    format!("transmit.{}: requires the 'dbc' file in binding config", name),
+                ))
+            },
+            None => {
+                return Err(AfbError::new(
+                    "invalid-config",
+                    0,
+                    format!("transmit.{}: unknown message", name),
+                ))
+            },
+        }
+        if txsock.is_none() {
+            match SockCanHandle::open_bcm(config.can_device, CanTimeStamp::CLASSIC) {
+                Ok(sockfd) => txsock = Some(Rc::new(sockfd)),
+                Err(canerr) => {
+                    return Err(AfbError::new("fail-sockbcm-open", 0, canerr.to_string()))
+                },
+            }
+        }
+    }
+
+    // Alarm rules share one `alarms` event; each one is attached to its signal below.
+    let alarm_event = if config.alarms.is_empty() {
+        None
+    } else {
+        let event = AfbEvent::new("alarms").finalize()?;
+        api.add_event(event);
+        Some(event)
+    };
+    let mut alarms = Vec::new();
+    for (idx, alarm) in config.alarms.iter().enumerate() {
+        let rule = match logic::parse_alarm_rule(&alarm.when) {
+            Ok(rule) => rule,
+            Err(info) => {
+                return Err(AfbError::new(
+                    "invalid-config",
+                    0,
+                    format!("alarms[{}].when: {}", idx, info),
+                ))
+            },
+        };
+        if let Some(event) = alarm_event {
+            alarms.push(Rc::new(Alarm {
+                uid: to_static_str(alarm.uid.clone()),
+                msg: RefCell::new(None),
+                severity: alarm.severity,
+                info: to_static_str(alarm.info.clone()),
+                rule,
+                state: RefCell::new(logic::AlarmState::default()),
+                event,
+                attached: Cell::new(false),
+            }));
+        }
+    }
+
+    let mut virtuals = Vec::new();
+    for (idx, virt) in config.virtuals.iter().enumerate() {
+        let (expr, inputs) = match logic::parse_expr(&virt.expr) {
+            Ok(parsed) => parsed,
+            Err(info) => {
+                return Err(AfbError::new(
+                    "invalid-config",
+                    0,
+                    format!("virtuals[{}].expr: {}", idx, info),
+                ))
+            },
+        };
+        let uid = to_static_str(format!("virtual/{}", virt.uid));
+        let event = AfbEvent::new(uid).finalize()?;
+        virtuals.push(Rc::new(VirtualSignal {
+            uid,
+            expr,
+            values: RefCell::new(vec![None; inputs.len()]),
+            attached: RefCell::new(vec![false; inputs.len()]),
+            inputs,
+            messages: RefCell::new(Vec::new()),
+            default: logic::Demand {
+                rate: MSG_DFT_RATE,
+                watchdog: MSG_DFT_WATCHDOG,
+                flag: SubscribeFlag::NEW,
+            },
+            average: RefCell::new(virt.average.map(logic::MovingAverage::new)),
+            subscribers: RefCell::new(HashMap::new()),
+            value: Cell::new(None),
+            stamp: Cell::new(0),
+            status: Cell::new(CanDataStatus::Unset),
+            event,
+        }));
+    }
+
+    let bcm_config = SockBcmConfig {
+        _uid: uid,
+        bcm,
+        _evt: evt,
+        messages: config.messages.clone(),
+        layouts: layouts.messages.into_iter().map(|def| (def.id, def)).collect(),
+        transmit: config.transmit.clone(),
+        txsock,
+        alarms: alarms.clone(),
+        virtuals: virtuals.clone(),
+        history: config.history.clone(),
+    };
+    let mut msg_ctxs = HashMap::new();
+    let mut catalog = Vec::new();
+    let mut set_entries = Vec::new();
+
     for (idx, msg_rfc) in msgs.iter().enumerate() {
         let (canid, name) = match msg_rfc.try_borrow() {
             Ok(m) => {
@@ -946,16 +2538,123 @@ pub fn create_pool_verbs(
             Err(_e) => (0, "<borrow_failed>"),
         };
 
-        if let Err(err) = register_msg(api, &bcm_config, msg_rfc) {
-            println!(
-                "create_pool_verbs: register_msg FAILED at idx={} canid={} name={} -> {:?}",
-                idx, canid, name, err
-            );
-            // We return the error to see if this is what breaks the loop.
-            return Err(err);
+        match register_msg(api, &bcm_config, msg_rfc) {
+            Ok(msg_ctx) => {
+                let signals = match msg_rfc.try_borrow() {
+                    Ok(msg) => msg
+                        .get_signals()
+                        .iter()
+                        .filter_map(|sig_rfc| sig_rfc.try_borrow().ok().map(|sig| sig.get_name()))
+                        .collect(),
+                    Err(_) => Vec::new(),
+                };
+                set_entries.push(SetEntry { name, canid, signals, data: msg_ctx.clone() });
+                catalog.push((name, msg_ctx.clone()));
+                msg_ctxs.insert(canid, msg_ctx);
+            },
+            Err(err) => {
+                println!(
+                    "create_pool_verbs: register_msg FAILED at idx={} canid={} name={} -> {:?}",
+                    idx, canid, name, err
+                );
+                // We return the error to see if this is what breaks the loop.
+                return Err(err);
+            },
         }
     }
 
+    let catalog_verb = AfbVerb::new("catalog")
+        .set_callback(catalog_vcb)
+        .set_context(CatalogCtx { messages: catalog })
+        .set_info("DBC definition of every message and signal")
+        .set_usage("{}")
+        .finalize()?;
+    api.add_verb(catalog_verb);
+
+    if let Some(alarm) = alarms.iter().find(|alarm| !alarm.attached.get()) {
+        return Err(AfbError::new(
+            "invalid-config",
+            0,
+            format!("alarms.{}: unknown signal '{}'", alarm.uid, alarm.rule.signal),
+        ));
+    }
+    if let Some(event) = alarm_event {
+        let mut messages: Vec<Rc<MessageDataCtx>> = Vec::new();
+        for alarm in &alarms {
+            if let Some(msg_ctx) = alarm.msg.borrow().as_ref() {
+                if !messages.iter().any(|msg| Rc::ptr_eq(msg, msg_ctx)) {
+                    messages.push(msg_ctx.clone());
+                }
+            }
+        }
+        let alarms_verb = AfbVerb::new("alarms")
+            .set_callback(alarms_vcb)
+            .set_context(AlarmsVerbCtx {
+                alarms,
+                event,
+                messages,
+                watched: Rc::new(Cell::new(false)),
+                listeners: Rc::new(RefCell::new(HashSet::new())),
+            })
+            .set_info("List, acknowledge and subscribe to signal alarms")
+            .set_usage("{['action':'list'|'ack'|'subscribe'|'unsubscribe'],['uid':'name'|'*'],['all':true]}")
+            .add_sample("{'action':'list'}")?
+            .add_sample("{'action':'ack','uid':'*'}")?
+            .finalize()?;
+        api.add_verb(alarms_verb);
+    }
+
+    // Virtual signals live in their own group, next to the message groups.
+    if !virtuals.is_empty() {
+        let mut group = AfbGroup::new("virtual")
+            .set_info("Signals computed from DBC signals")
+            .set_prefix("virtual");
+        for virt in virtuals {
+            let attached = virt.attached.borrow().clone();
+            if let Some((input, _)) =
+                virt.inputs.iter().zip(attached).find(|(_, attached)| !attached)
+            {
+                return Err(AfbError::new(
+                    "invalid-config",
+                    0,
+                    format!("{}: unknown signal '{}'", virt.uid, input),
+                ));
+            }
+            let name = &virt.uid["virtual/".len()..];
+            let verb = AfbVerb::new(name)
+                .set_callback(virtual_vcb)
+                .set_context(VirtualVerbCtx { data: virt.clone() })
+                .set_actions("['read','subscribe','unsubscribe']")?
+                .add_sample("{'action':'subscribe','rate':250,'watchdog':5000,'flag':'new'}")?
+                .finalize()?;
+            group = group.add_verb(verb).add_event(virt.event);
+        }
+        group.finalize()?;
+        api.add_group(group);
+    }
+
+    let unset_verb = AfbVerb::new("unsubscribe_set")
+        .set_callback(unsubscribe_set_vcb)
+        .set_context(UnsetVerbCtx {
+            messages: set_entries.iter().map(|entry| entry.data.clone()).collect(),
+        })
+        .set_info("Close a set opened by subscribe_set")
+        .set_usage("{'uid':'set/N'}")
+        .add_sample("{'uid':'set/1'}")?
+        .finalize()?;
+    api.add_verb(unset_verb);
+
+    let set_verb = AfbVerb::new("subscribe_set")
+        .set_callback(subscribe_set_vcb)
+        .set_context(SetVerbCtx { messages: set_entries, count: Cell::new(0) })
+        .set_info("Subscribe to many signals with one aggregated event")
+        .set_usage(
+            "{'signals':['message/signal',...],['rate':ms],['watchdog':ms],['flag':'new'|'all']}",
+        )
+        .add_sample("{'signals':['*/*Volt*'],'rate':500,'flag':'new'}")?
+        .finalize()?;
+    api.add_verb(set_verb);
+
     // Subscribe to backend raw frames (bcm/evt) and feed the DBC pool.
     //let pattern = to_static_str(format!("{}/{}", bcm, evt));
     let pattern = "*";
@@ -963,7 +2662,7 @@ pub fn create_pool_verbs(
         .set_info("Receive low-level BCM data frame")
         .set_pattern(pattern)
         .set_callback(bcm_event_cb)
-        .set_context(EvtUserData { pool });
+        .set_context(EvtUserData { pool, msgs: msg_ctxs });
 
     evt_handler.register(api_root);
     evt_handler.finalize()?;
@@ -973,14 +2672,36 @@ pub fn create_pool_verbs(
     Ok(())
 }
 
+/// Create verbs/events/groups from the DBC file named by the `dbc` configuration key.
+///
+/// The file is parsed when the binding starts (see `DbcDynPool`), so a single generic
+/// binding can serve any vehicle without regenerating and rebuilding the pool.
+pub fn create_dbc_verbs(
+    api_root: AfbApiV4,
+    api: &mut afbv4::apiv4::AfbApi,
+    config: &SockcanBindingConfig,
+) -> Result<(), AfbError> {
+    let path = match config.dbc {
+        Some(path) => path,
+        None => return Err(AfbError::new("invalid-config", 0, "dbc: missing DBC file path")),
+    };
+
+    let dbc = dynpool::load_dbc(path)?;
+    let pool = DbcDynPool::build(config.api_uid, dbc.clone())?;
+    afb_log_msg!(Notice, api_root, "dbc:{} loaded messages:{}", path, pool.get_messages().len());
+    register_pool(api_root, api, config, Box::new(pool), dbc)
+}
+
 //-------------------------------------
 // for benchmarking and testing purposes
 //-------------------------------------
 
 // Keep this module pure: no Afb types, no I/O.
 pub mod logic {
-    use sockcan::prelude::CanDataStatus;
+    use crate::dbcfile::{DbcMessageDef, DbcSignalDef};
+    use sockcan::prelude::{CanDataStatus, CanDbcType};
     use sockdata::types::SubscribeFlag;
+    use std::collections::VecDeque;
 
     #[derive(Copy, Clone, Debug, Eq, PartialEq)]
     pub enum Action {
@@ -988,6 +2709,10 @@ pub mod logic {
         Unsubscribe,
         Read,
         Reset,
+        Write,
+        Info,
+        History,
+        Stats,
     }
 
     pub fn parse_action(s: &str) -> Option<Action> {
@@ -1000,6 +2725,14 @@ pub mod logic {
             Some(Action::Read)
         } else if s.eq_ignore_ascii_case("RESET") {
             Some(Action::Reset)
+        } else if s.eq_ignore_ascii_case("WRITE") || s.eq_ignore_ascii_case("SET") {
+            Some(Action::Write)
+        } else if s.eq_ignore_ascii_case("INFO") || s.eq_ignore_ascii_case("DESCRIBE") {
+            Some(Action::Info)
+        } else if s.eq_ignore_ascii_case("HISTORY") {
+            Some(Action::History)
+        } else if s.eq_ignore_ascii_case("STATS") {
+            Some(Action::Stats)
         } else {
             None
         }
@@ -1016,6 +2749,878 @@ pub mod logic {
         }
     }
 
+    /// Numeric view of a decoded signal value (booleans as 1/0), whatever its `CanDbcType`.
+    pub fn dbc_number(value: &CanDbcType) -> Option<f64> {
+        fn number(value: &serde_json::Value) -> Option<f64> {
+            match value {
+                serde_json::Value::Bool(value) => Some(if *value { 1.0 } else { 0.0 }),
+                serde_json::Value::Number(value) => value.as_f64(),
+                // Externally tagged enum variant: {"F64":1.5}
+                serde_json::Value::Object(entries) if entries.len() == 1 => {
+                    entries.values().next().and_then(number)
+                },
+                _ => None,
+            }
+        }
+        number(&serde_json::to_value(value).ok()?)
+    }
+
+    /// Physical value of a JSON number, boolean (1/0) or value table label of `def`.
+    pub fn json_value(
+        name: &str,
+        value: &serde_json::Value,
+        def: Option<&DbcSignalDef>,
+    ) -> Result<f64, String> {
+        match value {
+            serde_json::Value::Bool(value) => Ok(if *value { 1.0 } else { 0.0 }),
+            serde_json::Value::Number(value) => {
+                value.as_f64().ok_or_else(|| format!("{}: invalid number", name))
+            },
+            serde_json::Value::String(label) => def
+                .and_then(|def| def.label_value(label))
+                .ok_or_else(|| format!("{}: unknown value label '{}'", name, label)),
+            _ => Err(format!("{}: expect a number, a boolean or a label", name)),
+        }
+    }
+
+    /// Extract the physical values of a `write` request from its JSON text.
+    ///
+    /// A signal verb (`signal` set) takes `{'value':x}`, a message verb takes
+    /// `{'values':{'Signal':x,...}}`. Booleans are written as 1/0, strings are resolved
+    /// through the value table of the signal in `layout`.
+    pub fn parse_write_values(
+        json: &str,
+        signal: Option<&str>,
+        layout: Option<&DbcMessageDef>,
+    ) -> Result<Vec<(String, f64)>, String> {
+        let query: serde_json::Value = match serde_json::from_str(json) {
+            Ok(query) => query,
+            Err(error) => return Err(error.to_string()),
+        };
+        let number = |name: &str, value: &serde_json::Value| {
+            json_value(name, value, layout.and_then(|layout| layout.signal(name)))
+        };
+
+        match signal {
+            Some(signal) => match query.get("value") {
+                Some(value) => Ok(vec![(signal.to_string(), number(signal, value)?)]),
+                None => Err("missing 'value'".to_string()),
+            },
+            None => {
+                let mut values = Vec::new();
+                if let Some(entries) = query.get("values") {
+                    let entries = match entries.as_object() {
+                        Some(entries) => entries,
+                        None => return Err("'values' expects an object".to_string()),
+                    };
+                    for (name, value) in entries {
+                        values.push((name.clone(), number(name, value)?));
+                    }
+                }
+                Ok(values)
+            },
+        }
+    }
+
+    /// Read the optional `match` subscribe filter: values (numbers or labels) a signal must
+    /// hold for its updates to be pushed.
+    pub fn parse_match_filter(
+        json: &str,
+        name: &str,
+        def: Option<&DbcSignalDef>,
+    ) -> Result<Option<Vec<f64>>, String> {
+        let query: serde_json::Value = match serde_json::from_str(json) {
+            Ok(query) => query,
+            Err(error) => return Err(error.to_string()),
+        };
+        let entries = match query.get("match") {
+            None => return Ok(None),
+            Some(serde_json::Value::Array(entries)) => entries,
+            Some(value) => return Ok(Some(vec![json_value(name, value, def)?])),
+        };
+        let mut values = Vec::with_capacity(entries.len());
+        for value in entries {
+            values.push(json_value(name, value, def)?);
+        }
+        Ok(Some(values))
+    }
+
+    /// Return true when `value` passes the `match` filter (no filter lets everything through).
+    pub fn value_selected(filter: Option<&[f64]>, value: Option<f64>) -> bool {
+        match (filter, value) {
+            (None, _) => true,
+            (Some(filter), Some(value)) => filter.contains(&value),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Read the mandatory `signals` list of a `subscribe_set` request.
+    pub fn parse_set_patterns(json: &str) -> Result<Vec<String>, String> {
+        let query: serde_json::Value = match serde_json::from_str(json) {
+            Ok(query) => query,
+            Err(error) => return Err(error.to_string()),
+        };
+        let entries = match query.get("signals").and_then(|value| value.as_array()) {
+            Some(entries) if !entries.is_empty() => entries,
+            _ => return Err("expect a non empty 'signals' array".to_string()),
+        };
+        let mut patterns = Vec::with_capacity(entries.len());
+        for entry in entries {
+            match entry.as_str() {
+                Some(pattern) => patterns.push(pattern.to_string()),
+                None => return Err(format!("invalid signal pattern {}", entry)),
+            }
+        }
+        Ok(patterns)
+    }
+
+    /// Return true when `pattern` (`message/signal`, or `message` for all its signals)
+    /// selects signal `sig` of message `msg`.
+    pub fn set_selects(pattern: &str, msg: &str, sig: &str) -> bool {
+        match pattern.split_once('/') {
+            Some((msg_pattern, sig_pattern)) => {
+                glob_match(msg_pattern, msg) && glob_match(sig_pattern, sig)
+            },
+            None => glob_match(pattern, msg),
+        }
+    }
+
+    /// Case-insensitive match of `text` against `pattern` (`*` any run, `?` one character).
+    pub fn glob_match(pattern: &str, text: &str) -> bool {
+        let pattern = pattern.as_bytes();
+        let text = text.as_bytes();
+        let (mut pidx, mut tidx) = (0, 0);
+        let mut star: Option<(usize, usize)> = None;
+
+        while tidx < text.len() {
+            match pattern.get(pidx) {
+                Some(b'*') => {
+                    star = Some((pidx, tidx));
+                    pidx += 1;
+                },
+                Some(byte) if *byte == b'?' || byte.eq_ignore_ascii_case(&text[tidx]) => {
+                    pidx += 1;
+                    tidx += 1;
+                },
+                _ => match star {
+                    // Backtrack: let the last `*` swallow one more character.
+                    Some((spidx, stidx)) => {
+                        pidx = spidx + 1;
+                        tidx = stidx + 1;
+                        star = Some((spidx, stidx + 1));
+                    },
+                    None => return false,
+                },
+            }
+        }
+        pattern[pidx..].iter().all(|byte| *byte == b'*')
+    }
+
+    /// Band a value lies in, relative to the `above` / `below` thresholds.
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub enum Zone {
+        Above,
+        #[default]
+        Normal,
+        Below,
+    }
+
+    /// Deadband and threshold filter of a signal subscription.
+    ///
+    /// - `deadband`: push when the value moved by at least this amount since the last
+    ///   pushed value (`relative`: percent of the last pushed value),
+    /// - `above` / `below`: push when the value enters or leaves the band above/below the
+    ///   threshold; it leaves the band once back over `hysteresis` on the other side.
+    ///
+    /// With both, either a threshold crossing or a deadband move is pushed.
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub struct ValueFilter {
+        pub deadband: Option<f64>,
+        pub relative: bool,
+        pub above: Option<f64>,
+        pub below: Option<f64>,
+        pub hysteresis: f64,
+        last: Option<f64>,
+        zone: Zone,
+    }
+
+    impl ValueFilter {
+        pub fn zone(&self) -> Zone {
+            self.zone
+        }
+
+        fn next_zone(&self, value: f64) -> Zone {
+            match self.zone {
+                Zone::Above if self.above.is_some_and(|above| value > above - self.hysteresis) => {
+                    return Zone::Above
+                },
+                Zone::Below if self.below.is_some_and(|below| value < below + self.hysteresis) => {
+                    return Zone::Below
+                },
+                _ => {},
+            }
+            if self.above.is_some_and(|above| value > above) {
+                Zone::Above
+            } else if self.below.is_some_and(|below| value < below) {
+                Zone::Below
+            } else {
+                Zone::Normal
+            }
+        }
+
+        /// Return true when `value` must be pushed, and remember it as the last pushed one.
+        pub fn accept(&mut self, value: f64) -> bool {
+            let zone = self.next_zone(value);
+            let crossed = zone != self.zone;
+            self.zone = zone;
+
+            let moved = match (self.deadband, self.last) {
+                (Some(_), None) => true,
+                (Some(deadband), Some(last)) => {
+                    let band = if self.relative { last.abs() * deadband / 100.0 } else { deadband };
+                    (value - last).abs() >= band
+                },
+                (None, _) => false,
+            };
+            let thresholds = self.above.is_some() || self.below.is_some();
+
+            let emit = match (thresholds, self.deadband.is_some()) {
+                (true, true) => crossed || moved,
+                (true, false) => crossed,
+                (false, true) => moved,
+                (false, false) => true,
+            };
+            if emit {
+                self.last = Some(value);
+            }
+            emit
+        }
+    }
+
+    /// Read the optional `deadband`, `above`, `below` and `hysteresis` subscribe keys.
+    ///
+    /// `deadband` is an absolute value or a `"N%"` string; thresholds also accept value
+    /// table labels of `def`.
+    pub fn parse_value_filter(
+        json: &str,
+        def: Option<&DbcSignalDef>,
+    ) -> Result<Option<ValueFilter>, String> {
+        let query: serde_json::Value = match serde_json::from_str(json) {
+            Ok(query) => query,
+            Err(error) => return Err(error.to_string()),
+        };
+        let mut filter = ValueFilter::default();
+
+        match query.get("deadband") {
+            None => {},
+            Some(serde_json::Value::String(text)) => {
+                let percent = text.trim().strip_suffix('%').map(str::trim);
+                match percent.and_then(|value| value.parse::<f64>().ok()) {
+                    Some(value) => {
+                        filter.deadband = Some(value);
+                        filter.relative = true;
+                    },
+                    None => return Err(format!("deadband: invalid percent '{}'", text)),
+                }
+            },
+            Some(value) => filter.deadband = Some(json_value("deadband", value, None)?),
+        }
+        if let Some(value) = query.get("above") {
+            filter.above = Some(json_value("above", value, def)?);
+        }
+        if let Some(value) = query.get("below") {
+            filter.below = Some(json_value("below", value, def)?);
+        }
+        if let Some(value) = query.get("hysteresis") {
+            filter.hysteresis = json_value("hysteresis", value, None)?;
+        }
+
+        if filter.deadband.is_some_and(|deadband| deadband.is_nan() || deadband < 0.0) {
+            return Err("deadband: must be >= 0".to_string());
+        }
+        if filter.hysteresis.is_nan() || filter.hysteresis < 0.0 {
+            return Err("hysteresis: must be >= 0".to_string());
+        }
+        if let (Some(above), Some(below)) = (filter.above, filter.below) {
+            if above <= below {
+                return Err(format!("above:{} must be greater than below:{}", above, below));
+            }
+        }
+
+        if filter.deadband.is_none() && filter.above.is_none() && filter.below.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(filter))
+    }
+
+    /// Comparison operator of an alarm rule.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub enum AlarmOp {
+        Gt,
+        Ge,
+        Lt,
+        Le,
+        Eq,
+        Ne,
+    }
+
+    /// Parsed alarm condition: `signal op value` holding for at least `delay` ms.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct AlarmRule {
+        pub signal: String,
+        pub op: AlarmOp,
+        pub value: f64,
+        pub delay: u64,
+    }
+
+    impl AlarmRule {
+        pub fn holds(&self, value: f64) -> bool {
+            match self.op {
+                AlarmOp::Gt => value > self.value,
+                AlarmOp::Ge => value >= self.value,
+                AlarmOp::Lt => value < self.value,
+                AlarmOp::Le => value <= self.value,
+                AlarmOp::Eq => value == self.value,
+                AlarmOp::Ne => value != self.value,
+            }
+        }
+    }
+
+    /// Parse `"Message/Signal <op> <value> [for <delay>]"`, delay in ms (`500`, `500ms`)
+    /// or seconds (`2s`).
+    pub fn parse_alarm_rule(text: &str) -> Result<AlarmRule, String> {
+        let mut tokens = text.split_whitespace();
+        let signal = match tokens.next() {
+            Some(signal) if signal.contains('/') => signal.to_string(),
+            _ => return Err(format!("expect 'Message/Signal' in '{}'", text)),
+        };
+        let op = match tokens.next() {
+            Some(">") => AlarmOp::Gt,
+            Some(">=") => AlarmOp::Ge,
+            Some("<") => AlarmOp::Lt,
+            Some("<=") => AlarmOp::Le,
+            Some("==") => AlarmOp::Eq,
+            Some("!=") => AlarmOp::Ne,
+            other => return Err(format!("invalid operator '{}'", other.unwrap_or_default())),
+        };
+        let value = tokens.next().ok_or("missing value")?;
+        let value = value.parse::<f64>().map_err(|_| format!("invalid value '{}'", value))?;
+
+        let delay = match (tokens.next(), tokens.next()) {
+            (None, _) => 0,
+            (Some("for"), Some(delay)) => {
+                let parsed = if let Some(ms) = delay.strip_suffix("ms") {
+                    ms.parse::<u64>().ok()
+                } else if let Some(sec) = delay.strip_suffix('s') {
+                    sec.parse::<f64>().ok().map(|sec| (sec * 1000.0) as u64)
+                } else {
+                    delay.parse::<u64>().ok()
+                };
+                parsed.ok_or(format!("invalid delay '{}'", delay))?
+            },
+            _ => return Err("expect 'for <delay>' after the value".to_string()),
+        };
+        if let Some(token) = tokens.next() {
+            return Err(format!("unexpected '{}'", token));
+        }
+        Ok(AlarmRule { signal, op, value, delay })
+    }
+
+    /// Alarm state transition reported by `AlarmState::update`.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub enum AlarmChange {
+        Raised,
+        Cleared,
+    }
+
+    /// Runtime state of an alarm rule; stamps are in µs like signal stamps.
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub struct AlarmState {
+        since: Option<u64>,
+        pub active: bool,
+        pub latched: bool,
+        pub value: f64,
+        pub stamp: u64,
+    }
+
+    impl AlarmState {
+        /// Feed a new signal value. The alarm is raised (and latched) once the condition
+        /// held for the rule delay, and cleared on the first value breaking it.
+        pub fn update(&mut self, rule: &AlarmRule, value: f64, stamp: u64) -> Option<AlarmChange> {
+            if rule.holds(value) {
+                let since = *self.since.get_or_insert(stamp);
+                if !self.active && stamp.saturating_sub(since) >= rule.delay.saturating_mul(1000) {
+                    self.active = true;
+                    self.latched = true;
+                    self.value = value;
+                    self.stamp = stamp;
+                    return Some(AlarmChange::Raised);
+                }
+            } else {
+                self.since = None;
+                if self.active {
+                    self.active = false;
+                    self.value = value;
+                    self.stamp = stamp;
+                    return Some(AlarmChange::Cleared);
+                }
+            }
+            None
+        }
+
+        /// Acknowledge a latched alarm; return false when there was nothing to acknowledge.
+        pub fn acknowledge(&mut self) -> bool {
+            std::mem::replace(&mut self.latched, false)
+        }
+    }
+
+    /// Binary operator of a virtual signal expression.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub enum ExprOp {
+        Add,
+        Sub,
+        Mul,
+        Div,
+    }
+
+    /// Virtual signal expression; `Input` indexes the input list returned by `parse_expr`.
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Expr {
+        Num(f64),
+        Input(usize),
+        Neg(Box<Expr>),
+        Bin(ExprOp, Box<Expr>, Box<Expr>),
+    }
+
+    impl Expr {
+        /// Value of the expression, None until every input is known or when not finite.
+        pub fn eval(&self, inputs: &[Option<f64>]) -> Option<f64> {
+            let value = match self {
+                Expr::Num(value) => *value,
+                Expr::Input(idx) => inputs.get(*idx).copied().flatten()?,
+                Expr::Neg(expr) => -expr.eval(inputs)?,
+                Expr::Bin(op, left, right) => {
+                    let (left, right) = (left.eval(inputs)?, right.eval(inputs)?);
+                    match op {
+                        ExprOp::Add => left + right,
+                        ExprOp::Sub => left - right,
+                        ExprOp::Mul => left * right,
+                        ExprOp::Div => left / right,
+                    }
+                },
+            };
+            value.is_finite().then_some(value)
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum ExprToken {
+        Num(f64),
+        Input(String),
+        Op(char),
+    }
+
+    /// Split an expression; a `/` between two names is part of a `Message/Signal` input.
+    fn expr_tokens(text: &str) -> Result<Vec<ExprToken>, String> {
+        let chars: Vec<char> = text.chars().collect();
+        let is_name = |chr: char| chr.is_ascii_alphanumeric() || chr == '_';
+        let mut tokens = Vec::new();
+        let mut idx = 0;
+        while idx < chars.len() {
+            let chr = chars[idx];
+            if chr.is_whitespace() {
+                idx += 1;
+            } else if "+-*/()".contains(chr) {
+                tokens.push(ExprToken::Op(chr));
+                idx += 1;
+            } else if chr.is_ascii_digit() || chr == '.' {
+                let start = idx;
+                while idx < chars.len() && (chars[idx].is_ascii_digit() || chars[idx] == '.') {
+                    idx += 1;
+                }
+                let number: String = chars[start..idx].iter().collect();
+                match number.parse::<f64>() {
+                    Ok(value) => tokens.push(ExprToken::Num(value)),
+                    Err(_) => return Err(format!("invalid number '{}'", number)),
+                }
+            } else if is_name(chr) {
+                let start = idx;
+                while idx < chars.len() && is_name(chars[idx]) {
+                    idx += 1;
+                }
+                if idx + 1 < chars.len()
+                    && chars[idx] == '/'
+                    && (chars[idx + 1].is_ascii_alphabetic() || chars[idx + 1] == '_')
+                {
+                    idx += 1;
+                    while idx < chars.len() && is_name(chars[idx]) {
+                        idx += 1;
+                    }
+                }
+                let name: String = chars[start..idx].iter().collect();
+                if !name.contains('/') {
+                    return Err(format!("expect 'Message/Signal' got '{}'", name));
+                }
+                tokens.push(ExprToken::Input(name));
+            } else {
+                return Err(format!("unexpected '{}'", chr));
+            }
+        }
+        Ok(tokens)
+    }
+
+    struct ExprParser {
+        tokens: Vec<ExprToken>,
+        pos: usize,
+        inputs: Vec<String>,
+    }
+
+    impl ExprParser {
+        fn next_op(&mut self, ops: &str) -> Option<char> {
+            match self.tokens.get(self.pos) {
+                Some(ExprToken::Op(op)) if ops.contains(*op) => {
+                    self.pos += 1;
+                    Some(*op)
+                },
+                _ => None,
+            }
+        }
+
+        fn sum(&mut self) -> Result<Expr, String> {
+            let mut expr = self.product()?;
+            while let Some(op) = self.next_op("+-") {
+                let op = if op == '+' { ExprOp::Add } else { ExprOp::Sub };
+                expr = Expr::Bin(op, Box::new(expr), Box::new(self.product()?));
+            }
+            Ok(expr)
+        }
+
+        fn product(&mut self) -> Result<Expr, String> {
+            let mut expr = self.unary()?;
+            while let Some(op) = self.next_op("*/") {
+                let op = if op == '*' { ExprOp::Mul } else { ExprOp::Div };
+                expr = Expr::Bin(op, Box::new(expr), Box::new(self.unary()?));
+            }
+            Ok(expr)
+        }
+
+        fn unary(&mut self) -> Result<Expr, String> {
+            if self.next_op("-").is_some() {
+                return Ok(Expr::Neg(Box::new(self.unary()?)));
+            }
+            let token = self.tokens.get(self.pos).cloned();
+            self.pos += 1;
+            match token {
+                Some(ExprToken::Num(value)) => Ok(Expr::Num(value)),
+                Some(ExprToken::Input(name)) => {
+                    let idx = match self.inputs.iter().position(|n| n.eq_ignore_ascii_case(&name)) {
+                        Some(idx) => idx,
+                        None => {
+                            self.inputs.push(name);
+                            self.inputs.len() - 1
+                        },
+                    };
+                    Ok(Expr::Input(idx))
+                },
+                Some(ExprToken::Op('(')) => {
+                    let expr = self.sum()?;
+                    match self.next_op(")") {
+                        Some(_) => Ok(expr),
+                        None => Err("missing ')'".to_string()),
+                    }
+                },
+                Some(ExprToken::Op(op)) => Err(format!("unexpected '{}'", op)),
+                None => Err("unexpected end of expression".to_string()),
+            }
+        }
+    }
+
+    /// Parse `"BmsStatus/PackVoltage * BmsStatus/PackCurrent / 1000"`: numbers, `Message/Signal`
+    /// inputs, `+ - * /`, unary minus and parentheses. Returns the expression and its inputs.
+    pub fn parse_expr(text: &str) -> Result<(Expr, Vec<String>), String> {
+        let mut parser = ExprParser { tokens: expr_tokens(text)?, pos: 0, inputs: Vec::new() };
+        let expr = parser.sum()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok((expr, parser.inputs)),
+            Some(ExprToken::Op(op)) => Err(format!("unexpected '{}'", op)),
+            Some(_) => Err("missing operator".to_string()),
+        }
+    }
+
+    /// Mean of the last `window` values.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct MovingAverage {
+        window: usize,
+        samples: VecDeque<f64>,
+    }
+
+    impl MovingAverage {
+        pub fn new(window: usize) -> Self {
+            MovingAverage { window: window.max(1), samples: VecDeque::with_capacity(window) }
+        }
+
+        /// Add a value and return the new mean.
+        pub fn push(&mut self, value: f64) -> f64 {
+            if self.samples.len() == self.window {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(value);
+            self.samples.iter().sum::<f64>() / self.samples.len() as f64
+        }
+    }
+
+    /// Count limit of a history buffer only bounded by age.
+    pub const HISTORY_MAX_SAMPLES: usize = 100_000;
+
+    /// Ring buffer of the last value changes of a signal, as (stamp µs, value) samples.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct History {
+        samples: usize,
+        span: Option<u64>,
+        buffer: VecDeque<(u64, f64)>,
+    }
+
+    impl History {
+        /// Keep at most `samples` values, none older than `seconds` before the newest one.
+        pub fn new(samples: Option<usize>, seconds: Option<u64>) -> Self {
+            let samples = samples.unwrap_or(HISTORY_MAX_SAMPLES).clamp(1, HISTORY_MAX_SAMPLES);
+            History {
+                samples,
+                span: seconds.map(|seconds| seconds.saturating_mul(1_000_000)),
+                buffer: VecDeque::new(),
+            }
+        }
+
+        pub fn push(&mut self, stamp: u64, value: f64) {
+            if self.buffer.len() == self.samples {
+                self.buffer.pop_front();
+            }
+            self.buffer.push_back((stamp, value));
+            if let Some(span) = self.span {
+                let oldest = stamp.saturating_sub(span);
+                while self.buffer.front().is_some_and(|(stamp, _)| *stamp < oldest) {
+                    self.buffer.pop_front();
+                }
+            }
+        }
+
+        /// Samples selected by `query`, oldest first.
+        pub fn query(&self, query: &HistoryQuery) -> Vec<(u64, f64)> {
+            let selected = self.buffer.iter().copied().filter(|(stamp, _)| {
+                query.start.is_none_or(|start| *stamp >= start)
+                    && query.end.is_none_or(|end| *stamp <= end)
+            });
+            let interval = match query.interval {
+                Some(interval) => interval.saturating_mul(1000),
+                None => return selected.collect(),
+            };
+
+            let mut samples = Vec::new();
+            let mut window: Option<(u64, f64, usize)> = None;
+            for (stamp, value) in selected {
+                window = match window {
+                    Some((first, sum, count)) if stamp - first < interval => {
+                        Some((first, sum + value, count + 1))
+                    },
+                    Some((first, sum, count)) => {
+                        samples.push((first, sum / count as f64));
+                        Some((stamp, value, 1))
+                    },
+                    None => Some((stamp, value, 1)),
+                };
+            }
+            if let Some((first, sum, count)) = window {
+                samples.push((first, sum / count as f64));
+            }
+            samples
+        }
+    }
+
+    /// Range of a `history` request.
+    ///
+    /// Samples are stamped between `start` and `end` (µs, both included). With `interval`
+    /// (ms) consecutive samples are averaged per window of that length, each window stamped
+    /// with its first sample.
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub struct HistoryQuery {
+        pub start: Option<u64>,
+        pub end: Option<u64>,
+        pub interval: Option<u64>,
+    }
+
+    pub fn parse_history_query(json: &str) -> Result<HistoryQuery, String> {
+        let query: serde_json::Value = match serde_json::from_str(json) {
+            Ok(query) => query,
+            Err(error) => return Err(error.to_string()),
+        };
+        let mut values = [None; 3];
+        for (key, value) in ["start", "end", "interval"].iter().zip(values.iter_mut()) {
+            *value = match query.get(*key) {
+                None => None,
+                Some(number) => match number.as_u64() {
+                    Some(number) => Some(number),
+                    None => return Err(format!("{}: expect a positive integer", key)),
+                },
+            };
+        }
+        let [start, end, interval] = values;
+        if let (Some(start), Some(end)) = (start, end) {
+            if start > end {
+                return Err(format!("start:{} after end:{}", start, end));
+            }
+        }
+        if interval == Some(0) {
+            return Err("interval: must be > 0".to_string());
+        }
+        Ok(HistoryQuery { start, end, interval })
+    }
+
+    /// Subscription parameters asked by one subscriber.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Demand {
+        pub rate: u64,
+        pub watchdog: u64,
+        pub flag: SubscribeFlag,
+    }
+
+    /// Most demanding combination of `demands`: shortest rate, shortest non-zero watchdog
+    /// (0, no watchdog, only when no subscriber asks for one), `ALL` as soon as one
+    /// subscriber asks for it. None once no demand is left.
+    pub fn merge_demands<'a>(demands: impl IntoIterator<Item = &'a Demand>) -> Option<Demand> {
+        demands.into_iter().fold(None, |merged: Option<Demand>, demand| {
+            Some(match merged {
+                None => demand.clone(),
+                Some(merged) => Demand {
+                    rate: merged.rate.min(demand.rate),
+                    watchdog: match (merged.watchdog, demand.watchdog) {
+                        (0, watchdog) | (watchdog, 0) => watchdog,
+                        (merged, watchdog) => merged.min(watchdog),
+                    },
+                    flag: if merged.flag == SubscribeFlag::ALL {
+                        SubscribeFlag::ALL
+                    } else {
+                        demand.flag.clone()
+                    },
+                },
+            })
+        })
+    }
+
+    /// Backend change aligning a message subscribed at `current` (None when unsubscribed) on
+    /// the `merged` demand: `Some(Some)` to (re)subscribe, `Some(None)` to unsubscribe, None
+    /// when up to date. It only depends on the demands left, not on who released them.
+    pub fn backend_change(
+        current: Option<&Demand>,
+        merged: Option<&Demand>,
+    ) -> Option<Option<Demand>> {
+        match (current, merged) {
+            (Some(_), None) => Some(None),
+            (current, Some(merged)) if current != Some(merged) => Some(Some(merged.clone())),
+            _ => None,
+        }
+    }
+
+    /// Window of the `stats` action when the request does not set one, in ms.
+    pub const STATS_DFT_WINDOW: u64 = 10_000;
+
+    /// Aggregates returned by `StatsWindow::compute`; `rate` is in samples per second.
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub struct Stats {
+        pub count: usize,
+        pub min: f64,
+        pub max: f64,
+        pub mean: f64,
+        pub stddev: f64,
+        pub rate: f64,
+    }
+
+    /// Received values of a signal over the last `span` (µs), as (stamp µs, value) samples.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct StatsWindow {
+        span: u64,
+        buffer: VecDeque<(u64, f64)>,
+    }
+
+    impl StatsWindow {
+        /// Keep the samples of the last `window` ms.
+        pub fn new(window: u64) -> Self {
+            StatsWindow { span: window.saturating_mul(1000), buffer: VecDeque::new() }
+        }
+
+        /// Keep at least the last `window` ms from now on.
+        pub fn widen(&mut self, window: u64) {
+            self.span = self.span.max(window.saturating_mul(1000));
+        }
+
+        pub fn push(&mut self, stamp: u64, value: f64) {
+            self.buffer.push_back((stamp, value));
+            let oldest = stamp.saturating_sub(self.span);
+            while self.buffer.front().is_some_and(|(stamp, _)| *stamp < oldest) {
+                self.buffer.pop_front();
+            }
+        }
+
+        /// Stamp of the newest sample, 0 when empty.
+        pub fn newest(&self) -> u64 {
+            self.buffer.back().map_or(0, |(stamp, _)| *stamp)
+        }
+
+        /// Aggregates of the samples received in the `window` ms up to the newest one.
+        pub fn compute(&self, window: u64) -> Stats {
+            let oldest = self.newest().saturating_sub(window.saturating_mul(1000));
+            let values: Vec<(u64, f64)> =
+                self.buffer.iter().copied().filter(|(stamp, _)| *stamp >= oldest).collect();
+            let count = values.len();
+            let first = match values.first() {
+                Some((stamp, _)) => *stamp,
+                None => return Stats::default(),
+            };
+
+            let mut stats = Stats { count, min: f64::MAX, max: f64::MIN, ..Stats::default() };
+            for (_, value) in &values {
+                stats.min = stats.min.min(*value);
+                stats.max = stats.max.max(*value);
+                stats.mean += value / count as f64;
+            }
+            let variance =
+                values.iter().map(|(_, value)| (value - stats.mean).powi(2)).sum::<f64>();
+            stats.stddev = (variance / count as f64).sqrt();
+
+            let elapsed = self.newest() - first;
+            if elapsed > 0 {
+                stats.rate = (count - 1) as f64 * 1_000_000.0 / elapsed as f64;
+            }
+            stats
+        }
+    }
+
+    /// Options of a `stats` request: `window` (ms) and, to subscribe, `stats` (period ms).
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub struct StatsQuery {
+        pub window: Option<u64>,
+        pub period: Option<u64>,
+    }
+
+    pub fn parse_stats_query(json: &str) -> Result<StatsQuery, String> {
+        let query: serde_json::Value = match serde_json::from_str(json) {
+            Ok(query) => query,
+            Err(error) => return Err(error.to_string()),
+        };
+        let mut values = [None; 2];
+        for (key, value) in ["window", "stats"].iter().zip(values.iter_mut()) {
+            *value = match query.get(*key) {
+                None => None,
+                Some(number) => match number.as_u64() {
+                    Some(0) => return Err(format!("{}: must be > 0", key)),
+                    Some(number) => Some(number),
+                    None => return Err(format!("{}: expect a positive integer", key)),
+                },
+            };
+        }
+        let [window, period] = values;
+        Ok(StatsQuery { window, period })
+    }
+
     pub fn should_emit(
         status: CanDataStatus,
         now: u64,
@@ -0,0 +1,61 @@
+/*
+ * Copyright (C) 2015-2023 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * $RP_BEGIN_LICENSE$
+ * Commercial License Usage
+ *  Licensees holding valid commercial IoT.bzh licenses may use this file in
+ *  accordance with the commercial license agreement provided with the
+ *  Software or, alternatively, in accordance with the terms contained in
+ *  a written agreement between you and The IoT.bzh Company. For licensing terms
+ *  and conditions see https://www.iot.bzh/terms-conditions. For further
+ *  information use the contact form at https://www.iot.bzh/contact.
+ *
+ * GNU General Public License Usage
+ *  Alternatively, this file may be used under the terms of the GNU General
+ *  Public license version 3. This license is as published by the Free Software
+ *  Foundation and appearing in the file LICENSE.GPLv3 included in the packaging
+ *  of this file. Please review the following information to ensure the GNU
+ *  General Public License requirements will be met
+ *  https://www.gnu.org/licenses/gpl-3.0.html.
+ * $RP_END_LICENSE$
+ */
+
+//!  Generic CAN DBC binding for libafb/redpesk.
+//!
+//! Unlike the other examples, no message pool is generated at build time: the DBC file
+//! named by the `dbc` configuration key is parsed when the binding loads, so the same
+//! shared object serves any vehicle.
+#![doc(
+    html_logo_url = "https://iot.bzh/images/defaults/company/512-479-max-transp.png",
+    html_favicon_url = "https://iot.bzh/images/defaults/favicon.ico"
+)]
+
+use afbv4::prelude::*;
+// Import helper that loads the DBC file and creates verbs/events from it.
+use dbcapi::create_dbc_verbs;
+// Import parser for the JSON configuration describing sockcan and API parameters.
+use sockdata::types::parse_dbc_config;
+
+/// Binding entry point.
+/// Runs when the shared object is loaded; create and register the API here.
+pub fn binding_init(rootv4: AfbApiV4, jconf: JsoncObj) -> Result<&'static AfbApi, AfbError> {
+    afb_log_msg!(Info, rootv4, "config:{}", jconf);
+
+    // Parse and validate JSON configuration into a strongly-typed structure;
+    // an invalid configuration aborts the binding load.
+    let config = parse_dbc_config(&jconf)?;
+
+    let can_api = AfbApi::new(config.api_uid)
+        .set_info(config.info)
+        .set_permission(AfbPermission::new(to_static_str(config.acls.to_owned())))
+        .seal(false)
+        .require_api(config.sock_api);
+
+    // Parse the configured DBC file and register verbs and events for its messages/signals.
+    create_dbc_verbs(rootv4, can_api, &config)?;
+    can_api.finalize()
+}
+
+// Register the binding entry point with libafb.
+AfbBindingRegister!(binding_init);
@@ -31,7 +31,7 @@ use afbv4::prelude::*;
 // Import helper that creates verbs/events from a DBC pool.
 use dbcapi::create_pool_verbs;
 // Import parser for the JSON configuration describing sockcan and API parameters.
-use sockdata::types::parse_sockcan_config;
+use sockdata::types::parse_dbc_config;
 
 // include generated dbc message pool
 include!("./__can-multiplexing.rs");
@@ -50,8 +50,9 @@ pub fn binding_init(rootv4: AfbApiV4, jconf: JsoncObj) -> Result<&'static AfbApi
     // (ACLs, bus names, credentials) and ensure log level is appropriate.
     afb_log_msg!(Info, rootv4, "config:{}", jconf);
 
-    // Parse and validate JSON configuration into a strongly-typed structure.
-    let config = parse_sockcan_config(&jconf);
+    // Parse and validate JSON configuration into a strongly-typed structure;
+    // an invalid configuration aborts the binding load.
+    let config = parse_dbc_config(&jconf)?;
 
     // create a new api
     // Create and configure the public API:
@@ -67,7 +68,7 @@ pub fn binding_init(rootv4: AfbApiV4, jconf: JsoncObj) -> Result<&'static AfbApi
     let pool = Box::new(CanMsgPool::new(config.api_uid));
 
     // Create verbs and events from the DBC pool and register them on the API.
-    create_pool_verbs(rootv4, can_api, jconf, pool)?;
+    create_pool_verbs(rootv4, can_api, &config, pool)?;
     // Finalize the API so it becomes visible/usable by clients.
     // After this call the API descriptor is no longer mutable.
     can_api.finalize()
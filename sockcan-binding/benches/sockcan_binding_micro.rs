@@ -108,4 +108,231 @@ mod tests {
 
         assert_eq!(v1, v2);
     }
+
+    #[test]
+    fn quotas_and_event_budget_enforce_limits() {
+        // Zero disables every limit.
+        assert!(!afb_sockcan::logic::quota_exceeded(1000, 1, 0));
+        assert!(afb_sockcan::logic::rate_allowed(0, 0));
+
+        assert!(!afb_sockcan::logic::quota_exceeded(7, 1, 8));
+        assert!(afb_sockcan::logic::quota_exceeded(8, 1, 8));
+        assert!(!afb_sockcan::logic::rate_allowed(0, 50));
+        assert!(afb_sockcan::logic::rate_allowed(50, 50));
+
+        // Shared filters run at the fastest caller demand, and are released with the last one.
+        let merge_rates = afb_sockcan::logic::merge_rates;
+        assert_eq!(merge_rates([(250, 0), (100, 5000), (500, 2000)]), Some((100, 2000)));
+        assert_eq!(merge_rates([(250, 0)]), Some((250, 0)));
+        assert_eq!(merge_rates(Vec::new()), None);
+
+        // Two events per second: the third one is dropped, a new window admits again.
+        let mut budget = afb_sockcan::logic::EventBudget::new();
+        assert!(budget.admit(0, 2));
+        assert!(budget.admit(10, 2));
+        assert!(!budget.admit(20, 2));
+        assert_eq!(budget.get_window_dropped(), 1);
+        assert!(budget.admit(1_000_000, 2));
+        assert_eq!(budget.get_window_dropped(), 0);
+        assert_eq!(budget.get_dropped(), 1);
+    }
+
+    #[test]
+    fn required_acls_follow_verb_and_canid_rules() {
+        use sockdata::types::{SockcanAclRule, SockcanVerbClass};
+
+        let rules = vec![
+            SockcanAclRule {
+                acl: "acl:hmi",
+                verbs: vec![SockcanVerbClass::Subscribe],
+                canids: vec![(0x100, 0x3FF)],
+            },
+            SockcanAclRule {
+                acl: "acl:diag",
+                verbs: vec![SockcanVerbClass::Subscribe, SockcanVerbClass::Send],
+                canids: vec![(0x7DF, 0x7DF)],
+            },
+        ];
+        let required = |class, canid| afb_sockcan::logic::required_acls(&rules, class, canid);
+
+        assert_eq!(required(SockcanVerbClass::Subscribe, Some(0x101)), Some(vec!["acl:hmi"]));
+        assert_eq!(required(SockcanVerbClass::Send, Some(0x7DF)), Some(vec!["acl:diag"]));
+        // Covered verb class but no matching range: denied.
+        assert_eq!(required(SockcanVerbClass::Send, Some(0x101)), Some(vec![]));
+        // No rule for this verb class: API-level ACL only.
+        assert_eq!(required(SockcanVerbClass::Configure, None), None);
+    }
+
+    #[test]
+    fn busload_counts_stuff_bits_and_scales_to_bitrate() {
+        // Worst-case on-wire sizes of 8-byte classic frames.
+        assert_eq!(afb_sockcan::logic::can_frame_bits(false, false, 8), 135);
+        assert_eq!(afb_sockcan::logic::can_frame_bits(true, false, 8), 160);
+        // Remote frames carry no data field, whatever their DLC.
+        assert_eq!(afb_sockcan::logic::can_frame_bits(false, true, 8), 55);
+        assert_eq!(afb_sockcan::logic::can_frame_bits(true, true, 8), 80);
+
+        let mut load = afb_sockcan::logic::BusLoad::new();
+        for _ in 0..100 {
+            load.add_frame(1250);
+        }
+        // 125 kbit over one second at 500 kbit/s.
+        let sample = load.sample(1_000_000, 500_000);
+        assert_eq!(sample.frames, 100);
+        assert!((sample.load - 25.0).abs() < 1e-9);
+
+        // The next window starts empty.
+        assert_eq!(load.sample(2_000_000, 500_000).load, 0.0);
+    }
+
+    #[test]
+    fn link_changes_decode_ifname_and_running_state() {
+        // nlmsghdr + ifinfomsg + IFLA_IFNAME attribute, as sent on RTMGRP_LINK.
+        let link = |msgtype: u16, flags: u32, name: &str| -> Vec<u8> {
+            let attrlen = 4 + name.len() + 1;
+            let padded = (attrlen + 3) & !3;
+            let mut msg = Vec::new();
+            msg.extend_from_slice(&((16 + 16 + padded) as u32).to_ne_bytes());
+            msg.extend_from_slice(&msgtype.to_ne_bytes());
+            msg.extend_from_slice(&[0u8; 10]); // nlmsg flags, seq, pid
+            msg.extend_from_slice(&[0u8; 8]); // ifi family, pad, type, index
+            msg.extend_from_slice(&flags.to_ne_bytes());
+            msg.extend_from_slice(&[0u8; 4]); // ifi change
+            msg.extend_from_slice(&(attrlen as u16).to_ne_bytes());
+            msg.extend_from_slice(&3u16.to_ne_bytes()); // IFLA_IFNAME
+            msg.extend_from_slice(name.as_bytes());
+            msg.resize(16 + 16 + padded, 0);
+            msg
+        };
+
+        let mut batch = link(16, 0x1, "can0"); // up but no carrier (bus-off)
+        batch.extend(link(16, 0x41, "can0")); // up and running
+        batch.extend(link(17, 0x41, "vcan10")); // removed
+        batch.extend(link(24, 0, "eth0")); // RTM_NEWROUTE, ignored
+
+        let changes = afb_sockcan::logic::parse_link_changes(&batch);
+        let changes: Vec<(&str, bool)> =
+            changes.iter().map(|change| (change.ifname.as_str(), change.up)).collect();
+        assert_eq!(changes, vec![("can0", false), ("can0", true), ("vcan10", false)]);
+
+        // Truncated datagrams stop decoding without panicking.
+        assert!(afb_sockcan::logic::parse_link_changes(&batch[..20]).is_empty());
+    }
+
+    #[test]
+    fn tx_frame_builds_data_and_rtr_frames() {
+        use afb_sockcan::logic::{tx_frame, CAN_EFF_FLAG, CAN_RTR_FLAG};
+
+        assert_eq!(tx_frame(0x101, &[1, 2, 3], false, 0), Ok((0x101, vec![1, 2, 3])));
+        // RTR frames carry no payload, only their DLC.
+        assert_eq!(tx_frame(0x101, &[], true, 4), Ok((0x101 | CAN_RTR_FLAG, vec![0; 4])));
+        assert!(tx_frame(0x101, &[1], true, 1).is_err());
+        assert!(tx_frame(0x101, &[0; 9], false, 0).is_err());
+        assert!(tx_frame(0x101, &[], true, 9).is_err());
+
+        // Standard ids are 11 bits unless the extended flag is set.
+        assert!(tx_frame(0x800, &[], false, 0).is_err());
+        assert!(tx_frame(0x800 | CAN_EFF_FLAG, &[], false, 0).is_ok());
+    }
+
+    #[test]
+    fn tx_plan_maps_period_and_count_to_bcm_timers() {
+        use afb_sockcan::logic::{tx_plan, TxPlan};
+
+        assert_eq!(tx_plan(0, 0, false), Ok(TxPlan::Once));
+        // Count-limited cycles run on ival1 so the kernel reports TxExpired.
+        assert_eq!(tx_plan(100, 10, false), Ok(TxPlan::Cyclic { ival1: 100, ival2: 0, count: 10 }));
+        assert_eq!(tx_plan(100, 0, false), Ok(TxPlan::Cyclic { ival1: 0, ival2: 100, count: 0 }));
+        assert!(tx_plan(0, 10, false).is_err());
+        assert_eq!(tx_plan(100, 10, true), Ok(TxPlan::Stop));
+    }
+
+    #[test]
+    fn change_filter_dedups_throttles_and_refreshes() {
+        use afb_sockcan::logic::change_filter;
+        use sockdata::types::SubscribeFlag;
+
+        // Raw NEW: only masked changes pass, rate throttles them, watchdog forces a refresh.
+        let mask = vec![0xFF, 0x00];
+        let mut filter = change_filter(true, SubscribeFlag::NEW, Some(&mask), 10, 100).unwrap();
+        assert!(filter.admit(0, &[1, 1]));
+        assert!(!filter.admit(20_000, &[1, 2]));
+        assert!(filter.admit(50_000, &[2, 2]));
+        assert!(!filter.admit(100_000, &[2, 5]));
+        assert!(filter.admit(150_000, &[2, 5]));
+
+        let mut filter = change_filter(true, SubscribeFlag::NEW, Some(&mask), 10, 0).unwrap();
+        assert!(filter.admit(0, &[1, 1]));
+        assert!(!filter.admit(5_000, &[2, 1]));
+        assert!(filter.admit(15_000, &[2, 1]));
+        assert!(!filter.admit(500_000, &[2, 9]));
+
+        // Raw ALL only throttles, BCM without mask keeps the kernel behaviour.
+        let mut filter = change_filter(true, SubscribeFlag::ALL, None, 10, 100).unwrap();
+        assert!(filter.admit(0, &[1]));
+        assert!(!filter.admit(5_000, &[1]));
+        assert!(filter.admit(10_000, &[1]));
+        assert!(change_filter(false, SubscribeFlag::NEW, None, 10, 100).is_none());
+        assert!(change_filter(false, SubscribeFlag::ALL, Some(&mask), 10, 100).is_none());
+    }
+
+    #[test]
+    fn two_callers_on_one_canid_get_the_merged_subscription() {
+        use afb_sockcan::logic::{change_filter, merge_demands, CanIdDemand};
+        use sockdata::types::SubscribeFlag;
+
+        let slow = CanIdDemand {
+            rate: 100,
+            watchdog: 0,
+            flag: SubscribeFlag::NEW,
+            raw: false,
+            mask: Some(vec![0x0F, 0x00]),
+        };
+        let fast = CanIdDemand {
+            rate: 10,
+            watchdog: 500,
+            flag: SubscribeFlag::NEW,
+            raw: true,
+            mask: Some(vec![0xF0]),
+        };
+
+        // The second caller does not lose the raw source, and the first keeps its mask bits.
+        let merged = merge_demands([&slow, &fast]).unwrap();
+        assert_eq!(merged, merge_demands([&fast, &slow]).unwrap());
+        assert_eq!((merged.rate, merged.watchdog, merged.raw), (10, 500, true));
+        assert_eq!(merged.flag, SubscribeFlag::NEW);
+        assert_eq!(merged.mask, Some(vec![0xFF, 0xFF]));
+        let mut filter = change_filter(
+            merged.raw,
+            merged.flag.clone(),
+            merged.mask.as_ref(),
+            merged.rate,
+            merged.watchdog,
+        )
+        .unwrap();
+        assert!(filter.admit(0, &[0x01, 0x00]));
+        assert!(filter.admit(20_000, &[0x11, 0x00]));
+        assert!(filter.admit(40_000, &[0x11, 0x01]));
+
+        // A caller wanting every frame, or without mask, disables change detection for all.
+        let all = CanIdDemand { flag: SubscribeFlag::ALL, mask: None, ..slow.clone() };
+        assert_eq!(merge_demands([&slow, &all]).unwrap().flag, SubscribeFlag::ALL);
+        let unmasked = CanIdDemand { mask: None, ..slow.clone() };
+        assert_eq!(merge_demands([&unmasked, &slow]).unwrap().mask, None);
+
+        // Once the raw caller leaves, the CAN ID goes back to its BCM filter.
+        assert_eq!(merge_demands([&slow]), Some(slow.clone()));
+        assert_eq!(merge_demands(Vec::new()), None);
+    }
+
+    #[test]
+    fn bcm_proc_lists_rx_filters_only() {
+        let text = ">>> socket 00000000c0ffee00 / sk 00000000deadbeef / bo 0000000012345678 / dropped 0 / bound can0 <<<\n\
+            rx_op: 257 can0  [1]   timeo=1000000000 # recv 0 (0) => reduction: 0%\n\
+            rx_op: 10A can0  [1]   thr=250000000 # recv 12 (4) => reduction: 66%\n\
+            tx_op: 101 can0 [1] t2=100000000 # sent 5\n";
+
+        assert_eq!(afb_sockcan::logic::parse_bcm_proc(text), vec![0x10A, 0x257]);
+        assert!(afb_sockcan::logic::parse_bcm_proc("").is_empty());
+    }
 }
@@ -21,23 +21,39 @@
  * $RP_END_LICENSE$
  */
 
-use crate::context::{AfbClientData, CanEvtCtx, CheckCtx, SessionCtx, SubVerbCtx};
+use crate::context::{
+    AfbClientData, BusLoadVerbCtx, CallerCtx, CanEvtCtx, CanIdCtx, CheckCtx, LinkCtx, MetricsCtx,
+    SessionCounters, SessionCtx, StateCtx, SubVerbCtx,
+};
+use crate::logic::{self, CanIdDemand, EventBudget};
 use afbv4::prelude::*;
 
 use sockcan::prelude::{
-    CanBcmFlag, CanBcmOpCode, CanError, CanTimeStamp, SockBcmCmd, SockBcmMsg, SockCanBcm,
-    SockCanHandle,
+    CanAnyFrame, CanBcmFlag, CanBcmOpCode, CanError, CanTimeStamp, SockBcmCmd, SockBcmMsg,
+    SockCanBcm, SockCanHandle,
+};
+use sockdata::types::{
+    CanBcmAlive, CanBcmData, CanBcmError, CanBcmLink, CanBcmSubscriptions, CanBcmTimeout,
+    CanBcmTxStatus, CanBusLoad, CanLinkStatus, CanTxStatus, RtrReplyParam, SendParam,
+    SockcanEventMode, SockcanVerbClass, SubscribeParam, SubscribeSource, UnSubscribeParam,
 };
-use sockdata::types::{CanBcmData, CanBcmError, SubscribeParam, UnSubscribeParam};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 /// Asynchronous callback invoked when the CAN BCM file descriptor becomes readable.
 ///
 /// This event-loop handler:
 /// - reads one BCM frame from the socket,
 /// - converts it into a higher-level `CanBcmData` or `CanBcmError`,
-/// - pushes the payload to the associated AFB event,
+/// - reports BCM `RxTimeout` as `CanBcmTimeout` and the first frame after it as `CanBcmAlive`,
+/// - reports BCM `TxExpired` as a `CanBcmTxStatus` on the session event,
+/// - pushes the payload to the associated AFB event, within the session event budget,
 /// - optionally re-arms RX timers on `RxTimeout` notifications,
+/// - updates the session runtime counters reported by the `metrics` verb,
 /// - closes the socket and unreferences the event when there are no more listeners.
 ///
 pub(crate) fn async_can_cb(
@@ -57,49 +73,155 @@ pub(crate) fn async_can_cb(
             opcode: msg.get_opcode(),
             len: msg.get_len()?,
             data: msg.get_data()?.to_vec(),
+            rtr: msg.get_frame_id()? & logic::CAN_RTR_FLAG != 0,
         })
     };
 
+    // A link going down is reported as a pending socket error (ENETDOWN): consume it so the
+    // main loop does not spin on it, recovery is driven by `link_state_cb`.
+    if revent & (AfbEvtFdPoll::ERR.bits() | AfbEvtFdPoll::HUP.bits()) != 0 {
+        let _ = ctx.client.sockfd.borrow().get_bcm_frame();
+        return Ok(());
+    }
+
     // Only handle "readable" events; other poll flags are silently ignored.
     if revent == AfbEvtFdPoll::IN.bits() {
-        let msg = ctx.client.sockfd.get_bcm_frame();
+        let msg = ctx.client.sockfd.borrow().get_bcm_frame();
         let opcode = msg.get_opcode();
         let msgid = msg.get_id();
-        // Push either a normal data frame or an error wrapper to the event.
-        let listener = match data(msg) {
-            Err(error) => {
-                ctx.client.event.push(CanBcmError::new(error.get_uid(), -1, error.get_info()))
+
+        // A count-limited cyclic transmission finished: its last frame was queued.
+        if let CanBcmOpCode::TxExpired = opcode {
+            if let Ok(canid) = msgid {
+                ctx.client.event.push(CanBcmTxStatus {
+                    canid,
+                    status: CanTxStatus::EXPIRED,
+                    info: String::new(),
+                });
+            }
+            return Ok(());
+        }
+
+        {
+            let mut counters = ctx.client.counters.borrow_mut();
+            counters.frames += 1;
+            if let CanBcmOpCode::RxTimeout = opcode {
+                counters.rx_timeouts += 1;
+            }
+        }
+        if let Ok(canid) = msgid {
+            if let Some(state) = ctx.client.canids.borrow_mut().get_mut(&canid) {
+                state.frames += 1;
+            }
+        }
+
+        // Watchdog bookkeeping: a RxTimeout marks the CAN ID as silent, and the next frame
+        // received for a silent CAN ID reports it alive again.
+        let now = ctx.client.elapsed_us();
+        let (timeout, alive) = match msgid {
+            Ok(canid) => match ctx.client.canids.borrow_mut().get_mut(&canid) {
+                Some(state) => {
+                    let silent = now.saturating_sub(state.last_seen) / 1000;
+                    if let CanBcmOpCode::RxTimeout = opcode {
+                        state.silent = true;
+                        (Some(CanBcmTimeout { canid, watchdog: state.watchdog, silent }), None)
+                    } else {
+                        state.last_seen = now;
+                        if state.silent {
+                            state.silent = false;
+                            (None, Some(CanBcmAlive { canid, watchdog: state.watchdog, silent }))
+                        } else {
+                            (None, None)
+                        }
+                    }
+                },
+                None => (None, None),
             },
-            Ok(data) => ctx.client.event.push(data),
+            Err(_) => (None, None),
+        };
+
+        // Userspace change detection: unchanged frames are dropped before using the budget,
+        // except the one reporting a silent CAN ID alive again.
+        if let (CanBcmOpCode::RxChanged, Ok(canid)) = (opcode, &msgid) {
+            if let Some(state) = ctx.client.canids.borrow_mut().get_mut(canid) {
+                if let (Some(change), Ok(payload)) = (&mut state.change, msg.get_data()) {
+                    if !change.admit(now, payload) && alive.is_none() {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+
+        // In `canid` event mode each CAN ID has its own event.
+        let event = ctx.client.event_for(msgid.as_ref().ok().copied());
+
+        // Keep track of pushed events and of the last known listener count.
+        let pushed = |listener: i32| -> i32 {
+            let mut counters = ctx.client.counters.borrow_mut();
+            counters.events += 1;
+            counters.listeners = listener;
+            listener
+        };
+
+        // Watchdog notifications bypass the event budget: they are rare and report lost ECUs.
+        let listener = if let Some(timeout) = timeout {
+            pushed(event.push(timeout))
+        } else {
+            let alive = alive.map(|alive| pushed(event.push(alive)));
+
+            // Enforce the per-session event budget before pushing data frames.
+            let admitted = admit_event(&ctx.client, now);
+
+            // Push either a normal data frame or an error wrapper to the event.
+            if !admitted {
+                // The budget counts the dropped frame. Without an alive notification nothing
+                // was pushed, so there is no listener count to act on.
+                match alive {
+                    Some(listener) => listener,
+                    None => return Ok(()),
+                }
+            } else {
+                match data(msg) {
+                    Err(error) => {
+                        ctx.client.counters.borrow_mut().decode_errors += 1;
+                        pushed(event.push(CanBcmError::new(error.get_uid(), -1, error.get_info())))
+                    },
+                    Ok(data) => pushed(event.push(data)),
+                }
+            }
         };
 
-        // On RX timeout, re-arm BCM timers for this CAN ID using the current rate and watchdog.
+        // On RX timeout, re-arm BCM timers for this CAN ID using its rate and watchdog.
         if let CanBcmOpCode::RxTimeout = opcode {
             if let Ok(canid) = msgid {
-                if let Err(_error) = SockBcmCmd::new(
-                    CanBcmOpCode::RxSetup,
-                    CanBcmFlag::RX_FILTER_ID
-                        | CanBcmFlag::SET_TIMER
-                        | CanBcmFlag::START_TIMER
-                        | CanBcmFlag::RX_ANNOUNCE_RESUME,
-                    canid,
-                )
-                .set_timers(ctx.client.rate, ctx.client.watchdog)
-                .apply(&ctx.client.sockfd)
+                let (rate, watchdog) = match ctx.client.canids.borrow().get(&canid) {
+                    Some(state) => (state.rate, state.watchdog),
+                    None => (ctx.client.rate, ctx.client.watchdog),
+                };
+                if let Err(_error) =
+                    rx_filter(canid, rate, watchdog).apply(&*ctx.client.sockfd.borrow())
                 {
+                    ctx.client.counters.borrow_mut().filter_failures += 1;
                     afb_log_msg!(
                         Warning,
                         ctx.client.event,
                         "fail-sockbcm-filter canid={} rate={} watchdog={}",
                         canid,
-                        ctx.client.rate,
-                        ctx.client.watchdog
+                        rate,
+                        watchdog
                     );
                     return Ok(());
                 }
             }
         };
 
+        // In `canid` event mode the shared socket stays open, and a CAN ID is only released
+        // with the demand of its last caller (`release_demands`), which keeps the caller sets
+        // in sync.
+        if ctx.client.per_canid {
+            return Ok(());
+        }
+
         // If no listeners remain on this event, close the BCM socket and drop the event.
         if listener < 1 {
             afb_log_msg!(
@@ -108,8 +230,7 @@ pub(crate) fn async_can_cb(
                 "closing-bcm-event uid:{} no more listener",
                 ctx.client.uid
             );
-            ctx.client.event.unref(); // delete associated event
-            ctx.client.sockfd.close(); // close associated socket
+            ctx.client.close(); // delete associated event and close socket
 
             return Ok(());
         }
@@ -117,12 +238,410 @@ pub(crate) fn async_can_cb(
     Ok(())
 }
 
+/// Account one data event against the session budget, warning on the first drop of a window.
+fn admit_event(client: &AfbClientData, now: u64) -> bool {
+    let mut budget = client.budget.borrow_mut();
+    let admitted = budget.admit(now, client.max_events);
+    if !admitted && budget.get_window_dropped() == 1 {
+        afb_log_msg!(
+            Warning,
+            client.event,
+            "sockbcm-event-throttled uid:{} max_events:{}/s dropped:{}",
+            client.uid,
+            client.max_events,
+            budget.get_dropped()
+        );
+    }
+    admitted
+}
+
+/// Asynchronous callback invoked when the session raw socket becomes readable.
+///
+/// Only frames of CAN IDs subscribed with the `RAW` source are considered. They go through
+/// their userspace change detection stage and the session event budget, then are pushed
+/// as `CanBcmData` (`RxChanged`, stamped in µs since the epoch).
+pub(crate) fn async_raw_cb(
+    _evtfd: &AfbEvtFd,
+    revent: u32,
+    ctx: &AfbCtxData,
+) -> Result<(), AfbError> {
+    let ctx: &CanEvtCtx = ctx.get_ref::<CanEvtCtx>()?;
+    let frame = match ctx.client.rawfd.borrow().as_ref() {
+        Some(rawfd) => rawfd.get_can_frame(),
+        None => return Ok(()),
+    };
+    // Like BCM sockets, a pending error (link down) is consumed by the read above.
+    if revent != AfbEvtFdPoll::IN.bits() {
+        return Ok(());
+    }
+
+    let (canid, len, data) = match frame {
+        CanAnyFrame::RawStd(frame) => (frame.get_id(), frame.get_len(), frame.get_data().to_vec()),
+        CanAnyFrame::RawFd(frame) => (frame.get_id(), frame.get_len(), frame.get_data().to_vec()),
+        _ => return Ok(()),
+    };
+    let rtr = canid & logic::CAN_RTR_FLAG != 0;
+    let canid = canid & !logic::CAN_RTR_FLAG;
+
+    let now = ctx.client.elapsed_us();
+    {
+        let mut canids = ctx.client.canids.borrow_mut();
+        let state = match canids.get_mut(&canid) {
+            Some(state) if state.raw => state,
+            _ => return Ok(()),
+        };
+        state.frames += 1;
+        state.last_seen = now;
+        ctx.client.counters.borrow_mut().frames += 1;
+        if let Some(change) = &mut state.change {
+            if !change.admit(now, &data) {
+                return Ok(());
+            }
+        }
+    }
+
+    if !admit_event(&ctx.client, now) {
+        return Ok(());
+    }
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |stamp| stamp.as_micros());
+    let listener = ctx.client.event_for(Some(canid)).push(CanBcmData {
+        canid,
+        len,
+        stamp: stamp as u64,
+        opcode: CanBcmOpCode::RxChanged,
+        data,
+        rtr,
+    });
+    {
+        let mut counters = ctx.client.counters.borrow_mut();
+        counters.events += 1;
+        counters.listeners = listener;
+    }
+
+    // In `canid` event mode CAN IDs are released with their last caller demand.
+    if listener < 1 && !ctx.client.per_canid {
+        ctx.client.close();
+    }
+    Ok(())
+}
+
+/// Build the BCM `RxSetup` command installing the RX filter of one CAN ID.
+///
+/// Timers are only set when a non-zero `rate` or `watchdog` is requested.
+fn rx_filter(canid: u32, rate: u64, watchdog: u64) -> SockBcmCmd {
+    let mut filter = SockBcmCmd::new(
+        CanBcmOpCode::RxSetup,
+        CanBcmFlag::RX_FILTER_ID
+            | CanBcmFlag::SET_TIMER
+            | CanBcmFlag::START_TIMER
+            | CanBcmFlag::RX_ANNOUNCE_RESUME,
+        canid,
+    );
+    if rate > 0 || watchdog > 0 {
+        filter.set_timers(rate, watchdog);
+    }
+    filter
+}
+
+/// Open the client raw socket on first use and register it so `async_raw_cb` gets its frames.
+fn start_raw_listener(client: &Arc<AfbClientData>) -> Result<(), AfbError> {
+    if client.rawfd.borrow().is_none() {
+        let rawfd = match SockCanHandle::open_raw(client.candev, CanTimeStamp::CLASSIC) {
+            Ok(handle) => handle,
+            Err(canerr) => return Err(AfbError::new("fail-sockraw-open", 0, canerr.to_string())),
+        };
+        client.rawfd.replace(Some(rawfd));
+    }
+
+    let rawfd = match client.rawfd.borrow().as_ref() {
+        Some(rawfd) => rawfd.as_rawfd(),
+        None => return Ok(()),
+    };
+    let evtfd = AfbEvtFd::new(client.uid);
+    evtfd
+        .set_fd(rawfd)
+        .set_events(AfbEvtFdPoll::IN)
+        .set_callback(async_raw_cb)
+        .set_context(CanEvtCtx { client: Arc::clone(client) })
+        .start()?;
+    if let Some(previous) = client.raw_evtfd.replace(Some(evtfd)) {
+        previous.unref();
+    }
+    Ok(())
+}
+
+/// Register the client BCM socket in the AFB main loop so `async_can_cb` gets its frames.
+fn start_bcm_listener(client: &Arc<AfbClientData>) -> Result<(), AfbError> {
+    let evtfd = AfbEvtFd::new(client.uid);
+    evtfd
+        .set_fd(client.sockfd.borrow().as_rawfd())
+        .set_events(AfbEvtFdPoll::IN)
+        .set_callback(async_can_cb)
+        .set_context(CanEvtCtx { client: Arc::clone(client) })
+        .start()?;
+    if let Some(previous) = client.bcm_evtfd.replace(Some(evtfd)) {
+        previous.unref();
+    }
+    Ok(())
+}
+
+/// Open a BCM socket and its notification event, and register them as a new client.
+///
+/// In `canid` event mode (`per_canid`) the client serves every caller and per-CAN ID
+/// events are created on demand by `canid_event`.
+fn open_client(
+    request: &AfbRequest,
+    ctx: &SubVerbCtx,
+    rate: u64,
+    watchdog: u64,
+    per_canid: bool,
+) -> Result<Arc<AfbClientData>, AfbError> {
+    // Open a new BCM socket on the configured CAN device.
+    let sockfd = match SockCanHandle::open_bcm(ctx.candev, CanTimeStamp::CLASSIC) {
+        Ok(handle) => handle,
+        Err(bcmerr) => {
+            let error = AfbError::new("fail-sockbcm-open", 0, bcmerr.to_string());
+            afb_log_msg!(Warning, request, &error);
+            return Err(error);
+        },
+    };
+
+    // Create a new AFB event bound to `ctx.sockevt` to broadcast BCM notifications.
+    let event = AfbEvent::new(ctx.sockevt);
+    if event.register(request.get_api().get_apiv4()) < 0 {
+        let error = AfbError::new(
+            "evt-fail-registration",
+            0,
+            format!("evt-fail-registration uid:{}", ctx.uid),
+        );
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    } else {
+        event.finalize()?;
+    }
+
+    // Client data shared across the event, BCM socket and session.
+    // NOTE: `Arc` is used even if `AfbClientData` is not Send/Sync; this is constrained
+    //   to the AFB event loop context.
+    #[allow(clippy::arc_with_non_send_sync)]
+    let client_data = Arc::new(AfbClientData {
+        uid: ctx.uid,
+        session: ctx.state.new_session_id(),
+        candev: ctx.candev,
+        sockfd: RefCell::new(sockfd),
+        rawfd: RefCell::new(None),
+        bcm_evtfd: Cell::new(None),
+        raw_evtfd: Cell::new(None),
+        event,
+        events: RefCell::new(HashMap::new()),
+        per_canid,
+        rate,
+        watchdog,
+        max_events: ctx.state.limits.max_events,
+        canids: RefCell::new(HashMap::new()),
+        budget: RefCell::new(EventBudget::new()),
+        counters: RefCell::new(SessionCounters::default()),
+        started: Instant::now(),
+        closed: Cell::new(false),
+        degraded: Cell::new(false),
+    });
+    ctx.state.add_session(&client_data);
+
+    // Register the BCM socket file descriptor in the AFB main loop for async callbacks.
+    start_bcm_listener(&client_data)?;
+    Ok(client_data)
+}
+
+/// Return the `{sockevt}/0x{canid:x}` event of `canid`, creating it on first use.
+fn canid_event(
+    request: &AfbRequest,
+    ctx: &SubVerbCtx,
+    client: &AfbClientData,
+    canid: u32,
+) -> Result<&'static AfbEvent, AfbError> {
+    if let Some(event) = client.events.borrow().get(&canid) {
+        return Ok(*event);
+    }
+
+    let uid = to_static_str(format!("{}/0x{:x}", ctx.sockevt, canid));
+    let event = AfbEvent::new(uid);
+    if event.register(request.get_api().get_apiv4()) < 0 {
+        let error =
+            AfbError::new("evt-fail-registration", 0, format!("evt-fail-registration uid:{}", uid));
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
+    event.finalize()?;
+    client.events.borrow_mut().insert(canid, event);
+    Ok(event)
+}
+
+/// Point a CAN ID at the source of `demand`: the raw source drops its BCM filter, the BCM
+/// source (re)installs it at the demand timers. Return false when the filter was refused.
+fn apply_source(client: &AfbClientData, canid: u32, was_bcm: bool, demand: &CanIdDemand) -> bool {
+    if demand.raw {
+        if was_bcm {
+            let _ = SockBcmCmd::new(CanBcmOpCode::RxDelete, CanBcmFlag::NONE, canid)
+                .apply(&*client.sockfd.borrow());
+        }
+        true
+    } else {
+        rx_filter(canid, demand.rate, demand.watchdog)
+            .apply(&*client.sockfd.borrow())
+            .is_ok()
+    }
+}
+
+/// Remove the RX filter and event of a CAN ID whose last caller demand was released.
+fn release_canid(client: &AfbClientData, canid: u32) {
+    if let Some(event) = client.events.borrow_mut().remove(&canid) {
+        if let Some(state) = client.canids.borrow_mut().remove(&canid) {
+            if !state.raw {
+                let _ = SockBcmCmd::new(CanBcmOpCode::RxDelete, CanBcmFlag::NONE, canid)
+                    .apply(&*client.sockfd.borrow());
+            }
+        }
+        event.unref();
+    }
+}
+
+/// Drop the demands of a `canid` event mode caller on its CAN IDs.
+///
+/// A CAN ID left without demand is released (RxDelete and event unref), the others keep
+/// their filter at the merged subscription of the remaining callers.
+pub(crate) fn release_demands(client: &AfbClientData, caller: u32, canids: &[u32]) {
+    for canid in canids {
+        let (was_bcm, merged) = match client.canids.borrow_mut().get_mut(canid) {
+            Some(state) => {
+                state.demands.remove(&caller);
+                (!state.raw, logic::merge_demands(state.demands.values()))
+            },
+            None => continue,
+        };
+        match merged {
+            None => release_canid(client, *canid),
+            Some(merged) => {
+                if !apply_source(client, *canid, was_bcm, &merged) {
+                    client.counters.borrow_mut().filter_failures += 1;
+                    continue;
+                }
+                if let Some(state) = client.canids.borrow_mut().get_mut(canid) {
+                    state.set_demand(&merged);
+                }
+            },
+        }
+    }
+}
+
+/// Return the BCM client serving the caller, creating it on first use.
+///
+/// In session event mode this is the client attached to the caller session (subject to
+/// `max_sessions`); in `canid` event mode the client shared by every caller, each caller
+/// session then getting a `CallerCtx` counted against `max_sessions`. The caller is
+/// subscribed to the client event either way.
+fn session_client(
+    request: &AfbRequest,
+    ctx: &SubVerbCtx,
+    rate: u64,
+    watchdog: u64,
+) -> Result<Arc<AfbClientData>, AfbError> {
+    let client = if ctx.state.event_mode == SockcanEventMode::Canid {
+        if CallerCtx::get(request).is_err() {
+            if logic::quota_exceeded(ctx.state.callers.get(), 1, ctx.state.limits.max_sessions) {
+                let error = AfbError::new(
+                    "fail-session-quota",
+                    0,
+                    format!("max sessions:{} reached", ctx.state.limits.max_sessions),
+                );
+                afb_log_msg!(Warning, request, &error);
+                return Err(error);
+            }
+            CallerCtx::set(
+                request,
+                CallerCtx {
+                    id: ctx.state.new_session_id(),
+                    state: ctx.state.clone(),
+                    canids: HashSet::new(),
+                    tx: None,
+                },
+            )?;
+            ctx.state.callers.set(ctx.state.callers.get() + 1);
+        }
+
+        // One BCM client serves every caller, the shared event carries link state and errors.
+        let shared = ctx.state.shared.borrow().clone();
+        let client = match shared {
+            Some(client) if !client.closed.get() => client,
+            _ => {
+                let client = open_client(request, ctx, rate, watchdog, true)?;
+                ctx.state.shared.replace(Some(Arc::clone(&client)));
+                client
+            },
+        };
+        client.event.subscribe(request)?;
+        client
+    } else {
+        // Check if a BCM session already exists for this request/session.
+        match SessionCtx::get_from(request) {
+            Ok(session) => Arc::clone(&session.client),
+            Err(_) => {
+                if logic::quota_exceeded(
+                    ctx.state.session_count(),
+                    1,
+                    ctx.state.limits.max_sessions,
+                ) {
+                    let error = AfbError::new(
+                        "fail-session-quota",
+                        0,
+                        format!("max sessions:{} reached", ctx.state.limits.max_sessions),
+                    );
+                    afb_log_msg!(Warning, request, &error);
+                    return Err(error);
+                }
+                let client = open_client(request, ctx, rate, watchdog, false)?;
+
+                // Subscribe the current request to the newly created event.
+                client.event.subscribe(request)?;
+
+                // Attach the session context so future calls can reuse the same BCM socket and event.
+                SessionCtx::set_for(request, SessionCtx { client: Arc::clone(&client) })?;
+                client
+            },
+        }
+    };
+    Ok(client)
+}
+
+/// Return the BCM client a `canid` event mode caller transmits on, opening it on first use.
+///
+/// BCM cycles belong to the socket that installed them: a private socket keeps the TX
+/// statuses of the caller on its own event and lets `stop` only remove its own cycles.
+fn caller_tx_client(
+    request: &AfbRequest,
+    ctx: &SubVerbCtx,
+) -> Result<Arc<AfbClientData>, AfbError> {
+    session_client(request, ctx, 0, 0)?;
+    let caller = CallerCtx::get(request)?;
+    if let Some(client) = caller.tx.as_ref().filter(|client| !client.closed.get()) {
+        return Ok(Arc::clone(client));
+    }
+    let client = open_client(request, ctx, 0, 0, false)?;
+    client.event.subscribe(request)?;
+    caller.tx = Some(Arc::clone(&client));
+    Ok(client)
+}
+
 // ============ Subscribe Canids ===============
 /// Subscribe verb for BCM-handled CAN IDs.
 ///
 /// Responsibilities:
-/// - ensure a BCM socket and associated AFB event exist for the session,
-/// - subscribe the caller to that event,
+/// - check the caller holds a `subscribe` permission for every requested CAN ID,
+/// - enforce the configured quotas (sessions, canids per session, minimum rate),
+/// - ensure a BCM socket and associated AFB event exist for the session (or, in `canid`
+///   event mode, for the whole binding),
+/// - subscribe the caller to that event, and in `canid` event mode to each CAN ID event,
 /// - register the file descriptor callback in the AFB main loop,
 /// - install BCM RX filters (RxSetup) for the requested CAN IDs with optional timers.
 ///
@@ -142,83 +661,133 @@ pub(crate) fn subscribe_cb(
         return Err(error);
     }
 
-    // Check if a BCM session already exists for this request/session.
-    let session = match SessionCtx::get_from(request) {
-        Ok(session) => session,
-        Err(_) => {
-            // Open a new BCM socket on the configured CAN device.
-            let sockfd = match SockCanHandle::open_bcm(ctx.candev, CanTimeStamp::CLASSIC) {
-                Ok(handle) => handle,
-                Err(bcmerr) => {
-                    let error = AfbError::new("fail-sockbcm-open", 0, bcmerr.to_string());
-                    afb_log_msg!(Warning, request, &error);
-                    return Err(error);
-                },
-            };
-
-            // Create a new AFB event bound to `ctx.sockevt` to broadcast BCM notifications.
-            let event = AfbEvent::new(ctx.sockevt);
-            if event.register(request.get_api().get_apiv4()) < 0 {
-                let error = AfbError::new(
-                    "evt-fail-registration",
-                    0,
-                    format!("evt-fail-registration uid:{}", ctx.uid),
-                );
-                afb_log_msg!(Warning, request, &error);
-                return Err(error);
-            } else {
-                event.finalize()?;
-            }
-
-            // Client data shared across the event, BCM socket and session.
-            // NOTE: `Arc` is used even if `AfbClientData` is not Send/Sync; this is constrained
-            //   to the AFB event loop context.
-            #[allow(clippy::arc_with_non_send_sync)]
-            let client_data = Arc::new(AfbClientData {
-                uid: ctx.uid,
-                sockfd,
-                event,
-                rate: param.get_rate(),
-                watchdog: param.get_watchdog(),
-            });
+    let denied: Vec<u32> = param
+        .get_canids()
+        .iter()
+        .copied()
+        .filter(|canid| !ctx.state.granted(request, SockcanVerbClass::Subscribe, Some(*canid)))
+        .collect();
+    if !denied.is_empty() {
+        let error = AfbError::new(
+            "fail-canid-permission",
+            0,
+            format!("subscribe not permitted on canids={:?}", denied),
+        );
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
 
-            // Subscribe the current request to the newly created event.
-            client_data.event.subscribe(request)?;
+    let limits = &ctx.state.limits;
+    if !logic::rate_allowed(param.get_rate(), limits.min_rate) {
+        let error = AfbError::new(
+            "fail-rate-limit",
+            0,
+            format!("rate:{}ms below binding minimum:{}ms", param.get_rate(), limits.min_rate),
+        );
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
 
-            // Register the BCM socket file descriptor in the AFB main loop for async callbacks.
-            AfbEvtFd::new(ctx.uid)
-                .set_fd(client_data.sockfd.as_rawfd())
-                .set_events(AfbEvtFdPoll::IN)
-                .set_callback(async_can_cb)
-                .set_context(CanEvtCtx { client: Arc::clone(&client_data) })
-                .start()?;
+    let client = session_client(request, ctx, param.get_rate(), param.get_watchdog())?;
+    // In `canid` event mode quotas and rate demands are kept per caller.
+    let mut caller = if client.per_canid { Some(CallerCtx::get(request)?) } else { None };
 
-            // Attach the session context so future calls can reuse the same BCM socket and event.
-            SessionCtx::set_for(request, SessionCtx { client: Arc::clone(&client_data) })?
+    // Only count CAN IDs that are not already installed on this session (or caller).
+    let (installed, added) = match &caller {
+        Some(caller) => (
+            caller.canids.len(),
+            param.get_canids().iter().filter(|canid| !caller.canids.contains(canid)).count(),
+        ),
+        None => {
+            let canids = client.canids.borrow();
+            (
+                canids.len(),
+                param.get_canids().iter().filter(|canid| !canids.contains_key(canid)).count(),
+            )
         },
     };
+    if logic::quota_exceeded(installed, added, limits.max_canids) {
+        let error = AfbError::new(
+            "fail-canid-quota",
+            0,
+            format!(
+                "session uid:{} has {} canids, {} more exceed max:{}",
+                client.uid, installed, added, limits.max_canids
+            ),
+        );
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
+
+    // Raw source subscriptions read every frame from the session raw socket.
+    let raw = param.get_source() == SubscribeSource::RAW;
+    if raw && client.rawfd.borrow().is_none() {
+        if let Err(error) = start_raw_listener(&client) {
+            afb_log_msg!(Warning, request, &error);
+            return Err(error);
+        }
+    }
 
     // Subscribe to BCM CAN events for each requested CAN ID.
+    let demand = CanIdDemand {
+        rate: param.get_rate(),
+        watchdog: param.get_watchdog(),
+        flag: param.get_flag(),
+        raw,
+        mask: param.get_mask().cloned(),
+    };
+    let caller_id = caller.as_ref().map(|caller| caller.id);
     let mut can_error: Vec<u32> = Vec::new();
     for canid in param.get_canids() {
-        // Configure a BCM RX filter with timer support for this CAN ID.
-        let mut filter = SockBcmCmd::new(
-            CanBcmOpCode::RxSetup,
-            CanBcmFlag::RX_FILTER_ID
-                | CanBcmFlag::SET_TIMER
-                | CanBcmFlag::START_TIMER
-                | CanBcmFlag::RX_ANNOUNCE_RESUME,
-            *canid,
-        );
+        // A shared CAN ID serves the most demanding of its callers.
+        let (was_bcm, merged) = match client.canids.borrow().get(canid) {
+            Some(state) => (
+                !state.raw,
+                match caller_id {
+                    Some(id) => logic::merge_demands(
+                        state
+                            .demands
+                            .iter()
+                            .filter(|(caller, _)| **caller != id)
+                            .map(|(_, demand)| demand)
+                            .chain([&demand]),
+                    )
+                    .unwrap_or_else(|| demand.clone()),
+                    None => demand.clone(),
+                },
+            ),
+            None => (false, demand.clone()),
+        };
 
-        // Only set timers when the caller provides a non-zero rate or watchdog.
-        if param.get_rate() > 0 || param.get_watchdog() > 0 {
-            filter.set_timers(param.get_rate(), param.get_watchdog());
-        }
+        if apply_source(&client, *canid, was_bcm, &merged) {
+            client
+                .canids
+                .borrow_mut()
+                .entry(*canid)
+                .or_insert_with(|| CanIdCtx {
+                    rate: merged.rate,
+                    watchdog: merged.watchdog,
+                    frames: 0,
+                    last_seen: client.elapsed_us(),
+                    silent: false,
+                    flag: merged.flag.clone(),
+                    raw: merged.raw,
+                    change: None,
+                    demands: HashMap::new(),
+                })
+                .set_demand(&merged);
 
-        match filter.apply(&session.client.sockfd) {
-            Ok(()) => {},
-            Err(_error) => can_error.push(*canid),
+            // In `canid` event mode the caller only listens to the events of its CAN IDs.
+            if let Some(caller) = caller.as_mut() {
+                if let Some(state) = client.canids.borrow_mut().get_mut(canid) {
+                    state.demands.insert(caller.id, demand.clone());
+                }
+                caller.canids.insert(*canid);
+                canid_event(request, ctx, &client, *canid)?.subscribe(request)?;
+            }
+        } else {
+            client.counters.borrow_mut().filter_failures += 1;
+            can_error.push(*canid)
         }
     }
 
@@ -244,12 +813,21 @@ pub(crate) fn subscribe_cb(
 /// - remove RX filters (RxDelete) for the specified CAN IDs on the current session's BCM socket,
 /// - keep the underlying socket and event alive as long as the session itself remains valid.
 ///
+/// In `canid` event mode the caller is unsubscribed from each CAN ID event and its rate
+/// demand dropped; the shared filter is removed (RxDelete) with the last caller demand,
+/// otherwise it keeps running at the fastest remaining demand.
+///
 /// Expected request payload: `UnSubscribeParam`.
 pub(crate) fn unsubscribe_cb(
     request: &AfbRequest,
     args: &AfbRqtData,
-    _ctx: &AfbCtxData,
+    ctx: &AfbCtxData,
 ) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<StateCtx>()?;
+    if ctx.state.event_mode == SockcanEventMode::Canid {
+        return unsubscribe_canid_events(request, args, ctx);
+    }
+
     let session = SessionCtx::get_from(request)?;
     afb_log_msg!(Notice, request, "unsubscribe from session uid:{}", session.client.uid);
 
@@ -264,12 +842,16 @@ pub(crate) fn unsubscribe_cb(
     // Remove BCM CAN events for each requested CAN ID.
     let mut can_error: Vec<u32> = Vec::new();
     for canid in param.get_canids() {
-        // Remove the BCM RX filter(s) for this CAN ID.
+        // Remove the BCM RX filter(s) for this CAN ID, raw source CAN IDs have none.
         // TODO: explain – document whether `RxDelete` removes all filters for this ID or only one entry.
+        let raw = matches!(session.client.canids.borrow().get(canid), Some(state) if state.raw);
         let mut filter = SockBcmCmd::new(CanBcmOpCode::RxDelete, CanBcmFlag::NONE, *canid);
+        let removed = if raw { Ok(()) } else { filter.apply(&*session.client.sockfd.borrow()) };
 
-        match filter.apply(&session.client.sockfd) {
-            Ok(()) => {},
+        match removed {
+            Ok(()) => {
+                session.client.canids.borrow_mut().remove(canid);
+            },
             Err(_error) => can_error.push(*canid),
         }
     }
@@ -291,6 +873,221 @@ pub(crate) fn unsubscribe_cb(
     Ok(())
 }
 
+/// `canid` event mode unsubscribe: drop the caller from each CAN ID event, then release
+/// its demands on the shared filters.
+fn unsubscribe_canid_events(
+    request: &AfbRequest,
+    args: &AfbRqtData,
+    ctx: &StateCtx,
+) -> Result<(), AfbError> {
+    let param = args.get::<&UnSubscribeParam>(0)?;
+
+    if param.get_canids().is_empty() {
+        let error = AfbError::new("fail-empty-canids", 0, "canids list is empty");
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
+
+    let shared = ctx.state.shared.borrow().clone();
+    let mut released: Vec<u32> = Vec::new();
+    let mut can_error: Vec<u32> = Vec::new();
+    for canid in param.get_canids() {
+        let event = shared.as_ref().and_then(|client| client.events.borrow().get(canid).copied());
+        match event {
+            Some(event) => {
+                event.unsubscribe(request)?;
+                released.push(*canid);
+            },
+            None => can_error.push(*canid),
+        }
+    }
+
+    if let (Some(client), Ok(caller)) = (shared.as_ref(), CallerCtx::get(request)) {
+        released.retain(|canid| caller.canids.remove(canid));
+        release_demands(client, caller.id, &released);
+    }
+
+    if !can_error.is_empty() {
+        let error = AfbError::new(
+            "fail-canid-Unsubscribe",
+            0,
+            format!("No subscription for canids={:?}", can_error),
+        );
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
+
+    request.reply(AFB_NO_DATA, 0);
+    Ok(())
+}
+
+// ============ Send Frames ===============
+/// Send verb: transmit one classic CAN frame, or a remote transmission request.
+///
+/// The caller needs a `send` permission on the CAN ID. The frame is sent once (`TxSend`)
+/// or cyclically (`TxSetup` with `period`/`count`) on the caller BCM socket, created on
+/// first use as `subscribe` does (a private one per caller in `canid` event mode). Every
+/// outcome is also pushed on the event of that socket as a `CanBcmTxStatus`; they report
+/// what the kernel queued, a count-limited cycle reports `EXPIRED` once its last frame was.
+///
+/// Expected request payload: `SendParam`.
+pub(crate) fn send_cb(
+    request: &AfbRequest,
+    args: &AfbRqtData,
+    ctx: &AfbCtxData,
+) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<SubVerbCtx>()?;
+    let param = args.get::<&SendParam>(0)?;
+
+    if !ctx.state.granted(request, SockcanVerbClass::Send, Some(param.canid)) {
+        let error = AfbError::new(
+            "fail-canid-permission",
+            0,
+            format!("send not permitted on canid={}", param.canid),
+        );
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
+
+    let (canid, data) = match logic::tx_frame(param.canid, &param.data, param.rtr, param.len) {
+        Ok(frame) => frame,
+        Err(info) => {
+            let error = AfbError::new("fail-send-frame", 0, info);
+            afb_log_msg!(Warning, request, &error);
+            return Err(error);
+        },
+    };
+
+    let plan = match logic::tx_plan(param.period, param.count, param.stop) {
+        Ok(plan) => plan,
+        Err(info) => {
+            let error = AfbError::new("fail-send-frame", 0, info);
+            afb_log_msg!(Warning, request, &error);
+            return Err(error);
+        },
+    };
+
+    let client = match ctx.state.event_mode {
+        SockcanEventMode::Canid => caller_tx_client(request, ctx)?,
+        SockcanEventMode::Session => session_client(request, ctx, 0, 0)?,
+    };
+    let (mut frame, status) = match plan {
+        logic::TxPlan::Once => {
+            let mut frame = SockBcmCmd::new(CanBcmOpCode::TxSend, CanBcmFlag::NONE, canid);
+            frame.add_frame(canid, &data);
+            (frame, CanTxStatus::QUEUED)
+        },
+        logic::TxPlan::Cyclic { ival1, ival2, count } => {
+            let mut flags =
+                CanBcmFlag::SET_TIMER | CanBcmFlag::START_TIMER | CanBcmFlag::TX_ANNOUNCE;
+            if count > 0 {
+                flags |= CanBcmFlag::TX_COUNTEVT;
+            }
+            let mut frame = SockBcmCmd::new(CanBcmOpCode::TxSetup, flags, canid);
+            // set_timers(rate, watchdog) maps to ival2 and ival1 respectively.
+            frame.set_timers(ival2, ival1).set_count(count).add_frame(canid, &data);
+            (frame, CanTxStatus::STARTED)
+        },
+        logic::TxPlan::Stop => {
+            (SockBcmCmd::new(CanBcmOpCode::TxDelete, CanBcmFlag::NONE, canid), CanTxStatus::STOPPED)
+        },
+    };
+
+    if let Err(canerr) = frame.apply(&*client.sockfd.borrow()) {
+        // ENOBUFS (TX queue full), ENETDOWN, ... are reported to event listeners as well.
+        client.event.push(CanBcmTxStatus {
+            canid,
+            status: CanTxStatus::ERROR,
+            info: canerr.to_string(),
+        });
+        let error = AfbError::new("fail-sockbcm-send", 0, canerr.to_string());
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
+    client.event.push(CanBcmTxStatus { canid, status, info: String::new() });
+
+    request.reply(AFB_NO_DATA, 0);
+    Ok(())
+}
+
+/// Install the RTR auto-reply of `canid` on the responder socket.
+fn rtr_reply_setup(sockfd: &SockCanHandle, canid: u32, data: &[u8]) -> Result<(), CanError> {
+    let mut reply = SockBcmCmd::new(CanBcmOpCode::RxSetup, CanBcmFlag::RX_RTR_FRAME, canid);
+    reply.add_frame(canid, data);
+    reply.apply(sockfd)
+}
+
+/// RTR auto-reply verb: let the kernel answer remote transmission requests.
+///
+/// A BCM `RxSetup` with `RX_RTR_FRAME` stores the reply frame of a CAN ID; every RTR
+/// received for it is answered by the kernel without waking the binding. Replies are
+/// binding-wide, live on a dedicated socket and survive a link down/up.
+///
+/// Expected request payload: `RtrReplyParam`.
+pub(crate) fn rtr_reply_cb(
+    request: &AfbRequest,
+    args: &AfbRqtData,
+    ctx: &AfbCtxData,
+) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<SubVerbCtx>()?;
+    let param = args.get::<&RtrReplyParam>(0)?;
+
+    if !ctx.state.granted(request, SockcanVerbClass::Send, Some(param.canid)) {
+        let error = AfbError::new(
+            "fail-canid-permission",
+            0,
+            format!("rtr reply not permitted on canid={}", param.canid),
+        );
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
+
+    let (canid, data) = match logic::tx_frame(param.canid, &param.data, false, 0) {
+        Ok(frame) => frame,
+        Err(info) => {
+            let error = AfbError::new("fail-send-frame", 0, info);
+            afb_log_msg!(Warning, request, &error);
+            return Err(error);
+        },
+    };
+
+    let mut responder = ctx.state.responder.borrow_mut();
+    if responder.is_none() {
+        match SockCanHandle::open_bcm(ctx.candev, CanTimeStamp::CLASSIC) {
+            Ok(handle) => *responder = Some(handle),
+            Err(bcmerr) => {
+                let error = AfbError::new("fail-sockbcm-open", 0, bcmerr.to_string());
+                afb_log_msg!(Warning, request, &error);
+                return Err(error);
+            },
+        }
+    }
+    let sockfd = match responder.as_ref() {
+        Some(sockfd) => sockfd,
+        None => return Err(AfbError::new("fail-sockbcm-open", 0, "no responder socket")),
+    };
+
+    let status = if param.delete {
+        SockBcmCmd::new(CanBcmOpCode::RxDelete, CanBcmFlag::NONE, canid).apply(sockfd)
+    } else {
+        rtr_reply_setup(sockfd, canid, &data)
+    };
+    if let Err(canerr) = status {
+        let error = AfbError::new("fail-rtr-reply", 0, canerr.to_string());
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
+
+    if param.delete {
+        ctx.state.rtr_replies.borrow_mut().remove(&canid);
+    } else {
+        ctx.state.rtr_replies.borrow_mut().insert(canid, data);
+    }
+
+    request.reply(AFB_NO_DATA, 0);
+    Ok(())
+}
+
 // ============ Close SockBcm ===============
 /// Close the BCM subscription for the current session.
 ///
@@ -299,15 +1096,39 @@ pub(crate) fn unsubscribe_cb(
 /// - unreferences the AFB event,
 /// - closes the underlying BCM socket,
 /// - detaches the `SessionCtx` from the request.
+///
+/// In `canid` event mode the shared socket stays open: the caller is unsubscribed from
+/// every event of the binding and its rate demands are released.
 pub(crate) fn close_cb(
     request: &AfbRequest,
     _args: &AfbRqtData,
-    _ctx: &AfbCtxData,
+    ctx: &AfbCtxData,
 ) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<StateCtx>()?;
+    if ctx.state.event_mode == SockcanEventMode::Canid {
+        if let Some(client) = ctx.state.shared.borrow().as_ref() {
+            for event in client.events.borrow().values() {
+                let _ = event.unsubscribe(request);
+            }
+            let _ = client.event.unsubscribe(request);
+            if let Ok(caller) = CallerCtx::get(request) {
+                let canids: Vec<u32> = caller.canids.drain().collect();
+                release_demands(client, caller.id, &canids);
+            }
+        }
+        if let Ok(caller) = CallerCtx::get(request) {
+            if let Some(tx) = caller.tx.take() {
+                tx.close();
+            }
+        }
+        let _ = CallerCtx::unref(request);
+        request.reply(AFB_NO_DATA, 0);
+        return Ok(());
+    }
+
     let session = SessionCtx::get_from(request)?;
     afb_log_msg!(Notice, request, "closing subscription uid:{}", session.client.uid);
-    session.client.event.unref();
-    session.client.sockfd.close();
+    session.client.close();
     let _ = SessionCtx::unref_from(request);
     Ok(())
 }
@@ -323,6 +1144,12 @@ pub(crate) fn check_cb(
     ctx: &AfbCtxData,
 ) -> Result<(), AfbError> {
     let vbdata: &mut CheckCtx = ctx.get_mut::<CheckCtx>()?;
+    if !vbdata.state.granted(request, SockcanVerbClass::Configure, None) {
+        let error = AfbError::new("fail-verb-permission", 0, "check not permitted");
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
+
     // Best-effort open/close of a BCM socket to validate connectivity and configuration.
     match SockCanHandle::open_bcm(vbdata.candev, CanTimeStamp::CLASSIC) {
         Ok(sock) => sock.close(),
@@ -336,3 +1163,408 @@ pub(crate) fn check_cb(
     request.reply(AFB_NO_DATA, 0);
     Ok(())
 }
+
+// =========== Metrics SockBcm ===============
+/// Runtime counters verb.
+///
+/// Replies with a `CanBcmMetrics` snapshot of every live session. When called with
+/// `{'subscribe':true}` (resp. `false`) the caller is also (un)subscribed to the periodic
+/// `metrics` event, which is only available when `metrics_period` is configured.
+pub(crate) fn metrics_cb(
+    request: &AfbRequest,
+    args: &AfbRqtData,
+    ctx: &AfbCtxData,
+) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<MetricsCtx>()?;
+    if !ctx.state.granted(request, SockcanVerbClass::Configure, None) {
+        let error = AfbError::new("fail-verb-permission", 0, "metrics not permitted");
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
+
+    if let Ok(jquery) = args.get::<JsoncObj>(0) {
+        if let Ok(subscribe) = jquery.get::<bool>("subscribe") {
+            let event = match ctx.event {
+                Some(event) => event,
+                None => {
+                    let error = AfbError::new(
+                        "fail-metrics-disabled",
+                        0,
+                        "periodic metrics event requires 'metrics_period' in binding config",
+                    );
+                    afb_log_msg!(Warning, request, &error);
+                    return Err(error);
+                },
+            };
+            if subscribe {
+                event.subscribe(request)?;
+            } else {
+                event.unsubscribe(request)?;
+            }
+        }
+    }
+
+    let mut params = AfbParams::new();
+    params.push(ctx.state.metrics())?;
+    request.reply(params, 0);
+    Ok(())
+}
+
+/// Periodic timer callback pushing a `CanBcmMetrics` snapshot on the `metrics` event.
+pub(crate) fn metrics_timer_cb(
+    _timer: &AfbTimer,
+    _decount: u32,
+    ctx: &AfbCtxData,
+) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<MetricsCtx>()?;
+    if let Some(event) = ctx.event {
+        event.push(ctx.state.metrics());
+    }
+    Ok(())
+}
+
+// =========== List SockBcm ===============
+/// Return the RX filters `/proc/net/can-bcm` lists for a BCM socket, None when unreadable.
+///
+/// The kernel names each socket entry after its inode number.
+fn bcm_proc_filters(sockfd: &SockCanHandle) -> Option<Vec<u32>> {
+    let inode = std::fs::metadata(format!("/proc/self/fd/{}", sockfd.as_rawfd())).ok()?.ino();
+    let text = std::fs::read_to_string(format!("/proc/net/can-bcm/{}", inode)).ok()?;
+    Some(logic::parse_bcm_proc(&text))
+}
+
+/// Active subscriptions introspection verb.
+///
+/// Replies with a `CanBcmSubscriptions` listing every live session (only the caller one
+/// with `{'session':true}`) and its CAN IDs. With `{'kernel':true}` the RX filters are
+/// cross-checked against `/proc/net/can-bcm`, which helps tracking down missing events.
+pub(crate) fn list_cb(
+    request: &AfbRequest,
+    args: &AfbRqtData,
+    ctx: &AfbCtxData,
+) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<StateCtx>()?;
+    if !ctx.state.granted(request, SockcanVerbClass::Configure, None) {
+        let error = AfbError::new("fail-verb-permission", 0, "list not permitted");
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
+
+    let (mut own, mut kernel) = (false, false);
+    if let Ok(jquery) = args.get::<JsoncObj>(0) {
+        own = jquery.get::<bool>("session").unwrap_or(false);
+        kernel = jquery.get::<bool>("kernel").unwrap_or(false);
+    }
+    if kernel && !Path::new("/proc/net/can-bcm").is_dir() {
+        let error = AfbError::new("fail-bcm-proc", 0, "/proc/net/can-bcm not available");
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
+
+    let clients = match (own, ctx.state.event_mode) {
+        (false, _) => ctx.state.live_sessions(),
+        (true, SockcanEventMode::Canid) => ctx.state.shared.borrow().iter().cloned().collect(),
+        (true, SockcanEventMode::Session) => match SessionCtx::get_from(request) {
+            Ok(session) => vec![Arc::clone(&session.client)],
+            Err(_) => Vec::new(),
+        },
+    };
+
+    let sessions = clients
+        .iter()
+        .filter(|client| !client.closed.get())
+        .map(|client| {
+            let filters = if kernel { bcm_proc_filters(&client.sockfd.borrow()) } else { None };
+            client.subscriptions(filters)
+        })
+        .collect();
+
+    let mut params = AfbParams::new();
+    params.push(CanBcmSubscriptions { event_mode: ctx.state.event_mode, sessions })?;
+    request.reply(params, 0);
+    Ok(())
+}
+
+// =========== Bus load ===============
+/// Raw monitor socket callback: account the on-wire size of every received frame.
+pub(crate) fn busload_can_cb(
+    _evtfd: &AfbEvtFd,
+    revent: u32,
+    ctx: &AfbCtxData,
+) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<BusLoadVerbCtx>()?;
+    let monitor = match &ctx.monitor {
+        Some(monitor) => monitor,
+        None => return Ok(()),
+    };
+
+    if revent == AfbEvtFdPoll::IN.bits() {
+        let (canid, len) = match monitor.sockfd.get_can_frame() {
+            CanAnyFrame::RawStd(frame) => (frame.get_id(), frame.get_len()),
+            CanAnyFrame::RawFd(frame) => (frame.get_id(), frame.get_len()),
+            _ => return Ok(()),
+        };
+        let bits = logic::can_frame_bits(
+            canid & logic::CAN_EFF_FLAG != 0,
+            canid & logic::CAN_RTR_FLAG != 0,
+            len,
+        );
+        monitor.load.borrow_mut().add_frame(bits);
+    }
+    Ok(())
+}
+
+/// Sampling timer: close the bus load window, publish it and warn on high load.
+pub(crate) fn busload_timer_cb(
+    _timer: &AfbTimer,
+    _decount: u32,
+    ctx: &AfbCtxData,
+) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<BusLoadVerbCtx>()?;
+    let monitor = match &ctx.monitor {
+        Some(monitor) => monitor,
+        None => return Ok(()),
+    };
+
+    let now = monitor.started.elapsed().as_micros() as u64;
+    let sample = monitor.load.borrow_mut().sample(now, monitor.bitrate);
+    let high = sample.load > monitor.threshold as f64;
+
+    let busload = CanBusLoad {
+        device: monitor.device.to_string(),
+        bitrate: monitor.bitrate,
+        load: sample.load,
+        frames: sample.frames,
+        period: sample.period / 1000,
+        threshold: monitor.threshold,
+        high,
+    };
+
+    let was_high = monitor.last.replace(busload.clone()).high;
+    if high && !was_high {
+        afb_log_msg!(
+            Warning,
+            monitor.event,
+            "sockcan-busload-high dev:{} load:{:.1}% threshold:{}%",
+            monitor.device,
+            sample.load,
+            monitor.threshold
+        );
+    }
+
+    monitor.event.push(busload);
+    Ok(())
+}
+
+/// Bus load verb: reply with the last sample and optionally (un)subscribe to the
+/// periodic `busload` event with `{'subscribe':true|false}`.
+pub(crate) fn busload_cb(
+    request: &AfbRequest,
+    args: &AfbRqtData,
+    ctx: &AfbCtxData,
+) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<BusLoadVerbCtx>()?;
+    if !ctx.state.granted(request, SockcanVerbClass::Configure, None) {
+        let error = AfbError::new("fail-verb-permission", 0, "busload not permitted");
+        afb_log_msg!(Warning, request, &error);
+        return Err(error);
+    }
+    let monitor = match &ctx.monitor {
+        Some(monitor) => monitor,
+        None => {
+            let error = AfbError::new(
+                "fail-busload-disabled",
+                0,
+                "bus load requires 'busload':{'period':xx_ms} in binding config",
+            );
+            afb_log_msg!(Warning, request, &error);
+            return Err(error);
+        },
+    };
+
+    if let Ok(jquery) = args.get::<JsoncObj>(0) {
+        if let Ok(subscribe) = jquery.get::<bool>("subscribe") {
+            if subscribe {
+                monitor.event.subscribe(request)?;
+            } else {
+                monitor.event.unsubscribe(request)?;
+            }
+        }
+    }
+
+    let mut params = AfbParams::new();
+    params.push(monitor.last.borrow().clone())?;
+    request.reply(params, 0);
+    Ok(())
+}
+
+// =========== Link state ===============
+/// Reopen the BCM socket of a degraded session and reinstall every RX filter.
+///
+/// The main loop registration of each previous socket, with its client reference, is
+/// released before the socket is closed, since the next open may reuse its descriptor
+/// number. Filters use the rate and watchdog stored per CAN ID.
+/// Returns the restored and failed CAN IDs.
+fn recover_session(client: &Arc<AfbClientData>) -> Result<(Vec<u32>, Vec<u32>), AfbError> {
+    let sockfd = match SockCanHandle::open_bcm(client.candev, CanTimeStamp::CLASSIC) {
+        Ok(handle) => handle,
+        Err(bcmerr) => return Err(AfbError::new("fail-sockbcm-open", 0, bcmerr.to_string())),
+    };
+    if let Some(evtfd) = client.bcm_evtfd.take() {
+        evtfd.unref();
+    }
+    client.sockfd.replace(sockfd).close();
+    start_bcm_listener(client)?;
+
+    // The raw socket of `RAW` source subscriptions is reopened the same way.
+    let rawfd = client.rawfd.borrow_mut().take();
+    let raw_ok = match rawfd {
+        Some(rawfd) => {
+            if let Some(evtfd) = client.raw_evtfd.take() {
+                evtfd.unref();
+            }
+            rawfd.close();
+            start_raw_listener(client).is_ok()
+        },
+        None => true,
+    };
+
+    let now = client.elapsed_us();
+    let mut restored = Vec::new();
+    let mut failed = Vec::new();
+    for (canid, state) in client.canids.borrow_mut().iter_mut() {
+        let status = if state.raw {
+            if raw_ok {
+                Ok(())
+            } else {
+                Err(())
+            }
+        } else {
+            rx_filter(*canid, state.rate, state.watchdog)
+                .apply(&*client.sockfd.borrow())
+                .map_err(|_error| ())
+        };
+        match status {
+            Ok(()) => {
+                state.last_seen = now;
+                state.silent = false;
+                restored.push(*canid);
+            },
+            Err(_error) => {
+                client.counters.borrow_mut().filter_failures += 1;
+                failed.push(*canid);
+            },
+        }
+    }
+    restored.sort_unstable();
+    failed.sort_unstable();
+    Ok((restored, failed))
+}
+
+/// Close the RTR responder socket when the link goes down, and reinstall every
+/// auto-reply on a fresh socket once it is back.
+fn recover_rtr_replies(ctx: &LinkCtx, up: bool) {
+    let mut responder = ctx.state.responder.borrow_mut();
+    if !up {
+        if let Some(sockfd) = responder.take() {
+            sockfd.close();
+        }
+        return;
+    }
+    if responder.is_some() || ctx.state.rtr_replies.borrow().is_empty() {
+        return;
+    }
+
+    let sockfd = match SockCanHandle::open_bcm(ctx.device, CanTimeStamp::CLASSIC) {
+        Ok(handle) => handle,
+        Err(_error) => return, // retried on the next link change
+    };
+    for (canid, data) in ctx.state.rtr_replies.borrow().iter() {
+        let _ = rtr_reply_setup(&sockfd, *canid, data);
+    }
+    *responder = Some(sockfd);
+}
+
+/// rtnetlink callback tracking the CAN interface link state.
+///
+/// On a down transition every live session is marked degraded and receives a `DOWN`
+/// `CanBcmLink` event. On an up transition degraded sessions get a fresh BCM socket with
+/// their `RxSetup` filters reinstalled, then an `UP` event listing restored CAN IDs.
+/// A session that cannot be reopened stays degraded and is retried on the next change.
+pub(crate) fn link_state_cb(
+    _evtfd: &AfbEvtFd,
+    revent: u32,
+    ctx: &AfbCtxData,
+) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<LinkCtx>()?;
+    if revent != AfbEvtFdPoll::IN.bits() {
+        return Ok(());
+    }
+
+    // A batch may hold a down/up flap (e.g. bus-off restart): degrade then recover.
+    let changes: Vec<bool> = ctx
+        .monitor
+        .read_changes()
+        .into_iter()
+        .filter(|change| change.ifname == ctx.device)
+        .map(|change| change.up)
+        .collect();
+    let up = match changes.last() {
+        Some(up) => *up,
+        None => return Ok(()),
+    };
+    let went_down = ctx.up.replace(up) && changes.contains(&false);
+    if went_down {
+        recover_rtr_replies(ctx, false);
+    }
+    if up {
+        recover_rtr_replies(ctx, true);
+    }
+
+    for client in ctx.state.live_sessions() {
+        if went_down {
+            client.degraded.set(true);
+            afb_log_msg!(
+                Warning,
+                client.event,
+                "sockbcm-link-down uid:{} dev:{}",
+                client.uid,
+                ctx.device
+            );
+            client.event.push(CanBcmLink {
+                device: ctx.device.to_string(),
+                status: CanLinkStatus::DOWN,
+                restored: Vec::new(),
+                failed: Vec::new(),
+            });
+        }
+
+        if !up || !client.degraded.get() {
+            continue;
+        }
+        match recover_session(&client) {
+            Ok((restored, failed)) => {
+                client.degraded.set(false);
+                afb_log_msg!(
+                    Notice,
+                    client.event,
+                    "sockbcm-link-recovered uid:{} dev:{} restored:{:?} failed:{:?}",
+                    client.uid,
+                    ctx.device,
+                    restored,
+                    failed
+                );
+                client.event.push(CanBcmLink {
+                    device: ctx.device.to_string(),
+                    status: CanLinkStatus::UP,
+                    restored,
+                    failed,
+                });
+            },
+            Err(error) => {
+                afb_log_msg!(Warning, client.event, &error);
+            },
+        }
+    }
+    Ok(())
+}
@@ -21,24 +21,309 @@
  * $RP_END_LICENSE$
  */
 
+use crate::logic::{self, BusLoad, CanIdDemand, ChangeFilter, EventBudget};
+use crate::netlink::LinkMonitor;
 use afbv4::prelude::*;
 use sockcan::prelude::SockCanHandle;
+use sockdata::types::{
+    CanBcmIdMetrics, CanBcmIdSubscription, CanBcmMetrics, CanBcmSessionMetrics,
+    CanBcmSessionSubscriptions, CanBusLoad, CanSocketState, SockcanAclRule, SockcanBindingConfig,
+    SockcanEventMode, SockcanLimits, SockcanVerbClass, SubscribeFlag, SubscribeSource,
+};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Instant;
+
+/// Subscription state kept for each CAN ID installed on a BCM socket.
+///
+/// `last_seen` is expressed in `AfbClientData::elapsed_us` time and `silent` is set
+/// between a BCM `RxTimeout` and the next received frame. `flag` is only kept for the
+/// `list` verb. `raw` CAN IDs are read from the
+/// session raw socket instead of a BCM filter, and `change` holds the userspace change
+/// detection stage when the subscription asked for one. In `canid` event mode `demands`
+/// keeps the subscription asked by each caller, the other fields holding their merge.
+pub(crate) struct CanIdCtx {
+    pub rate: u64,
+    pub watchdog: u64,
+    pub frames: u64,
+    pub last_seen: u64,
+    pub silent: bool,
+    pub flag: SubscribeFlag,
+    pub raw: bool,
+    pub change: Option<ChangeFilter>,
+    pub demands: HashMap<u32, CanIdDemand>,
+}
+
+impl CanIdCtx {
+    /// Run the CAN ID at `demand`, rebuilding its change detection stage.
+    pub fn set_demand(&mut self, demand: &CanIdDemand) {
+        self.rate = demand.rate;
+        self.watchdog = demand.watchdog;
+        self.flag = demand.flag.clone();
+        self.raw = demand.raw;
+        self.change = logic::change_filter(
+            demand.raw,
+            demand.flag.clone(),
+            demand.mask.as_ref(),
+            demand.rate,
+            demand.watchdog,
+        );
+    }
+}
+
+/// Runtime counters kept for each session, reported by the `metrics` verb.
+#[derive(Default)]
+pub(crate) struct SessionCounters {
+    pub frames: u64,
+    pub events: u64,
+    pub rx_timeouts: u64,
+    pub decode_errors: u64,
+    pub filter_failures: u64,
+    pub listeners: i32,
+}
 
 /// Per-client/session runtime data for a CAN BCM subscription.
 ///
 /// This structure ties together:
 /// - a logical client `uid`,
-/// - the BCM socket handle,
-/// - the associated AFB event used to publish BCM frames,
-/// - the rate/watchdog values used for BCM timers.
+/// - the CAN device and BCM socket handle, replaced when the link comes back,
+/// - the raw socket opened by the first `RAW` source subscription,
+/// - the main loop registrations (`AfbEvtFd`) of both sockets,
+/// - the associated AFB event used to publish BCM frames, or in `canid` event mode the
+///   per-CAN ID events, the session event then only carries link state and errors,
+/// - the rate/watchdog values used for BCM timers,
+/// - the subscribed CAN IDs, the per-second event budget and runtime counters,
+/// - the `degraded` flag, set while the CAN interface link is down.
 ///
 pub(crate) struct AfbClientData {
     pub uid: &'static str,
-    pub sockfd: SockCanHandle,
+    pub session: u32,
+    pub candev: &'static str,
+    pub sockfd: RefCell<SockCanHandle>,
+    pub rawfd: RefCell<Option<SockCanHandle>>,
+    pub bcm_evtfd: Cell<Option<&'static AfbEvtFd>>,
+    pub raw_evtfd: Cell<Option<&'static AfbEvtFd>>,
     pub event: &'static AfbEvent,
+    pub events: RefCell<HashMap<u32, &'static AfbEvent>>,
+    pub per_canid: bool,
     pub rate: u64,
     pub watchdog: u64,
+    pub max_events: u64,
+    pub canids: RefCell<HashMap<u32, CanIdCtx>>,
+    pub budget: RefCell<EventBudget>,
+    pub counters: RefCell<SessionCounters>,
+    pub started: Instant,
+    pub closed: Cell<bool>,
+    pub degraded: Cell<bool>,
+}
+
+impl AfbClientData {
+    /// Monotonic time in microseconds since this client was created.
+    pub(crate) fn elapsed_us(&self) -> u64 {
+        self.started.elapsed().as_micros() as u64
+    }
+
+    /// Event carrying the notifications of `canid`: its own event in `canid` event mode,
+    /// the session event otherwise.
+    pub(crate) fn event_for(&self, canid: Option<u32>) -> &'static AfbEvent {
+        match canid.and_then(|canid| self.events.borrow().get(&canid).copied()) {
+            Some(event) => event,
+            None => self.event,
+        }
+    }
+
+    /// Release the AFB events, main loop registrations and sockets, and mark the client
+    /// as closed.
+    pub(crate) fn close(&self) {
+        if !self.closed.replace(true) {
+            for evtfd in [self.bcm_evtfd.take(), self.raw_evtfd.take()].into_iter().flatten() {
+                evtfd.unref();
+            }
+            for (_canid, event) in self.events.borrow_mut().drain() {
+                event.unref();
+            }
+            self.event.unref();
+            self.sockfd.borrow().close();
+            if let Some(rawfd) = self.rawfd.borrow_mut().take() {
+                rawfd.close();
+            }
+        }
+    }
+
+    /// Snapshot of this session subscriptions.
+    ///
+    /// `kernel` holds the RX filters found in `/proc/net/can-bcm` for the BCM socket
+    /// when a cross-check was requested.
+    pub(crate) fn subscriptions(&self, kernel: Option<Vec<u32>>) -> CanBcmSessionSubscriptions {
+        let canids = self.canids.borrow();
+        let mut subscriptions: Vec<CanBcmIdSubscription> = canids
+            .iter()
+            .map(|(canid, state)| CanBcmIdSubscription {
+                canid: *canid,
+                rate: state.rate,
+                watchdog: state.watchdog,
+                flag: state.flag.clone(),
+                source: if state.raw { SubscribeSource::RAW } else { SubscribeSource::BCM },
+                silent: state.silent,
+                frames: state.frames,
+                kernel: match &kernel {
+                    Some(filters) if !state.raw => Some(filters.contains(canid)),
+                    _ => None,
+                },
+            })
+            .collect();
+        subscriptions.sort_by_key(|entry| entry.canid);
+
+        // Kernel filters the binding does not know about, e.g. left over by a failed delete.
+        let unknown = kernel.map(|filters| {
+            filters
+                .into_iter()
+                .filter(|canid| !matches!(canids.get(canid), Some(state) if !state.raw))
+                .collect()
+        });
+
+        CanBcmSessionSubscriptions {
+            session: self.session,
+            uid: self.uid.to_string(),
+            device: self.candev.to_string(),
+            listeners: self.counters.borrow().listeners,
+            socket: if self.degraded.get() {
+                CanSocketState::DEGRADED
+            } else {
+                CanSocketState::OPEN
+            },
+            raw: self.rawfd.borrow().is_some(),
+            canids: subscriptions,
+            unknown,
+        }
+    }
+
+    /// Snapshot of this session counters.
+    pub(crate) fn metrics(&self) -> CanBcmSessionMetrics {
+        let counters = self.counters.borrow();
+        let mut canids: Vec<CanBcmIdMetrics> = self
+            .canids
+            .borrow()
+            .iter()
+            .map(|(canid, state)| CanBcmIdMetrics { canid: *canid, frames: state.frames })
+            .collect();
+        canids.sort_by_key(|entry| entry.canid);
+
+        CanBcmSessionMetrics {
+            session: self.session,
+            listeners: counters.listeners,
+            frames: counters.frames,
+            events: counters.events,
+            rx_timeouts: counters.rx_timeouts,
+            decode_errors: counters.decode_errors,
+            filter_failures: counters.filter_failures,
+            dropped: self.budget.borrow().get_dropped(),
+            canids,
+        }
+    }
+}
+
+/// Binding-wide runtime state shared by the verbs.
+///
+/// Keeps track of active BCM sessions so quotas can be enforced across clients,
+/// and of the fine-grained permission rules with their AFB permission handles.
+/// In `canid` event mode, `shared` holds the single BCM client serving every caller.
+/// RTR auto-replies live on a dedicated `responder` socket, `rtr_replies` keeps their
+/// payload so they can be reinstalled after a link down/up.
+pub(crate) struct SockcanState {
+    pub event_mode: SockcanEventMode,
+    pub shared: RefCell<Option<Arc<AfbClientData>>>,
+    pub responder: RefCell<Option<SockCanHandle>>,
+    pub rtr_replies: RefCell<HashMap<u32, Vec<u8>>>,
+    pub limits: SockcanLimits,
+    pub permissions: Vec<SockcanAclRule>,
+    pub acls: HashMap<&'static str, &'static AfbPermission>,
+    pub sessions: RefCell<Vec<Arc<AfbClientData>>>,
+    pub callers: Cell<usize>,
+    pub next_session: Cell<u32>,
+}
+
+impl SockcanState {
+    pub(crate) fn new(config: &SockcanBindingConfig) -> Rc<Self> {
+        let mut acls = HashMap::new();
+        for rule in &config.permissions {
+            acls.entry(rule.acl).or_insert_with(|| AfbPermission::new(rule.acl));
+        }
+
+        Rc::new(SockcanState {
+            event_mode: config.event_mode,
+            shared: RefCell::new(None),
+            responder: RefCell::new(None),
+            rtr_replies: RefCell::new(HashMap::new()),
+            limits: config.limits.clone(),
+            permissions: config.permissions.clone(),
+            acls,
+            sessions: RefCell::new(Vec::new()),
+            callers: Cell::new(0),
+            next_session: Cell::new(1),
+        })
+    }
+
+    /// Allocate a session number, unique within the binding lifetime.
+    pub(crate) fn new_session_id(&self) -> u32 {
+        let session = self.next_session.get();
+        self.next_session.set(session.wrapping_add(1));
+        session
+    }
+
+    /// Snapshot of the counters of every live session, with binding-wide totals.
+    pub(crate) fn metrics(&self) -> CanBcmMetrics {
+        self.session_count(); // forget closed sessions first
+        let mut metrics = CanBcmMetrics::default();
+        for client in self.sessions.borrow().iter() {
+            let session = client.metrics();
+            metrics.frames += session.frames;
+            metrics.events += session.events;
+            metrics.rx_timeouts += session.rx_timeouts;
+            metrics.decode_errors += session.decode_errors;
+            metrics.filter_failures += session.filter_failures;
+            metrics.dropped += session.dropped;
+            metrics.sessions.push(session);
+        }
+        metrics
+    }
+
+    /// Return true when the caller may perform `class` on `canid`
+    /// (or on the verb itself when `canid` is None).
+    pub(crate) fn granted(
+        &self,
+        request: &AfbRequest,
+        class: SockcanVerbClass,
+        canid: Option<u32>,
+    ) -> bool {
+        match logic::required_acls(&self.permissions, class, canid) {
+            None => true,
+            Some(acls) => acls.iter().any(|acl| match self.acls.get(acl) {
+                Some(permission) => request.check_permission(permission),
+                None => false,
+            }),
+        }
+    }
+
+    /// Track a newly created session.
+    pub(crate) fn add_session(&self, client: &Arc<AfbClientData>) {
+        self.sessions.borrow_mut().push(Arc::clone(client));
+    }
+
+    /// Snapshot of the live sessions, forgetting the closed ones.
+    pub(crate) fn live_sessions(&self) -> Vec<Arc<AfbClientData>> {
+        self.session_count();
+        self.sessions.borrow().clone()
+    }
+
+    /// Return the number of live sessions, forgetting the closed ones.
+    pub(crate) fn session_count(&self) -> usize {
+        let mut sessions = self.sessions.borrow_mut();
+        sessions.retain(|client| !client.closed.get());
+        sessions.len()
+    }
 }
 
 /// Context passed to the event file-descriptor callback handling BCM traffic.
@@ -92,21 +377,102 @@ impl SessionCtx {
     }
 }
 
-/// Context passed to the "subscribe" verb.
+// In `canid` event mode every caller shares one BCM client; `CallerCtx` tracks what each
+// caller session asked for, `caller_closing` drops its demands when the session ends.
+AfbSessionRegister!(CallerCtx, caller_closing);
+
+/// Per-caller state in `canid` event mode.
+///
+/// `id` keys the caller demands in `CanIdCtx::demands`, `canids` lists the CAN IDs it
+/// subscribed to and counts against `max_canids`. `tx` is the private BCM client the
+/// caller transmits on, so its cycles and TX statuses are not shared with other callers.
+pub(crate) struct CallerCtx {
+    pub id: u32,
+    pub state: Rc<SockcanState>,
+    pub canids: HashSet<u32>,
+    pub tx: Option<Arc<AfbClientData>>,
+}
+
+/// Caller closing callback: release the caller slot, its TX socket and its demands.
+fn caller_closing(caller: &mut CallerCtx) {
+    caller.state.callers.set(caller.state.callers.get().saturating_sub(1));
+    if let Some(tx) = caller.tx.take() {
+        tx.close();
+    }
+    let shared = caller.state.shared.borrow().clone();
+    if let Some(client) = shared {
+        let canids: Vec<u32> = caller.canids.drain().collect();
+        crate::callbacks::release_demands(&client, caller.id, &canids);
+    }
+}
+
+/// Context passed to the "subscribe", "send" and "rtr_reply" verbs.
 ///
 /// It aggregates static configuration used when creating a new BCM session:
 /// - `uid`: logical identifier used for logging and resource naming,
 /// - `sockevt`: AFB event name for BCM notifications,
-/// - `candev`: CAN device name (e.g. "can0") used to open the BCM socket.
+/// - `candev`: CAN device name (e.g. "can0") used to open the BCM socket,
+/// - `state`: binding-wide state used to enforce quotas.
 pub(crate) struct SubVerbCtx {
     pub uid: &'static str,
     pub sockevt: &'static str,
     pub candev: &'static str,
+    pub state: Rc<SockcanState>,
+}
+
+/// Context passed to the "unsubscribe" and "close" verbs, which depend on the event mode.
+pub(crate) struct StateCtx {
+    pub state: Rc<SockcanState>,
 }
 
 /// Context passed to the "check" verb.
 ///
-/// Contains the CAN device name to be probed when performing a BCM availability check.
+/// Contains the CAN device name to be probed when performing a BCM availability check,
+/// and the binding state used for permission checks.
 pub(crate) struct CheckCtx {
     pub candev: &'static str,
+    pub state: Rc<SockcanState>,
+}
+
+/// Context passed to the "metrics" verb and to the periodic metrics timer.
+///
+/// `event` is only set when `metrics_period` is configured.
+pub(crate) struct MetricsCtx {
+    pub state: Rc<SockcanState>,
+    pub event: Option<&'static AfbEvent>,
+}
+
+/// Raw-mode monitor used to estimate the bus load of one CAN interface.
+///
+/// The raw socket sees every frame on the bus, unlike BCM sessions whose delivery is
+/// throttled by the subscription rate. It is shared between the socket callback that
+/// accumulates frame sizes, the sampling timer and the "busload" verb.
+pub(crate) struct BusLoadCtx {
+    pub device: &'static str,
+    pub bitrate: u64,
+    pub threshold: u64,
+    pub sockfd: SockCanHandle,
+    pub event: &'static AfbEvent,
+    pub load: RefCell<BusLoad>,
+    pub last: RefCell<CanBusLoad>,
+    pub started: Instant,
+}
+
+/// Context passed to the bus load socket callback, timer and verb.
+///
+/// `monitor` is only set when `busload_period` is configured, `state` is used for
+/// permission checks.
+pub(crate) struct BusLoadVerbCtx {
+    pub monitor: Option<Rc<BusLoadCtx>>,
+    pub state: Rc<SockcanState>,
+}
+
+/// Context passed to the rtnetlink callback watching the CAN interface link state.
+///
+/// `up` keeps the last known state so only transitions degrade or recover sessions.
+pub(crate) struct LinkCtx {
+    pub device: &'static str,
+    pub monitor: LinkMonitor,
+    pub state: Rc<SockcanState>,
+    pub up: Cell<bool>,
 }
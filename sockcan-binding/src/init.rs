@@ -29,7 +29,7 @@ use sockdata::types::sockdata_register;
 /// Binding initialization callback invoked when the shared object is loaded by libafb.
 ///
 /// Responsibilities:
-/// - log and parse the JSON configuration object,
+/// - log, parse and validate the JSON configuration object,
 /// - register data converters (sockdata) with the AFB root API,
 /// - create the CAN-related API (name, permissions, metadata),
 /// - register verbs and events using the parsed configuration,
@@ -42,7 +42,8 @@ pub fn binding_init(rootv4: AfbApiV4, jconf: JsoncObj) -> Result<&'static AfbApi
     afb_log_msg!(Info, rootv4, "config:{}", jconf);
 
     // Parse all configuration fields from JSON into a strongly-typed configuration structure.
-    let config = parse_sockcan_config(&jconf);
+    // Unknown keys or mistyped values fail the binding initialization.
+    let config = parse_sockcan_config(&jconf)?;
 
     // Register data converters (sockdata) with the AFB root context so that CAN-related
     // payloads can be automatically mapped between wire representation and Rust structs.
@@ -33,8 +33,12 @@
 /// - implements the binding initialization logic (`init`),
 /// - defines verbs and their callbacks (`verbs`, `callbacks`) to manage CAN BCM
 ///   subscriptions and related operations.
+/// - keeps side-effect free helpers (`logic`) usable from benches and tests.
+/// - watches the CAN interface link state through rtnetlink (`netlink`).
 /// - depends on the `afb-sys` crate for low-level AFB bindings.
 mod callbacks;
 pub mod context;
 mod init;
+pub mod logic;
+mod netlink;
 mod verbs;
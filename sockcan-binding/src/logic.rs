@@ -0,0 +1,468 @@
+/*
+ * Copyright (C) 2015-2023 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * $RP_BEGIN_LICENSE$
+ * Commercial License Usage
+ *  Licensees holding valid commercial IoT.bzh licenses may use this file in
+ *  accordance with the commercial license agreement provided with the
+ *  Software or, alternatively, in accordance with the terms contained in
+ *  a written agreement between you and The IoT.bzh Company. For licensing terms
+ *  and conditions see https://www.iot.bzh/terms-conditions. For further
+ *  information use the contact form at https://www.iot.bzh/contact.
+ *
+ * GNU General Public License Usage
+ *  Alternatively, this file may be used under the terms of the GNU General
+ *  Public license version 3. This license is as published by the Free Software
+ *  Foundation and appearing in the file LICENSE.GPLv3 included in the packaging
+ *  of this file. Please review the following information to ensure the GNU
+ *  General Public License requirements will be met
+ *  https://www.gnu.org/licenses/gpl-3.0.html.
+ * $RP_END_LICENSE$
+ */
+
+//-------------------------------------
+// for benchmarking and testing purposes
+//-------------------------------------
+
+// Keep this module pure: no Afb types, no I/O.
+use sockdata::types::{SockcanAclRule, SockcanVerbClass, SubscribeFlag};
+
+/// Return true when adding `added` entries to `current` would exceed `max`.
+///
+/// A `max` of zero means "unlimited".
+pub fn quota_exceeded(current: usize, added: usize, max: usize) -> bool {
+    max != 0 && current.saturating_add(added) > max
+}
+
+/// Return true when the requested notification `rate` (ms) is acceptable.
+///
+/// A `min_rate` of zero disables the check; otherwise a rate of 0 ("every frame")
+/// or any rate below `min_rate` is rejected.
+pub fn rate_allowed(rate: u64, min_rate: u64) -> bool {
+    min_rate == 0 || rate >= min_rate
+}
+
+/// Merge the `(rate, watchdog)` demands of the callers sharing one RX filter.
+///
+/// The filter runs at the fastest demand: the smallest rate (0 forwards every frame) and
+/// the smallest non-zero watchdog, so a caller without watchdog does not disable the one
+/// asked by another caller. None once no demand is left.
+pub fn merge_rates(demands: impl IntoIterator<Item = (u64, u64)>) -> Option<(u64, u64)> {
+    demands.into_iter().fold(None, |merged, (rate, watchdog)| match merged {
+        None => Some((rate, watchdog)),
+        Some((merged_rate, merged_watchdog)) => {
+            let watchdog = match (merged_watchdog, watchdog) {
+                (0, watchdog) | (watchdog, 0) => watchdog,
+                (merged, watchdog) => merged.min(watchdog),
+            };
+            Some((merged_rate.min(rate), watchdog))
+        },
+    })
+}
+
+/// Subscription asked by one caller of a CAN ID shared in `canid` event mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanIdDemand {
+    pub rate: u64,
+    pub watchdog: u64,
+    pub flag: SubscribeFlag,
+    pub raw: bool,
+    pub mask: Option<Vec<u8>>,
+}
+
+/// Merge the subscriptions of the callers sharing one CAN ID event.
+///
+/// Every caller listens to the same event, so the filter forwards what the most demanding
+/// caller asked: timers as `merge_rates`, the `RAW` source when any caller reads it, `ALL`
+/// when any caller wants every frame, and for `NEW` the union of the masks (no mask when
+/// any caller has none). None once no demand is left.
+pub fn merge_demands<'a>(
+    demands: impl IntoIterator<Item = &'a CanIdDemand>,
+) -> Option<CanIdDemand> {
+    demands.into_iter().fold(None, |merged, demand| match merged {
+        None => Some(demand.clone()),
+        Some(merged) => {
+            let (rate, watchdog) =
+                merge_rates([(merged.rate, merged.watchdog), (demand.rate, demand.watchdog)])?;
+            let flag = match (&merged.flag, &demand.flag) {
+                (SubscribeFlag::NEW, SubscribeFlag::NEW) => SubscribeFlag::NEW,
+                _ => SubscribeFlag::ALL,
+            };
+            // Bytes beyond a mask compare fully, the union keeps every bit one caller watches.
+            let mask = match (merged.mask, &demand.mask) {
+                (Some(left), Some(right)) => Some(
+                    (0..left.len().max(right.len()))
+                        .map(|idx| {
+                            left.get(idx).copied().unwrap_or(0xFF)
+                                | right.get(idx).copied().unwrap_or(0xFF)
+                        })
+                        .collect(),
+                ),
+                _ => None,
+            };
+            Some(CanIdDemand { rate, watchdog, flag, raw: merged.raw || demand.raw, mask })
+        },
+    })
+}
+
+/// Fixed one-second window used to cap the number of events pushed per session.
+///
+/// Time is given by the caller in microseconds from any monotonic origin.
+#[derive(Debug, Default, Clone)]
+pub struct EventBudget {
+    window: u64,
+    count: u64,
+    window_dropped: u64,
+    dropped: u64,
+}
+
+impl EventBudget {
+    const WINDOW_US: u64 = 1_000_000;
+
+    pub fn new() -> Self {
+        EventBudget::default()
+    }
+
+    /// Account for one event at `now` and return true when it may be pushed.
+    ///
+    /// A `max` of zero means "unlimited" and always admits the event.
+    pub fn admit(&mut self, now: u64, max: u64) -> bool {
+        if max == 0 {
+            return true;
+        }
+
+        if now.saturating_sub(self.window) >= Self::WINDOW_US {
+            self.window = now;
+            self.count = 0;
+            self.window_dropped = 0;
+        }
+
+        if self.count < max {
+            self.count += 1;
+            true
+        } else {
+            self.window_dropped += 1;
+            self.dropped += 1;
+            false
+        }
+    }
+
+    /// Number of events dropped within the current window.
+    pub fn get_window_dropped(&self) -> u64 {
+        self.window_dropped
+    }
+
+    /// Total number of events dropped since the budget was created.
+    pub fn get_dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+/// Userspace change detection and throttling stage for one CAN ID.
+///
+/// Mirrors the `SubscribeParam` semantics: frames are forwarded at most every `rate` ms,
+/// and when a `mask` is set only if the masked payload changed, unless nothing was
+/// forwarded for `refresh` ms (the subscription watchdog), which forces an update.
+/// A throttled change stays pending and goes out with the next frame after `rate`.
+#[derive(Debug, Default, Clone)]
+pub struct ChangeFilter {
+    mask: Option<Vec<u8>>,
+    rate: u64,
+    refresh: u64,
+    last: Option<Vec<u8>>,
+    sent: u64,
+}
+
+impl ChangeFilter {
+    /// `mask` enables change detection (bytes beyond it compare fully), `rate` and
+    /// `refresh` are in ms, 0 disables them.
+    pub fn new(mask: Option<Vec<u8>>, rate: u64, refresh: u64) -> Self {
+        ChangeFilter { mask, rate: rate * 1000, refresh: refresh * 1000, last: None, sent: 0 }
+    }
+
+    /// Return true when the frame received at `now` (µs) with `data` must be forwarded.
+    pub fn admit(&mut self, now: u64, data: &[u8]) -> bool {
+        let masked: Vec<u8> = match &self.mask {
+            Some(mask) => data
+                .iter()
+                .enumerate()
+                .map(|(idx, byte)| byte & mask.get(idx).copied().unwrap_or(0xFF))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        if let Some(last) = &self.last {
+            let elapsed = now.saturating_sub(self.sent);
+            if elapsed < self.rate {
+                return false;
+            }
+            let unchanged = self.mask.is_some() && *last == masked;
+            if unchanged && (self.refresh == 0 || elapsed < self.refresh) {
+                return false;
+            }
+        }
+
+        self.last = Some(masked);
+        self.sent = now;
+        true
+    }
+}
+
+/// Build the change detection stage of a subscription, if it needs one.
+///
+/// - `RAW` source: `rate` throttles every frame, `NEW` adds change detection on the masked
+///   payload (full payload without `mask`) with a forced refresh every `watchdog` ms,
+/// - `BCM` source: the kernel throttles, a `mask` with `NEW` adds userspace change detection
+///   with the same forced refresh.
+pub fn change_filter(
+    raw: bool,
+    flag: SubscribeFlag,
+    mask: Option<&Vec<u8>>,
+    rate: u64,
+    watchdog: u64,
+) -> Option<ChangeFilter> {
+    match (raw, flag, mask) {
+        (true, SubscribeFlag::NEW, mask) => {
+            Some(ChangeFilter::new(Some(mask.cloned().unwrap_or_default()), rate, watchdog))
+        },
+        (true, SubscribeFlag::ALL, _) => Some(ChangeFilter::new(None, rate, 0)),
+        (false, SubscribeFlag::NEW, Some(mask)) => {
+            Some(ChangeFilter::new(Some(mask.clone()), 0, watchdog))
+        },
+        (false, _, _) => None,
+    }
+}
+
+/// Return the ACLs granting `class` on `canid` (or on the verb itself when `canid` is None).
+///
+/// - `None`: no rule covers `class`, only the API-level ACL applies,
+/// - `Some(empty)`: rules exist for `class` but none covers `canid`, access is denied,
+/// - `Some(acls)`: the caller must hold at least one of `acls`.
+pub fn required_acls(
+    rules: &[SockcanAclRule],
+    class: SockcanVerbClass,
+    canid: Option<u32>,
+) -> Option<Vec<&'static str>> {
+    if !rules.iter().any(|rule| rule.verbs.contains(&class)) {
+        return None;
+    }
+    Some(
+        rules
+            .iter()
+            .filter(|rule| rule.matches(class, canid))
+            .map(|rule| rule.acl)
+            .collect(),
+    )
+}
+
+/// Extended frame format flag carried in the CAN ID (`CAN_EFF_FLAG`).
+pub const CAN_EFF_FLAG: u32 = 0x8000_0000;
+
+/// Remote transmission request flag carried in the CAN ID (`CAN_RTR_FLAG`).
+pub const CAN_RTR_FLAG: u32 = 0x4000_0000;
+
+/// Largest payload of a classic CAN frame.
+pub const CAN_MAX_DLEN: usize = 8;
+
+/// Build the CAN ID and payload of a classic frame to transmit.
+///
+/// A remote transmission request carries no payload: `len` only sets its DLC, and the
+/// returned payload is zero-filled to that length so the frame gets the right DLC.
+pub fn tx_frame(canid: u32, data: &[u8], rtr: bool, len: u8) -> Result<(u32, Vec<u8>), String> {
+    let canid = canid & !CAN_RTR_FLAG;
+    let max = if canid & CAN_EFF_FLAG != 0 { 0x1FFF_FFFF } else { 0x7FF };
+    if canid & !CAN_EFF_FLAG > max {
+        return Err(format!("canid:{:#x} out of range", canid));
+    }
+
+    if rtr {
+        if !data.is_empty() {
+            return Err("remote transmission request cannot carry data".to_string());
+        }
+        if usize::from(len) > CAN_MAX_DLEN {
+            return Err(format!("rtr len:{} exceeds {} bytes", len, CAN_MAX_DLEN));
+        }
+        return Ok((canid | CAN_RTR_FLAG, vec![0; usize::from(len)]));
+    }
+
+    if data.len() > CAN_MAX_DLEN {
+        return Err(format!("data len:{} exceeds {} bytes", data.len(), CAN_MAX_DLEN));
+    }
+    Ok((canid, data.to_vec()))
+}
+
+/// How a `send` request is carried out by BCM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxPlan {
+    /// One-shot `TxSend`.
+    Once,
+    /// `TxSetup` sending `count` frames every `ival1` ms, then every `ival2` ms (0 stops).
+    Cyclic { ival1: u64, ival2: u64, count: u32 },
+    /// `TxDelete` of a running cycle.
+    Stop,
+}
+
+/// Turn the `period` (ms), `count` and `stop` fields of a `send` request into a BCM plan.
+///
+/// A count-limited cycle runs on `ival1` so the kernel reports `TxExpired` after its last
+/// frame; an endless cycle runs on `ival2`.
+pub fn tx_plan(period: u64, count: u32, stop: bool) -> Result<TxPlan, String> {
+    if stop {
+        return Ok(TxPlan::Stop);
+    }
+    match (period, count) {
+        (0, 0) => Ok(TxPlan::Once),
+        (0, _) => Err(format!("count:{} requires a period", count)),
+        (_, 0) => Ok(TxPlan::Cyclic { ival1: 0, ival2: period, count: 0 }),
+        (_, _) => Ok(TxPlan::Cyclic { ival1: period, ival2: 0, count }),
+    }
+}
+
+/// Estimated on-wire size in bits of a CAN frame carrying `len` data bytes.
+///
+/// Counts the fixed fields, worst-case stuff bits over SOF..CRC and the 3-bit interframe
+/// space: 47+8n (+stuffing) for standard frames, 67+8n (+stuffing) for extended frames.
+/// `remote` frames have no data field, `len` is only their requested DLC.
+/// CAN-FD frames are approximated at the nominal bitrate.
+pub fn can_frame_bits(extended: bool, remote: bool, len: u8) -> u64 {
+    let payload = if remote { 0 } else { 8 * u64::from(len) };
+    let stuffed = if extended { 54 + payload } else { 34 + payload };
+    stuffed + (stuffed - 1) / 4 + 13
+}
+
+/// Bus load accumulator fed with received frame sizes and sampled periodically.
+#[derive(Debug, Default, Clone)]
+pub struct BusLoad {
+    window: u64,
+    bits: u64,
+    frames: u64,
+}
+
+/// One bus load measurement over a sampling window.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BusLoadSample {
+    pub load: f64,
+    pub frames: u64,
+    pub bits: u64,
+    pub period: u64,
+}
+
+impl BusLoad {
+    pub fn new() -> Self {
+        BusLoad::default()
+    }
+
+    /// Account one received frame of `bits` on-wire bits.
+    pub fn add_frame(&mut self, bits: u64) {
+        self.bits += bits;
+        self.frames += 1;
+    }
+
+    /// Close the window at `now` (µs) and return its load in percent of `bitrate` (bit/s).
+    pub fn sample(&mut self, now: u64, bitrate: u64) -> BusLoadSample {
+        let period = now.saturating_sub(self.window);
+        let capacity = bitrate.saturating_mul(period) as f64 / 1_000_000.0;
+        let load = if capacity > 0.0 { self.bits as f64 * 100.0 / capacity } else { 0.0 };
+
+        let sample = BusLoadSample { load, frames: self.frames, bits: self.bits, period };
+        self.window = now;
+        self.bits = 0;
+        self.frames = 0;
+        sample
+    }
+}
+
+/// Link state change of one network interface, decoded from an rtnetlink message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkChange {
+    pub ifname: String,
+    pub up: bool,
+}
+
+// rtnetlink constants from <linux/rtnetlink.h>, <linux/if_link.h> and <net/if.h>.
+const NLMSG_HDRLEN: usize = 16;
+const IFINFO_LEN: usize = 16;
+const RTA_HDRLEN: usize = 4;
+const RTM_NEWLINK: u16 = 16;
+const RTM_DELLINK: u16 = 17;
+const IFLA_IFNAME: u16 = 3;
+const IFF_UP: u32 = 0x1;
+const IFF_RUNNING: u32 = 0x40;
+
+fn nl_align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn ne_u16(buf: &[u8], at: usize) -> u16 {
+    u16::from_ne_bytes([buf[at], buf[at + 1]])
+}
+
+fn ne_u32(buf: &[u8], at: usize) -> u32 {
+    u32::from_ne_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+}
+
+/// Decode the link changes carried by one rtnetlink datagram.
+///
+/// A link is reported up when it is both administratively up and running (carrier on);
+/// `RTM_DELLINK` always reports it down. Truncated or unrelated messages are skipped.
+pub fn parse_link_changes(buf: &[u8]) -> Vec<LinkChange> {
+    let mut changes = Vec::new();
+    let mut offset = 0;
+
+    while offset + NLMSG_HDRLEN <= buf.len() {
+        let msglen = ne_u32(buf, offset) as usize;
+        if msglen < NLMSG_HDRLEN || offset + msglen > buf.len() {
+            break;
+        }
+        let msgtype = ne_u16(buf, offset + 4);
+        let msg = &buf[offset..offset + msglen];
+        offset += nl_align(msglen);
+
+        if (msgtype != RTM_NEWLINK && msgtype != RTM_DELLINK)
+            || msg.len() < NLMSG_HDRLEN + IFINFO_LEN
+        {
+            continue;
+        }
+        let flags = ne_u32(msg, NLMSG_HDRLEN + 8);
+
+        // Walk the attributes looking for the interface name.
+        let mut ifname = None;
+        let mut attr = NLMSG_HDRLEN + IFINFO_LEN;
+        while attr + RTA_HDRLEN <= msg.len() {
+            let attrlen = ne_u16(msg, attr) as usize;
+            if attrlen < RTA_HDRLEN || attr + attrlen > msg.len() {
+                break;
+            }
+            if ne_u16(msg, attr + 2) == IFLA_IFNAME {
+                let name = &msg[attr + RTA_HDRLEN..attr + attrlen];
+                let name = name.split(|byte| *byte == 0).next().unwrap_or_default();
+                ifname = Some(String::from_utf8_lossy(name).into_owned());
+            }
+            attr += nl_align(attrlen);
+        }
+
+        if let Some(ifname) = ifname {
+            let up =
+                msgtype == RTM_NEWLINK && flags & (IFF_UP | IFF_RUNNING) == IFF_UP | IFF_RUNNING;
+            changes.push(LinkChange { ifname, up });
+        }
+    }
+    changes
+}
+
+/// Return the CAN IDs of the RX filters listed in one `/proc/net/can-bcm/<inode>` file.
+///
+/// The kernel prints one `rx_op: <canid in hex> <ifname> ...` line per RX operation
+/// after the `>>> socket ... <<<` header; other lines (TX operations) are ignored.
+pub fn parse_bcm_proc(text: &str) -> Vec<u32> {
+    let mut canids: Vec<u32> = text
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix("rx_op:"))
+        .filter_map(|line| line.split_whitespace().next())
+        .filter_map(|canid| u32::from_str_radix(canid, 16).ok())
+        .collect();
+    canids.sort_unstable();
+    canids.dedup();
+    canids
+}
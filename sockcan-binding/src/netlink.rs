@@ -0,0 +1,106 @@
+/*
+ * Copyright (C) 2015-2023 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * $RP_BEGIN_LICENSE$
+ * Commercial License Usage
+ *  Licensees holding valid commercial IoT.bzh licenses may use this file in
+ *  accordance with the commercial license agreement provided with the
+ *  Software or, alternatively, in accordance with the terms contained in
+ *  a written agreement between you and The IoT.bzh Company. For licensing terms
+ *  and conditions see https://www.iot.bzh/terms-conditions. For further
+ *  information use the contact form at https://www.iot.bzh/contact.
+ *
+ * GNU General Public License Usage
+ *  Alternatively, this file may be used under the terms of the GNU General
+ *  Public license version 3. This license is as published by the Free Software
+ *  Foundation and appearing in the file LICENSE.GPLv3 included in the packaging
+ *  of this file. Please review the following information to ensure the GNU
+ *  General Public License requirements will be met
+ *  https://www.gnu.org/licenses/gpl-3.0.html.
+ * $RP_END_LICENSE$
+ */
+
+use crate::logic::{self, LinkChange};
+use afbv4::prelude::*;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+// Multicast group notifying interface creation, removal and flag changes.
+const RTMGRP_LINK: u32 = 0x1;
+
+/// Non-blocking rtnetlink socket subscribed to link notifications.
+///
+/// The kernel reports every interface flag change (up/down, carrier) on this socket,
+/// which lets the binding notice when the CAN interface goes down and comes back.
+pub(crate) struct LinkMonitor {
+    fd: OwnedFd,
+}
+
+impl LinkMonitor {
+    /// Open a `NETLINK_ROUTE` socket bound to the `RTMGRP_LINK` multicast group.
+    pub(crate) fn open() -> Result<Self, AfbError> {
+        let fail = |info: io::Error| AfbError::new("fail-netlink-open", 0, info.to_string());
+
+        // SAFETY: plain socket(2) call, the returned descriptor is owned right away.
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK,
+                libc::NETLINK_ROUTE,
+            )
+        };
+        if fd < 0 {
+            return Err(fail(io::Error::last_os_error()));
+        }
+        // SAFETY: `fd` is a freshly created descriptor nobody else owns.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // SAFETY: sockaddr_nl is plain old data, all-zero is a valid value.
+        let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = RTMGRP_LINK;
+
+        // SAFETY: `addr` outlives the call and its size is passed along.
+        let status = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if status < 0 {
+            return Err(fail(io::Error::last_os_error()));
+        }
+
+        Ok(LinkMonitor { fd })
+    }
+
+    /// Raw descriptor to register in the AFB main loop.
+    pub(crate) fn as_rawfd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+
+    /// Drain every pending notification and return the decoded link changes.
+    pub(crate) fn read_changes(&self) -> Vec<LinkChange> {
+        let mut changes = Vec::new();
+        let mut buffer = [0u8; 8192];
+        loop {
+            // SAFETY: the kernel writes at most `buffer.len()` bytes into `buffer`.
+            let count = unsafe {
+                libc::recv(
+                    self.fd.as_raw_fd(),
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    buffer.len(),
+                    0,
+                )
+            };
+            if count <= 0 {
+                // EAGAIN once drained; ENOBUFS after an overrun simply drops stale states.
+                break;
+            }
+            changes.extend(logic::parse_link_changes(&buffer[..count as usize]));
+        }
+        changes
+    }
+}
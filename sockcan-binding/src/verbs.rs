@@ -21,10 +21,21 @@
  * $RP_END_LICENSE$
  */
 
-use crate::callbacks::{check_cb, close_cb, subscribe_cb, unsubscribe_cb};
-use crate::context::{CheckCtx, SubVerbCtx};
+use crate::callbacks::{
+    busload_can_cb, busload_cb, busload_timer_cb, check_cb, close_cb, link_state_cb, list_cb,
+    metrics_cb, metrics_timer_cb, rtr_reply_cb, send_cb, subscribe_cb, unsubscribe_cb,
+};
+use crate::context::{
+    BusLoadCtx, BusLoadVerbCtx, CheckCtx, LinkCtx, MetricsCtx, SockcanState, StateCtx, SubVerbCtx,
+};
+use crate::logic::BusLoad;
+use crate::netlink::LinkMonitor;
 use afbv4::prelude::*;
-use sockdata::types::SockcanBindingConfig;
+use sockcan::prelude::{CanTimeStamp, SockCanHandle};
+use sockdata::types::{CanBusLoad, SockcanBindingConfig};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Instant;
 
 // ============ Register Canids ===============
 
@@ -34,15 +45,45 @@ use sockdata::types::SockcanBindingConfig;
 /// attaches verb-specific context:
 /// - `subscribe`: create/attach a BCM session and install RX filters for CAN IDs,
 /// - `unsubscribe`: remove BCM filters for CAN IDs on the current session,
+/// - `send`: transmit one CAN frame or remote transmission request, once or cyclically,
+/// - `rtr_reply`: install or remove a kernel-side RTR auto-reply,
 /// - `check`: health-check that BCM is available on the target CAN device,
-/// - `close`: explicitly close the BCM session and release related resources.
+/// - `close`: explicitly close the BCM session and release related resources,
+/// - `list`: return the active sessions and subscriptions, optionally checked against the kernel,
+/// - `metrics`: return runtime counters, optionally as a periodic `metrics` event,
+/// - `busload`: return the estimated bus load, optionally as a periodic `busload` event.
+///
+/// It also starts the rtnetlink link monitor recovering sessions after a link down/up.
 ///
 /// The `config` parameter provides binding-level configuration:
 /// - `api_uid`: logical API identifier,
 /// - `event_uid`: event name used for BCM notifications,
-/// - `can_device`: CAN interface name (e.g. "can0").
+/// - `event_mode`: one event per session, or one `{event_uid}/0x{canid:x}` event per CAN ID,
+/// - `can_device`: CAN interface name (e.g. "can0"),
+/// - `limits`: per-client quotas enforced by `subscribe`,
+/// - `permissions`: per verb and per CAN ID range ACLs checked at call time,
+/// - `metrics_period`: period of the `metrics` event (0 disables it),
+/// - `bitrate`/`busload_period`/`busload_threshold`: bus load monitor settings.
 ///
 pub fn register(api: &mut AfbApi, config: &SockcanBindingConfig) -> Result<(), AfbError> {
+    // Binding-wide state shared by verbs that need to see every session.
+    let state = SockcanState::new(config);
+
+    // Watch the CAN interface link through rtnetlink: sessions are degraded while it is
+    // down, then their BCM sockets are reopened and filters reinstalled when it comes back.
+    let monitor = LinkMonitor::open()?;
+    AfbEvtFd::new("link")
+        .set_fd(monitor.as_rawfd())
+        .set_events(AfbEvtFdPoll::IN)
+        .set_callback(link_state_cb)
+        .set_context(LinkCtx {
+            device: config.can_device,
+            monitor,
+            state: state.clone(),
+            up: Cell::new(true),
+        })
+        .start()?;
+
     // Verb: subscribe
     //
     // Subscribes the caller to a set of CAN IDs via BCM, using optional
@@ -56,10 +97,14 @@ pub fn register(api: &mut AfbApi, config: &SockcanBindingConfig) -> Result<(), A
             uid: config.api_uid,
             sockevt: config.event_uid,
             candev: config.can_device,
+            state: state.clone(),
         })
         .set_info("Subscribe a canid array")
-        .set_usage("{'canids':[x,y,...,z],['rate':xx_ms],['watchdog':xx_ms],['flag':'ALL|NEW']}")
+        .set_usage(
+            "{'canids':[x,y,...,z],['rate':xx_ms],['watchdog':xx_ms],['flag':'ALL|NEW'],['source':'BCM|RAW'],['mask':[x,y,...]]}",
+        )
         .add_sample("{'canids':[266,257,599],'rate':250,'watchdog':1000,'flag':'ALL'}")?
+        .add_sample("{'canids':[257],'rate':100,'watchdog':1000,'flag':'NEW','source':'RAW','mask':[255,255,0,0]}")?
         .finalize()?;
     api.add_verb(subscribe);
 
@@ -69,19 +114,61 @@ pub fn register(api: &mut AfbApi, config: &SockcanBindingConfig) -> Result<(), A
     // on the current session.
     let unsubscribe = AfbVerb::new("unsubscribe")
         .set_callback(unsubscribe_cb)
+        .set_context(StateCtx { state: state.clone() })
         .set_info("Unsubscribe socket BCM cannids from session")
         .set_usage("{'canids':[x,y,...,z]}")
         .add_sample("{'canids':[266,257,599]}")?
         .finalize()?;
     api.add_verb(unsubscribe);
 
+    // Verb: send
+    //
+    // Transmits one classic CAN frame, or a remote transmission request with `rtr`,
+    // on the caller BCM socket, once or cyclically with `period` and `count`.
+    let send = AfbVerb::new("send")
+        .set_callback(send_cb)
+        .set_context(SubVerbCtx {
+            uid: config.api_uid,
+            sockevt: config.event_uid,
+            candev: config.can_device,
+            state: state.clone(),
+        })
+        .set_info("Send a CAN frame or remote transmission request")
+        .set_usage(
+            "{'canid':x,['data':[x,y,...]],['rtr':true,'len':x],['period':xx_ms,['count':n]],['stop':true]}",
+        )
+        .add_sample("{'canid':257,'data':[1,2,3,4]}")?
+        .add_sample("{'canid':257,'rtr':true,'len':8}")?
+        .add_sample("{'canid':257,'data':[1,2,3,4],'period':100,'count':10}")?
+        .finalize()?;
+    api.add_verb(send);
+
+    // Verb: rtr_reply
+    //
+    // Stores a reply frame the kernel sends back whenever a remote transmission
+    // request is received for the CAN ID, to simulate ECUs polled through RTR.
+    let rtr_reply = AfbVerb::new("rtr_reply")
+        .set_callback(rtr_reply_cb)
+        .set_context(SubVerbCtx {
+            uid: config.api_uid,
+            sockevt: config.event_uid,
+            candev: config.can_device,
+            state: state.clone(),
+        })
+        .set_info("Install or remove a RTR auto-reply")
+        .set_usage("{'canid':x,'data':[x,y,...],['delete':true]}")
+        .add_sample("{'canid':257,'data':[1,2,3,4]}")?
+        .add_sample("{'canid':257,'delete':true}")?
+        .finalize()?;
+    api.add_verb(rtr_reply);
+
     // Verb: check
     //
     // Performs a health check to ensure that a BCM socket can be opened on
     // the configured CAN device. It does not change any persistent state.
     let check = AfbVerb::new("check")
         .set_callback(check_cb)
-        .set_context(CheckCtx { candev: config.can_device })
+        .set_context(CheckCtx { candev: config.can_device, state: state.clone() })
         .set_info("Check socket BCM is available")
         .set_usage("no-input")
         .finalize()?;
@@ -93,10 +180,113 @@ pub fn register(api: &mut AfbApi, config: &SockcanBindingConfig) -> Result<(), A
     // unreferences the AFB event and closes the underlying socket.
     let close = AfbVerb::new("close")
         .set_callback(close_cb)
+        .set_context(StateCtx { state: state.clone() })
         .set_info("Close socket BCM session")
         .set_usage("no-input")
         .finalize()?;
     api.add_verb(close);
 
+    // Verb: list
+    //
+    // Returns the live sessions with their subscribed CAN IDs and socket state, optionally
+    // cross-checked against the RX filters the kernel lists in `/proc/net/can-bcm`.
+    let list = AfbVerb::new("list")
+        .set_callback(list_cb)
+        .set_context(StateCtx { state: state.clone() })
+        .set_info("List active sessions and subscriptions")
+        .set_usage("{['session':true|false],['kernel':true|false]}")
+        .add_sample("{'kernel':true}")?
+        .finalize()?;
+    api.add_verb(list);
+
+    // Verb: metrics
+    //
+    // Returns frame/event/error counters collected by the BCM callbacks for every live
+    // session. When `metrics_period` is set, a timer also pushes them on a `metrics` event.
+    let metrics_event = if config.metrics_period > 0 {
+        let event = AfbEvent::new("metrics").finalize()?;
+        api.add_event(event);
+        Some(event)
+    } else {
+        None
+    };
+
+    if metrics_event.is_some() {
+        AfbTimer::new("metrics")
+            .set_period(config.metrics_period as u32)
+            .set_decount(0)
+            .set_callback(metrics_timer_cb)
+            .set_context(MetricsCtx { state: state.clone(), event: metrics_event })
+            .start()?;
+    }
+
+    let metrics = AfbVerb::new("metrics")
+        .set_callback(metrics_cb)
+        .set_context(MetricsCtx { state: state.clone(), event: metrics_event })
+        .set_info("Return sockcan runtime counters")
+        .set_usage("{['subscribe':true|false]}")
+        .add_sample("{'subscribe':true}")?
+        .finalize()?;
+    api.add_verb(metrics);
+
+    // Verb: busload
+    //
+    // When `busload_period` is set, a raw-mode socket monitors every frame on the
+    // interface and a timer turns the accumulated frame sizes into a load percentage.
+    let monitor = if config.busload_period > 0 {
+        let sockfd = match SockCanHandle::open_raw(config.can_device, CanTimeStamp::CLASSIC) {
+            Ok(handle) => handle,
+            Err(canerr) => {
+                return Err(AfbError::new("fail-sockraw-open", 0, canerr.to_string()));
+            },
+        };
+
+        let event = AfbEvent::new("busload").finalize()?;
+        api.add_event(event);
+
+        let monitor = Rc::new(BusLoadCtx {
+            device: config.can_device,
+            bitrate: config.bitrate,
+            threshold: config.busload_threshold,
+            sockfd,
+            event,
+            load: RefCell::new(BusLoad::new()),
+            last: RefCell::new(CanBusLoad {
+                device: config.can_device.to_string(),
+                bitrate: config.bitrate,
+                threshold: config.busload_threshold,
+                ..CanBusLoad::default()
+            }),
+            started: Instant::now(),
+        });
+
+        AfbEvtFd::new("busload")
+            .set_fd(monitor.sockfd.as_rawfd())
+            .set_events(AfbEvtFdPoll::IN)
+            .set_callback(busload_can_cb)
+            .set_context(BusLoadVerbCtx { monitor: Some(monitor.clone()), state: state.clone() })
+            .start()?;
+
+        AfbTimer::new("busload")
+            .set_period(config.busload_period as u32)
+            .set_decount(0)
+            .set_callback(busload_timer_cb)
+            .set_context(BusLoadVerbCtx { monitor: Some(monitor.clone()), state: state.clone() })
+            .start()?;
+
+        Some(monitor)
+    } else {
+        None
+    };
+
+    let busload = AfbVerb::new("busload")
+        .set_callback(busload_cb)
+        .set_context(BusLoadVerbCtx { monitor, state: state.clone() })
+        .set_info("Return estimated CAN bus load")
+        .set_usage("{['subscribe':true|false]}")
+        .add_sample("{'subscribe':true}")?
+        .finalize()?;
+    api.add_verb(busload);
+
     Ok(())
 }
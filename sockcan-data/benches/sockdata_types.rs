@@ -75,4 +75,88 @@ mod tests {
 
         assert_eq!(v1, v2);
     }
+
+    #[test]
+    fn parse_canid_range_accepts_ids_and_ranges() {
+        use sockdata::types::parse_canid_range;
+
+        assert_eq!(parse_canid_range("0x7DF"), Some((0x7DF, 0x7DF)));
+        assert_eq!(parse_canid_range("2015"), Some((2015, 2015)));
+        assert_eq!(parse_canid_range("0x100-0x3FF"), Some((0x100, 0x3FF)));
+        assert_eq!(parse_canid_range("0x3FF-0x100"), None);
+        assert_eq!(parse_canid_range("hmi"), None);
+    }
+
+    #[test]
+    fn parse_sockcan_config_is_strict() {
+        use afbv4::prelude::JsoncObj;
+        use sockdata::types::{parse_dbc_config, parse_sockcan_config, SockcanAlarmSeverity};
+
+        let jconf =
+            JsoncObj::parse(r#"{"uid":"bms","sock_api":"sockbcm","sock_evt":"sockcan"}"#).unwrap();
+        let config = parse_dbc_config(&jconf).unwrap();
+        assert_eq!(config.api_uid, "bms");
+        assert_eq!(config.event_uid, "sockcan");
+        assert_eq!(config.can_device, "vcan0");
+        assert_eq!(config.event_mode, sockdata::types::SockcanEventMode::Session);
+        assert_eq!(config.dbc, None);
+
+        let jconf =
+            JsoncObj::parse(r#"{"event_mode":"canid","sock_api":"sockcan","dbc":"BMS.dbc"}"#)
+                .unwrap();
+        let config = parse_dbc_config(&jconf).unwrap();
+        assert_eq!(config.event_mode, sockdata::types::SockcanEventMode::Canid);
+        assert_eq!(config.dbc, Some("BMS.dbc"));
+
+        // Typo in a key and wrong value type are both rejected.
+        let jconf = JsoncObj::parse(r#"{"device":"can0"}"#).unwrap();
+        assert!(parse_sockcan_config(&jconf).is_err());
+        let jconf = JsoncObj::parse(r#"{"limits":{"max_canids":"8"}}"#).unwrap();
+        assert!(parse_sockcan_config(&jconf).is_err());
+        let jconf = JsoncObj::parse(r#"{"event_mode":"signal"}"#).unwrap();
+        assert!(parse_sockcan_config(&jconf).is_err());
+
+        let jconf =
+            JsoncObj::parse(r#"{"sock_api":"sockcan","transmit":{"BmsStatus":{"period":100}}}"#)
+                .unwrap();
+        let config = parse_dbc_config(&jconf).unwrap();
+        assert_eq!(config.transmit["BmsStatus"].period, Some(100));
+        let jconf =
+            JsoncObj::parse(r#"{"sock_api":"sockcan","transmit":{"BmsStatus":{"period":0}}}"#)
+                .unwrap();
+        assert!(parse_dbc_config(&jconf).is_err());
+
+        let jconf = JsoncObj::parse(
+            r#"{"sock_api":"sockcan","alarms":[{"uid":"hot","when":"BmsStatus/PackTemp > 55","severity":"critical"}]}"#,
+        )
+        .unwrap();
+        let config = parse_dbc_config(&jconf).unwrap();
+        assert_eq!(config.alarms[0].severity, SockcanAlarmSeverity::Critical);
+        let jconf = JsoncObj::parse(
+            r#"{"sock_api":"sockcan","alarms":[{"uid":"a","when":"X/Y > 1"},{"uid":"a","when":"X/Y < 0"}]}"#,
+        )
+        .unwrap();
+        assert!(parse_dbc_config(&jconf).is_err());
+        let jconf = JsoncObj::parse(
+            r#"{"sock_api":"sockcan","virtuals":[{"uid":"power","expr":"A/B","average":0}]}"#,
+        )
+        .unwrap();
+        assert!(parse_dbc_config(&jconf).is_err());
+        let jconf =
+            JsoncObj::parse(r#"{"sock_api":"sockcan","history":[{"signals":["BmsStatus/*"]}]}"#)
+                .unwrap();
+        assert!(parse_dbc_config(&jconf).is_err());
+
+        // DBC-only sections are rejected by the sockcan binding, and DBC bindings need sock_api.
+        // Sockcan configs keep loading with their legacy sock_api key.
+        let jconf = JsoncObj::parse(r#"{"uid":"bms","sock_api":"sockbcm"}"#).unwrap();
+        assert!(parse_sockcan_config(&jconf).is_ok());
+        let jconf = JsoncObj::parse(r#"{"sock_api":"sockbcm","dbc":"BMS.dbc"}"#).unwrap();
+        assert!(parse_sockcan_config(&jconf).is_err());
+        let jconf = JsoncObj::parse(r#"{"messages":{}}"#).unwrap();
+        assert!(parse_sockcan_config(&jconf).is_err());
+        assert!(parse_dbc_config(&jconf).is_err());
+        let config = parse_sockcan_config(&JsoncObj::parse(r#"{"dev":"can0"}"#).unwrap()).unwrap();
+        assert_eq!(config.sock_api, "");
+    }
 }
@@ -24,6 +24,8 @@
 // Import libafb dependencies and serde for JSON (de)serialization.
 use afbv4::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
 
 use sockcan::prelude::{CanBcmOpCode, CanDataStatus, CanDbcType};
 
@@ -72,6 +74,7 @@ AfbDataConverter!(bcm_data, CanBcmData);
 /// High-level representation of a BCM CAN frame that travels through the AFB API.
 ///
 /// This structure is used as the payload of BCM-related events and verb replies.
+/// `rtr` marks a remote transmission request, whose `data` carries no payload.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CanBcmData {
     pub canid: u32,
@@ -79,6 +82,66 @@ pub struct CanBcmData {
     pub stamp: u64,
     pub opcode: CanBcmOpCode,
     pub data: Vec<u8>,
+    #[serde(default)]
+    pub rtr: bool,
+}
+
+AfbDataConverter!(bcm_timeout, CanBcmTimeout);
+
+/// Watchdog notification: a subscribed CAN ID was not received within its watchdog.
+///
+/// Fields:
+/// - `canid`: silent CAN identifier,
+/// - `watchdog`: configured watchdog in ms,
+/// - `silent`: time in ms since the last frame (or the subscription) for this CAN ID.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CanBcmTimeout {
+    pub canid: u32,
+    pub watchdog: u64,
+    pub silent: u64,
+}
+
+AfbDataConverter!(bcm_alive, CanBcmAlive);
+
+/// Notification sent with the first frame received after a `CanBcmTimeout`.
+///
+/// Fields:
+/// - `canid`: CAN identifier received again,
+/// - `watchdog`: configured watchdog in ms,
+/// - `silent`: how long in ms the CAN ID had been silent.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CanBcmAlive {
+    pub canid: u32,
+    pub watchdog: u64,
+    pub silent: u64,
+}
+
+/// CAN interface link state as seen through rtnetlink.
+///
+/// `DOWN` – interface is down (bus-off restart, driver reload, `ip link set down`).
+///
+/// `UP` – interface is up again and BCM filters were reinstalled.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum CanLinkStatus {
+    DOWN,
+    UP,
+}
+
+AfbDataConverter!(bcm_link, CanBcmLink);
+
+/// Link state notification pushed on every session event of the interface.
+///
+/// Fields:
+/// - `device`: CAN interface name,
+/// - `status`: new link state, sessions are degraded while `DOWN`,
+/// - `restored`: CAN IDs whose `RxSetup` filter was reinstalled on `UP`,
+/// - `failed`: CAN IDs whose filter could not be reinstalled on `UP`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CanBcmLink {
+    pub device: String,
+    pub status: CanLinkStatus,
+    pub restored: Vec<u32>,
+    pub failed: Vec<u32>,
 }
 
 AfbDataConverter!(bcm_msg, DataBcmMsg);
@@ -100,19 +163,179 @@ AfbDataConverter!(bcm_sig, DataBcmSig);
 /// - `name`: DBC signal name,
 /// - `stamp`: timestamp of the last update,
 /// - `status`: data status (updated, timeout, invalid, etc.),
-/// - `value`: decoded value with the correct DBC type.
+/// - `value`: decoded value with the correct DBC type,
+/// - `label`: DBC value table (`VAL_`) label of `value`, if any.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DataBcmSig {
     pub name: String,
     pub stamp: u64,
     pub status: CanDataStatus,
     pub value: CanDbcType,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+AfbDataConverter!(bcm_sig_info, DataBcmSigInfo);
+
+/// DBC definition of a signal returned by the `info` action.
+///
+/// Fields:
+/// - `name` / `verb`: DBC signal name and the verb exposing it,
+/// - `start` / `size`: bit position and length in the frame,
+/// - `byte_order`: `"intel"` (little endian) or `"motorola"` (big endian),
+/// - `signed`, `float`: raw value encoding,
+/// - `factor`, `offset`: physical = raw * factor + offset,
+/// - `min`, `max`, `unit`: physical range and unit,
+/// - `multiplexor`: true for the multiplexer selector of the message,
+/// - `multiplexed`: multiplexer value carrying the signal, None when always present,
+/// - `values`: value table (raw value, label),
+/// - `comment`: DBC comment.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DataBcmSigInfo {
+    pub name: String,
+    pub verb: String,
+    pub start: u16,
+    pub size: u16,
+    pub byte_order: String,
+    pub signed: bool,
+    pub float: bool,
+    pub factor: f64,
+    pub offset: f64,
+    pub min: f64,
+    pub max: f64,
+    pub unit: String,
+    pub multiplexor: bool,
+    pub multiplexed: Option<u64>,
+    pub values: Vec<(i64, String)>,
+    pub comment: Option<String>,
+}
+
+AfbDataConverter!(bcm_msg_info, DataBcmMsgInfo);
+
+/// DBC definition of a message returned by the `info` action and the `catalog` verb.
+///
+/// `cycle` is the `GenMsgCycleTime` period in ms, None for event driven messages.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DataBcmMsgInfo {
+    pub name: String,
+    pub verb: String,
+    pub canid: u32,
+    pub dlc: u8,
+    pub sender: String,
+    pub cycle: Option<u64>,
+    pub comment: Option<String>,
+    pub signals: Vec<DataBcmSigInfo>,
+}
+
+AfbDataConverter!(bcm_catalog, DataBcmCatalog);
+
+/// Every message of a DBC pool, returned by the `catalog` verb.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DataBcmCatalog {
+    pub messages: Vec<DataBcmMsgInfo>,
+}
+
+AfbDataConverter!(bcm_sig_set, DataBcmSigSet);
+
+/// Aggregated event of a `subscribe_set` signal set.
+///
+/// `signals` only holds the signals updated since the previous push (all of them with
+/// the `ALL` flag), each named `message/signal`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DataBcmSigSet {
+    pub uid: String,
+    pub stamp: u64,
+    pub signals: Vec<DataBcmSig>,
+}
+
+/// State change reported by a `DataBcmAlarm`.
+///
+/// `RAISED` – the rule condition held for its whole delay, the alarm is latched.
+///
+/// `CLEARED` – the condition no longer holds; a latched alarm stays listed until acked.
+///
+/// `ACKED` – a client acknowledged the alarm.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum CanAlarmStatus {
+    RAISED,
+    CLEARED,
+    ACKED,
+}
+
+AfbDataConverter!(bcm_alarm, DataBcmAlarm);
+
+/// Alarm notification pushed on the `alarms` event and listed by the `alarms` verb.
+///
+/// Fields:
+/// - `uid` / `severity` / `info`: alarm rule from the binding config,
+/// - `signal`: watched signal (`message/signal`),
+/// - `status`: last state change (see `CanAlarmStatus`),
+/// - `active`: the condition currently holds,
+/// - `latched`: raised and not acknowledged yet,
+/// - `value` / `stamp`: signal value and timestamp of the last state change.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DataBcmAlarm {
+    pub uid: String,
+    pub signal: String,
+    pub severity: SockcanAlarmSeverity,
+    pub status: CanAlarmStatus,
+    pub active: bool,
+    pub latched: bool,
+    pub value: f64,
+    pub stamp: u64,
+    pub info: String,
+}
+
+AfbDataConverter!(bcm_alarms, DataBcmAlarms);
+
+/// Alarms returned by the `alarms` verb.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DataBcmAlarms {
+    pub alarms: Vec<DataBcmAlarm>,
+}
+
+/// One signal value kept in the history buffer.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DataBcmSample {
+    pub stamp: u64,
+    pub value: f64,
+}
+
+AfbDataConverter!(bcm_history, DataBcmHistory);
+
+/// Samples returned by the signal `history` action, oldest first.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DataBcmHistory {
+    pub name: String,
+    pub samples: Vec<DataBcmSample>,
+}
+
+AfbDataConverter!(bcm_sig_stats, DataBcmSigStats);
+
+/// Statistics of a signal returned by the `stats` action and pushed on its stats event.
+///
+/// Fields:
+/// - `name`: signal name (`message/signal` on the stats event),
+/// - `stamp`: timestamp of the newest sample, `window`: window length in ms,
+/// - `count` / `min` / `max` / `mean` / `stddev`: aggregates of the samples in the window,
+/// - `rate`: received samples per second over the window.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DataBcmSigStats {
+    pub name: String,
+    pub stamp: u64,
+    pub window: u64,
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub stddev: f64,
+    pub rate: f64,
 }
 
 impl CanBcmData {
     /// Construct a new BCM data record from low-level CAN parameters.
     pub fn new(canid: u32, opcode: CanBcmOpCode, stamp: u64, data: Vec<u8>, len: u8) -> Self {
-        CanBcmData { canid, len, stamp, opcode, data }
+        CanBcmData { canid, len, stamp, opcode, data, rtr: false }
     }
 
     /// Return the DLC (data length) of the CAN frame.
@@ -139,6 +362,11 @@ impl CanBcmData {
     pub fn get_data(&self) -> &Vec<u8> {
         &self.data
     }
+
+    /// Return true when this frame is a remote transmission request.
+    pub fn is_rtr(&self) -> bool {
+        self.rtr
+    }
 }
 
 AfbDataConverter!(subscribe_flag, SubscribeFlag);
@@ -154,6 +382,21 @@ pub enum SubscribeFlag {
     ALL,
 }
 
+AfbDataConverter!(subscribe_source, SubscribeSource);
+
+/// Socket delivering the frames of a subscription.
+///
+/// `BCM` – kernel broadcast manager filters, with kernel-side throttling and watchdog.
+///
+/// `RAW` – raw socket seeing every frame (loopback, replay, gateway traffic included),
+/// with change detection and throttling done in userspace.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum SubscribeSource {
+    #[default]
+    BCM,
+    RAW,
+}
+
 AfbDataConverter!(subscribe_param, SubscribeParam);
 
 /// Parameters used when subscribing to BCM CAN IDs.
@@ -162,7 +405,10 @@ AfbDataConverter!(subscribe_param, SubscribeParam);
 /// - `rate`: minimum interval between notifications (time unit is binding-specific),
 /// - `watchdog`: maximum allowed idle time before a timeout is reported,
 /// - `canids`: list of CAN IDs to subscribe to,
-/// - `flag`: controls which updates are delivered (new-only vs all).
+/// - `flag`: controls which updates are delivered (new-only vs all),
+/// - `source`: socket delivering the frames (see `SubscribeSource`),
+/// - `mask`: per-byte payload mask used by userspace change detection (missing bytes
+///   compare fully); with `flag` NEW it enables change detection on the BCM source too.
 ///
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SubscribeParam {
@@ -170,11 +416,37 @@ pub struct SubscribeParam {
     watchdog: u64,
     canids: Vec<u32>,
     flag: SubscribeFlag,
+    #[serde(default)]
+    source: SubscribeSource,
+    #[serde(default)]
+    mask: Option<Vec<u8>>,
 }
 impl SubscribeParam {
     /// Create a new subscription parameter set for the given CAN IDs and timer configuration.
     pub fn new(canids: Vec<u32>, watchdog: u64, rate: u64, flag: SubscribeFlag) -> Self {
-        SubscribeParam { rate, watchdog, canids, flag }
+        SubscribeParam { rate, watchdog, canids, flag, source: SubscribeSource::BCM, mask: None }
+    }
+
+    /// Select the frame source and the change detection mask of this subscription.
+    pub fn set_source(mut self, source: SubscribeSource, mask: Option<Vec<u8>>) -> Self {
+        self.source = source;
+        self.mask = mask;
+        self
+    }
+
+    /// Return the notification policy (new-only vs all).
+    pub fn get_flag(&self) -> SubscribeFlag {
+        self.flag.clone()
+    }
+
+    /// Return the socket delivering the frames of this subscription.
+    pub fn get_source(&self) -> SubscribeSource {
+        self.source
+    }
+
+    /// Return the change detection mask, if any.
+    pub fn get_mask(&self) -> Option<&Vec<u8>> {
+        self.mask.as_ref()
     }
 
     /// Create a new subscription parameter set for the given CAN IDs and timer configuration.
@@ -215,6 +487,231 @@ impl UnSubscribeParam {
     }
 }
 
+AfbDataConverter!(send_param, SendParam);
+
+/// Parameters of the `send` verb transmitting one classic CAN frame.
+///
+/// Fields:
+/// - `canid`: CAN identifier, `CAN_EFF_FLAG` selects the extended format,
+/// - `data`: payload (up to 8 bytes), must be empty for a remote transmission request,
+/// - `rtr`: send a remote transmission request instead of a data frame,
+/// - `len`: DLC requested by a remote transmission request (defaults to 0),
+/// - `period`: cyclic transmission interval in ms, 0 sends the frame once,
+/// - `count`: number of cyclic frames before the cycle expires, 0 repeats forever,
+/// - `stop`: stop the cyclic transmission of `canid`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SendParam {
+    pub canid: u32,
+    #[serde(default)]
+    pub data: Vec<u8>,
+    #[serde(default)]
+    pub rtr: bool,
+    #[serde(default)]
+    pub len: u8,
+    #[serde(default)]
+    pub period: u64,
+    #[serde(default)]
+    pub count: u32,
+    #[serde(default)]
+    pub stop: bool,
+}
+
+/// Transmission state reported by `CanBcmTxStatus`.
+///
+/// Statuses follow the BCM socket, not the bus: a frame is queued to the interface, its
+/// transmission on the wire is not confirmed.
+///
+/// `QUEUED` – one-shot frame queued to the interface.
+///
+/// `STARTED` – cyclic transmission installed, first frame queued right away.
+///
+/// `EXPIRED` – count-limited cycle finished, its last frame was queued.
+///
+/// `STOPPED` – cyclic transmission removed by the caller.
+///
+/// `ERROR` – transmission refused by the kernel (e.g. ENOBUFS when the TX queue is full).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum CanTxStatus {
+    QUEUED,
+    STARTED,
+    EXPIRED,
+    STOPPED,
+    ERROR,
+}
+
+AfbDataConverter!(bcm_tx_status, CanBcmTxStatus);
+
+/// Transmission notification pushed on the session event.
+///
+/// Fields:
+/// - `canid`: transmitted CAN identifier,
+/// - `status`: transmission state (see `CanTxStatus`),
+/// - `info`: error description when `status` is `ERROR`, empty otherwise.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CanBcmTxStatus {
+    pub canid: u32,
+    pub status: CanTxStatus,
+    #[serde(default)]
+    pub info: String,
+}
+
+AfbDataConverter!(rtr_reply_param, RtrReplyParam);
+
+/// Parameters of the `rtr_reply` verb managing kernel-side RTR auto-replies.
+///
+/// Fields:
+/// - `canid`: CAN identifier answered when a remote transmission request is received,
+/// - `data`: payload of the reply frame (up to 8 bytes),
+/// - `delete`: remove the auto-reply of `canid` instead of installing it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RtrReplyParam {
+    pub canid: u32,
+    #[serde(default)]
+    pub data: Vec<u8>,
+    #[serde(default)]
+    pub delete: bool,
+}
+
+AfbDataConverter!(bcm_metrics, CanBcmMetrics);
+
+/// Frame counter for one subscribed CAN ID.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CanBcmIdMetrics {
+    pub canid: u32,
+    pub frames: u64,
+}
+
+/// Runtime counters of one BCM session.
+///
+/// Fields:
+/// - `session`: session number, unique within the binding,
+/// - `listeners`: number of listeners seen on the last event push,
+/// - `frames`: BCM frames read from the socket,
+/// - `events`: events pushed to clients,
+/// - `rx_timeouts`: BCM `RxTimeout` notifications,
+/// - `decode_errors`: frames that could not be converted into `CanBcmData`,
+/// - `filter_failures`: BCM filters that could not be installed or re-armed,
+/// - `dropped`: frames dropped by the per-session event budget,
+/// - `canids`: per CAN ID frame counters.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CanBcmSessionMetrics {
+    pub session: u32,
+    pub listeners: i32,
+    pub frames: u64,
+    pub events: u64,
+    pub rx_timeouts: u64,
+    pub decode_errors: u64,
+    pub filter_failures: u64,
+    pub dropped: u64,
+    pub canids: Vec<CanBcmIdMetrics>,
+}
+
+/// Binding-wide runtime counters returned by the `metrics` verb and event.
+///
+/// Totals are summed over the live sessions listed in `sessions`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CanBcmMetrics {
+    pub frames: u64,
+    pub events: u64,
+    pub rx_timeouts: u64,
+    pub decode_errors: u64,
+    pub filter_failures: u64,
+    pub dropped: u64,
+    pub sessions: Vec<CanBcmSessionMetrics>,
+}
+
+AfbDataConverter!(socket_state, CanSocketState);
+
+/// State of a session BCM socket.
+///
+/// `OPEN` – the socket is bound and its filters are installed.
+///
+/// `DEGRADED` – the CAN link is down, the socket is reopened when it comes back.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum CanSocketState {
+    OPEN,
+    DEGRADED,
+}
+
+/// One CAN ID subscribed on a session, as reported by the `list` verb.
+///
+/// Fields:
+/// - `canid`: subscribed CAN identifier,
+/// - `rate`/`watchdog`: timers requested by the last subscription (ms),
+/// - `flag`: notification policy of the last subscription,
+/// - `source`: socket delivering the frames,
+/// - `silent`: the CAN ID missed its watchdog and no frame came back since,
+/// - `frames`: frames received for this CAN ID,
+/// - `kernel`: whether `/proc/net/can-bcm` shows the RX filter, only set by a cross-check
+///   of a `BCM` source subscription.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CanBcmIdSubscription {
+    pub canid: u32,
+    pub rate: u64,
+    pub watchdog: u64,
+    pub flag: SubscribeFlag,
+    pub source: SubscribeSource,
+    pub silent: bool,
+    pub frames: u64,
+    pub kernel: Option<bool>,
+}
+
+/// One session as reported by the `list` verb.
+///
+/// Fields:
+/// - `session`: session number, unique within the binding,
+/// - `uid`: client uid,
+/// - `device`: CAN interface the session is bound to,
+/// - `listeners`: number of listeners seen on the last event push,
+/// - `socket`: state of the BCM socket,
+/// - `raw`: a raw socket is open for `RAW` source subscriptions,
+/// - `canids`: subscribed CAN IDs sorted by id,
+/// - `unknown`: RX filters listed by `/proc/net/can-bcm` for the socket but not subscribed,
+///   only set by a cross-check.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CanBcmSessionSubscriptions {
+    pub session: u32,
+    pub uid: String,
+    pub device: String,
+    pub listeners: i32,
+    pub socket: CanSocketState,
+    pub raw: bool,
+    pub canids: Vec<CanBcmIdSubscription>,
+    pub unknown: Option<Vec<u32>>,
+}
+
+AfbDataConverter!(bcm_subscriptions, CanBcmSubscriptions);
+
+/// Active subscriptions returned by the `list` verb.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CanBcmSubscriptions {
+    pub event_mode: SockcanEventMode,
+    pub sessions: Vec<CanBcmSessionSubscriptions>,
+}
+
+AfbDataConverter!(bus_load, CanBusLoad);
+
+/// Estimated bus load of one CAN interface over the last sampling window.
+///
+/// Fields:
+/// - `device`: CAN interface name,
+/// - `bitrate`: configured nominal bitrate (bit/s),
+/// - `load`: estimated load in percent of `bitrate`, stuff bits included,
+/// - `frames`: frames seen during the window,
+/// - `period`: window duration in ms,
+/// - `threshold`: configured high-load threshold in percent,
+/// - `high`: true when `load` is above `threshold`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct CanBusLoad {
+    pub device: String,
+    pub bitrate: u64,
+    pub load: f64,
+    pub frames: u64,
+    pub period: u64,
+    pub threshold: u64,
+    pub high: bool,
+}
+
 // Register custom data types within the AFB binder.
 //
 // This function must be called during binding initialization so the framework
@@ -223,14 +720,320 @@ pub fn sockdata_register(_root: AfbApiV4) -> Result<(), AfbError> {
     // Custom types should be registered at binding startup time.
     bcm_error::register()?;
     bcm_data::register()?;
+    bcm_timeout::register()?;
+    bcm_alive::register()?;
+    bcm_link::register()?;
     bcm_sig::register()?;
     bcm_msg::register()?;
     subscribe_param::register()?;
     subscribe_flag::register()?;
+    subscribe_source::register()?;
     unsubscribe_param::register()?;
+    send_param::register()?;
+    bcm_tx_status::register()?;
+    rtr_reply_param::register()?;
+    bcm_metrics::register()?;
+    socket_state::register()?;
+    bcm_subscriptions::register()?;
+    bus_load::register()?;
+    bcm_sig_info::register()?;
+    bcm_msg_info::register()?;
+    bcm_catalog::register()?;
+    bcm_sig_set::register()?;
+    bcm_alarm::register()?;
+    bcm_alarms::register()?;
+    bcm_history::register()?;
+    bcm_sig_stats::register()?;
     Ok(())
 }
 
+/// Per-client quotas enforced by the sockcan binding (`"limits"` key).
+///
+/// A zero or missing value disables the corresponding limit.
+///
+/// Fields:
+/// - `max_canids`: maximum number of CAN IDs subscribed by one session,
+/// - `max_sessions`: maximum number of concurrent BCM sessions,
+/// - `min_rate`: lowest accepted notification rate in ms (rejects `rate:0` when set),
+/// - `max_events`: maximum number of events pushed per second to one session.
+///
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SockcanLimits {
+    pub max_canids: usize,
+    pub max_sessions: usize,
+    pub min_rate: u64,
+    pub max_events: u64,
+}
+
+/// Class of operation guarded by a `SockcanAclRule`.
+///
+/// `Subscribe` – receive frames (`subscribe`).
+///
+/// `Send` – transmit frames.
+///
+/// `Configure` – verbs that probe or report the binding setup (`check`, `list`, `metrics`,
+/// `busload`).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SockcanVerbClass {
+    Subscribe,
+    Send,
+    Configure,
+}
+
+/// How BCM notifications are published (`"event_mode"` key).
+///
+/// `Session` – one event per client session, multiplexing every subscribed CAN ID.
+///
+/// `Canid` – one shared BCM socket and one named event per CAN ID (`{event_uid}/0x{canid:x}`),
+/// clients subscribe and unsubscribe to each id through native AFB event subscription.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SockcanEventMode {
+    #[default]
+    Session,
+    Canid,
+}
+
+/// Fine-grained permission rule mapping a set of verbs and CAN ID ranges to an AFB permission.
+///
+/// Fields:
+/// - `acl`: AFB permission the caller must hold,
+/// - `verbs`: verb classes covered by this rule,
+/// - `canids`: inclusive CAN ID ranges covered by this rule, empty means any CAN ID.
+///
+#[derive(Debug, Clone)]
+pub struct SockcanAclRule {
+    pub acl: &'static str,
+    pub verbs: Vec<SockcanVerbClass>,
+    pub canids: Vec<(u32, u32)>,
+}
+
+impl SockcanAclRule {
+    /// Return true when this rule covers `class`, and `canid` when one is given.
+    pub fn matches(&self, class: SockcanVerbClass, canid: Option<u32>) -> bool {
+        if !self.verbs.contains(&class) {
+            return false;
+        }
+        match canid {
+            None => true,
+            Some(canid) => {
+                self.canids.is_empty()
+                    || self.canids.iter().any(|(first, last)| canid >= *first && canid <= *last)
+            },
+        }
+    }
+}
+
+/// Raw JSON form of a permission rule, before CAN ID ranges are parsed.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AclRuleJson {
+    acl: String,
+    verbs: Vec<SockcanVerbClass>,
+    #[serde(default)]
+    canids: Vec<CanIdRangeJson>,
+}
+
+/// A CAN ID given either as a JSON number or as a `"0x100"` / `"0x100-0x3FF"` string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CanIdRangeJson {
+    Id(u32),
+    Text(String),
+}
+
+/// Parse a CAN ID or an inclusive `first-last` CAN ID range.
+///
+/// Both bounds accept decimal or `0x` prefixed hexadecimal values.
+pub fn parse_canid_range(text: &str) -> Option<(u32, u32)> {
+    let number = |value: &str| -> Option<u32> {
+        let value = value.trim();
+        match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => value.parse::<u32>().ok(),
+        }
+    };
+
+    match text.split_once('-') {
+        Some((first, last)) => {
+            let (first, last) = (number(first)?, number(last)?);
+            if first <= last {
+                Some((first, last))
+            } else {
+                None
+            }
+        },
+        None => number(text).map(|canid| (canid, canid)),
+    }
+}
+
+/// Bus load monitor settings (`"busload"` key).
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BusLoadJson {
+    period: u64,
+    threshold: u64,
+}
+
+impl Default for BusLoadJson {
+    fn default() -> Self {
+        BusLoadJson { period: 0, threshold: 80 }
+    }
+}
+
+/// Per-message overrides applied by `dbcapi::create_pool_verbs` (`"messages"` key).
+///
+/// Fields:
+/// - `info`: verb description, default: `"(canid:N)"`,
+/// - `rate` / `watchdog`: initial throttling thresholds in ms.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SockcanMsgConfig {
+    pub info: Option<String>,
+    pub rate: Option<u64>,
+    pub watchdog: Option<u64>,
+}
+
+/// Message sent by the binding itself (`"transmit"` key), see `dbcapi::create_pool_verbs`.
+///
+/// Fields:
+/// - `period`: cycle time in ms, default: the DBC `GenMsgCycleTime` of the message.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SockcanTxConfig {
+    pub period: Option<u64>,
+}
+
+/// Severity of an alarm rule.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SockcanAlarmSeverity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+/// Alarm rule evaluated by DBC bindings (`"alarms"` key), see `dbcapi::create_pool_verbs`.
+///
+/// Fields:
+/// - `uid`: alarm name, unique in the binding,
+/// - `when`: condition `"Message/Signal <op> <value> [for <delay>]"`, with `op` one of
+///   `> >= < <= == !=` and `delay` in ms (`500`, `500ms`) or seconds (`2s`),
+/// - `severity`: `info`, `warning` (default) or `critical`,
+/// - `info`: free text copied into the alarm notifications.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SockcanAlarmConfig {
+    pub uid: String,
+    pub when: String,
+    #[serde(default)]
+    pub severity: SockcanAlarmSeverity,
+    #[serde(default)]
+    pub info: String,
+}
+
+/// Computed signal published by DBC bindings (`"virtuals"` key), see `dbcapi::create_pool_verbs`.
+///
+/// Fields:
+/// - `uid`: name of the `virtual/<uid>` verb and event, unique in the binding,
+/// - `expr`: expression over `Message/Signal` inputs with `+ - * /`, unary minus, numbers
+///   and parentheses, e.g. `"BmsStatus/PackVoltage * BmsStatus/PackCurrent"`,
+/// - `average`: when set, publish the moving average of the last `average` results.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SockcanVirtualConfig {
+    pub uid: String,
+    pub expr: String,
+    #[serde(default)]
+    pub average: Option<usize>,
+}
+
+/// History buffer kept by DBC bindings (`"history"` key), see `dbcapi::create_pool_verbs`.
+///
+/// Fields:
+/// - `signals`: `Message/Signal` patterns with `*`/`?` wildcards, the first entry matching a
+///   signal sets its buffer,
+/// - `samples`: number of values kept,
+/// - `seconds`: age of the values kept; with both limits the first one reached applies.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SockcanHistoryConfig {
+    pub signals: Vec<String>,
+    #[serde(default)]
+    pub samples: Option<usize>,
+    #[serde(default)]
+    pub seconds: Option<u64>,
+}
+
+fn default_dev() -> String {
+    "vcan0".to_string()
+}
+
+fn default_uid() -> String {
+    "sockcan".to_string()
+}
+
+fn default_event_uid() -> String {
+    "sockbcm".to_string()
+}
+
+fn default_acls() -> String {
+    "acl:sockcan".to_string()
+}
+
+fn default_bitrate() -> u64 {
+    500_000
+}
+
+/// JSON schema of the binding configuration, shared by the sockcan binding and by
+/// DBC bindings built with `dbcapi::create_pool_verbs`.
+///
+/// Unknown keys are rejected so that a typo does not silently fall back to a default.
+/// Keys listed in `DBC_CONFIG_KEYS` are only accepted by `parse_dbc_config`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SockcanConfigJson {
+    #[serde(default = "default_dev")]
+    dev: String,
+    #[serde(default = "default_uid")]
+    uid: String,
+    #[serde(default)]
+    sock_api: Option<String>,
+    #[serde(default)]
+    info: String,
+    #[serde(default = "default_event_uid", alias = "sock_evt")]
+    event_uid: String,
+    #[serde(default)]
+    event_mode: SockcanEventMode,
+    #[serde(default = "default_acls")]
+    acls: String,
+    #[serde(default)]
+    limits: SockcanLimits,
+    #[serde(default)]
+    permissions: Vec<AclRuleJson>,
+    #[serde(default)]
+    metrics_period: u64,
+    #[serde(default = "default_bitrate")]
+    bitrate: u64,
+    #[serde(default)]
+    busload: BusLoadJson,
+    #[serde(default)]
+    messages: HashMap<String, SockcanMsgConfig>,
+    #[serde(default)]
+    transmit: HashMap<String, SockcanTxConfig>,
+    #[serde(default)]
+    alarms: Vec<SockcanAlarmConfig>,
+    #[serde(default)]
+    virtuals: Vec<SockcanVirtualConfig>,
+    #[serde(default)]
+    history: Vec<SockcanHistoryConfig>,
+    #[serde(default)]
+    dbc: Option<String>,
+}
+
 /// Static configuration for the sockcan binding, parsed once from the JSON binding config.
 ///
 /// All fields are `'static` string slices, typically created using `to_static_str`,
@@ -238,59 +1041,219 @@ pub fn sockdata_register(_root: AfbApiV4) -> Result<(), AfbError> {
 ///
 /// Fields:
 /// - `api_uid`: public API identifier for this binding,
-/// - `event_uid`: event name used to emit BCM notifications,
+/// - `event_uid`: event name used for BCM notifications,
+/// - `event_mode`: one event per session or one event per CAN ID (see `SockcanEventMode`),
 /// - `can_device`: CAN interface name (e.g. "can0", "vcan0"),
-/// - `sock_api`: name of the underlying sockcan service API,
+/// - `sock_api`: name of the underlying sockcan service API (DBC bindings, empty when omitted),
 /// - `info`: human-readable API description,
-/// - `acls`: ACL expression required to access the API.
+/// - `acls`: ACL expression required to access the API,
+/// - `limits`: per-client quotas (see `SockcanLimits`),
+/// - `permissions`: per verb and per CAN ID range ACL rules (see `SockcanAclRule`),
+/// - `metrics_period`: period in ms of the `metrics` event, 0 disables it,
+/// - `bitrate`: nominal bitrate of `can_device` in bit/s, used for bus load estimation,
+/// - `busload_period`: bus load sampling period in ms, 0 disables the raw monitor,
+/// - `busload_threshold`: bus load in percent above which samples are flagged as high,
+/// - `messages`: per-message overrides used by DBC bindings (see `SockcanMsgConfig`),
+/// - `transmit`: messages cyclically sent by DBC bindings (see `SockcanTxConfig`),
+/// - `alarms`: alarm rules evaluated by DBC bindings (see `SockcanAlarmConfig`),
+/// - `virtuals`: signals computed by DBC bindings (see `SockcanVirtualConfig`),
+/// - `history`: signal history buffers kept by DBC bindings (see `SockcanHistoryConfig`),
+/// - `dbc`: DBC file loaded at startup by `dbcapi::create_dbc_verbs`.
 ///
 pub struct SockcanBindingConfig {
     pub api_uid: &'static str,
     pub event_uid: &'static str,
+    pub event_mode: SockcanEventMode,
     pub can_device: &'static str,
     pub sock_api: &'static str,
     pub info: &'static str,
     pub acls: &'static str,
+    pub limits: SockcanLimits,
+    pub permissions: Vec<SockcanAclRule>,
+    pub metrics_period: u64,
+    pub bitrate: u64,
+    pub busload_period: u64,
+    pub busload_threshold: u64,
+    pub messages: HashMap<String, SockcanMsgConfig>,
+    pub transmit: HashMap<String, SockcanTxConfig>,
+    pub alarms: Vec<SockcanAlarmConfig>,
+    pub virtuals: Vec<SockcanVirtualConfig>,
+    pub history: Vec<SockcanHistoryConfig>,
+    pub dbc: Option<&'static str>,
 }
 
-/// Parse the JSON configuration object into a `SockcanBindingConfig`.
+/// Sections read by DBC bindings only: the sockcan binding rejects them instead of ignoring
+/// a misplaced section. `sock_api` is not listed: sockcan configs always accepted it.
+const DBC_CONFIG_KEYS: [&str; 6] = ["messages", "transmit", "alarms", "virtuals", "history", "dbc"];
+
+/// Build the error returned for an invalid configuration, prefixed by the JSON key path.
+fn config_error(path: impl Display, info: impl Display) -> AfbError {
+    AfbError::new("invalid-config", 0, format!("{}: {}", path, info))
+}
+
+/// Parse and validate the JSON configuration object into a `SockcanBindingConfig`.
 ///
 /// Supported JSON keys and defaults:
 /// - `"dev"`       → `can_device`, default: `"vcan0"`
 /// - `"uid"`       → `api_uid`, default: `"sockcan"`
-/// - `"sock_api"`  → `sock_api`, default: `api_uid`
+/// - `"sock_api"`  → `sock_api`, required by DBC bindings, ignored by the sockcan binding
 /// - `"info"`      → `info`, default: `""`
-/// - `"event_uid"` → `event_uid`, default: `"sockbcm"`
+/// - `"event_uid"` (alias `"sock_evt"`) → `event_uid`, default: `"sockbcm"`
 /// - `"acls"`      → `acls`, default: `"acl:sockcan"`
+/// - `"limits"`    → `limits`, object with optional `max_canids`, `max_sessions`,
+///   `min_rate` and `max_events` keys, default: no limit
+/// - `"permissions"` → `permissions`, array of `{"acl","verbs","canids"}` rules, default: none
+/// - `"metrics_period"` → `metrics_period`, default: `0` (no periodic metrics event)
+/// - `"bitrate"`   → `bitrate`, default: `500000`
+/// - `"busload"`   → object with optional `period` (ms, default: `0`, disabled) and
+///   `threshold` (percent, default: `80`) keys
+/// - `"messages"`  → `messages`, object keyed by DBC message name, default: none
+/// - `"transmit"`  → `transmit`, object keyed by DBC message name with an optional
+///   `period` (ms) key, default: none
+/// - `"alarms"`    → `alarms`, array of `{"uid","when","severity","info"}` rules, default: none
+/// - `"virtuals"`  → `virtuals`, array of `{"uid","expr","average"}` signals, default: none
+/// - `"history"`   → `history`, array of `{"signals","samples","seconds"}` buffers, default: none
+/// - `"dbc"`       → `dbc`, path of a DBC file loaded at runtime, default: none
+///
+/// `messages`, `transmit`, `alarms`, `virtuals`, `history` and `dbc` configure DBC
+/// bindings: the sockcan binding rejects them, use `parse_dbc_config` to read them.
 ///
+/// When an `"args"` object is present, the configuration is read from it instead of the root.
+///
+/// Unknown keys, mistyped values and invalid CAN ID ranges are rejected with an
+/// `invalid-config` error naming the offending key path (e.g. `limits.max_canids`).
 /// All string values are converted to `'static` with `to_static_str`.
 ///
-pub fn parse_sockcan_config(jconf: &JsoncObj) -> SockcanBindingConfig {
-    let can_device =
-        if let Ok(value) = jconf.get::<String>("dev") { to_static_str(value) } else { "vcan0" };
+pub fn parse_sockcan_config(jconf: &JsoncObj) -> Result<SockcanBindingConfig, AfbError> {
+    parse_config(jconf, false)
+}
 
-    let api_uid =
-        if let Ok(value) = jconf.get::<String>("uid") { to_static_str(value) } else { "sockcan" };
+/// Parse the configuration of a DBC binding, see `parse_sockcan_config` for the keys.
+///
+/// `sock_api`, the sockcan API the DBC verbs subscribe to, is mandatory.
+pub fn parse_dbc_config(jconf: &JsoncObj) -> Result<SockcanBindingConfig, AfbError> {
+    parse_config(jconf, true)
+}
 
-    let sock_api = if let Ok(value) = jconf.get::<String>("sock_api") {
-        to_static_str(value)
-    } else {
-        api_uid
+fn parse_config(jconf: &JsoncObj, dbc: bool) -> Result<SockcanBindingConfig, AfbError> {
+    let jconf = match jconf.get::<JsoncObj>("args") {
+        Ok(args) => args,
+        Err(_) => jconf.clone(),
     };
 
-    let info = if let Ok(value) = jconf.get::<String>("info") { to_static_str(value) } else { "" };
-
-    let event_uid = if let Ok(value) = jconf.get::<String>("event_uid") {
-        to_static_str(value)
-    } else {
-        "sockbcm"
+    let text = jconf.to_string();
+    let mut deserializer = serde_json::Deserializer::from_str(&text);
+    let config: SockcanConfigJson = match serde_path_to_error::deserialize(&mut deserializer) {
+        Ok(config) => config,
+        Err(error) => return Err(config_error(error.path(), error.inner())),
     };
 
-    let acls = if let Ok(value) = jconf.get::<String>("acls") {
-        to_static_str(value)
-    } else {
-        "acl:sockcan"
+    if !dbc {
+        if let Ok(serde_json::Value::Object(keys)) = serde_json::from_str(&text) {
+            if let Some(key) = DBC_CONFIG_KEYS.iter().find(|key| keys.contains_key(**key)) {
+                return Err(config_error(key, "only valid in DBC bindings"));
+            }
+        }
+    }
+    let sock_api = match config.sock_api {
+        Some(value) => to_static_str(value),
+        None if dbc => {
+            return Err(config_error("sock_api", "missing sockcan API name of the DBC binding"))
+        },
+        None => "",
     };
 
-    SockcanBindingConfig { api_uid, event_uid, can_device, sock_api, info, acls }
+    if config.busload.period > 0 && config.bitrate == 0 {
+        return Err(config_error("bitrate", "must be > 0 when busload is enabled"));
+    }
+    if config.busload.threshold > 100 {
+        return Err(config_error("busload.threshold", "percent value must be <= 100"));
+    }
+    for (name, transmit) in &config.transmit {
+        if transmit.period == Some(0) {
+            return Err(config_error(format!("transmit.{}.period", name), "must be > 0"));
+        }
+    }
+    for (idx, alarm) in config.alarms.iter().enumerate() {
+        if config.alarms[..idx].iter().any(|other| other.uid == alarm.uid) {
+            return Err(config_error(
+                format!("alarms[{}].uid", idx),
+                format!("duplicated alarm '{}'", alarm.uid),
+            ));
+        }
+    }
+    for (idx, signal) in config.virtuals.iter().enumerate() {
+        if config.virtuals[..idx].iter().any(|other| other.uid == signal.uid) {
+            return Err(config_error(
+                format!("virtuals[{}].uid", idx),
+                format!("duplicated virtual signal '{}'", signal.uid),
+            ));
+        }
+        if signal.average == Some(0) {
+            return Err(config_error(format!("virtuals[{}].average", idx), "must be > 0"));
+        }
+    }
+    for (idx, history) in config.history.iter().enumerate() {
+        if history.signals.is_empty() {
+            return Err(config_error(format!("history[{}].signals", idx), "must not be empty"));
+        }
+        match (history.samples, history.seconds) {
+            (None, None) => {
+                return Err(config_error(format!("history[{}]", idx), "expect samples or seconds"))
+            },
+            (Some(0), _) => {
+                return Err(config_error(format!("history[{}].samples", idx), "must be > 0"))
+            },
+            (_, Some(0)) => {
+                return Err(config_error(format!("history[{}].seconds", idx), "must be > 0"))
+            },
+            _ => {},
+        }
+    }
+
+    let mut permissions = Vec::with_capacity(config.permissions.len());
+    for (ridx, rule) in config.permissions.into_iter().enumerate() {
+        let mut canids = Vec::with_capacity(rule.canids.len());
+        for (cidx, range) in rule.canids.into_iter().enumerate() {
+            match range {
+                CanIdRangeJson::Id(canid) => canids.push((canid, canid)),
+                CanIdRangeJson::Text(text) => match parse_canid_range(&text) {
+                    Some(range) => canids.push(range),
+                    None => {
+                        return Err(config_error(
+                            format!("permissions[{}].canids[{}]", ridx, cidx),
+                            format!("invalid canid range '{}'", text),
+                        ))
+                    },
+                },
+            }
+        }
+        permissions.push(SockcanAclRule {
+            acl: to_static_str(rule.acl),
+            verbs: rule.verbs,
+            canids,
+        });
+    }
+
+    Ok(SockcanBindingConfig {
+        api_uid: to_static_str(config.uid),
+        event_uid: to_static_str(config.event_uid),
+        event_mode: config.event_mode,
+        can_device: to_static_str(config.dev),
+        sock_api,
+        info: to_static_str(config.info),
+        acls: to_static_str(config.acls),
+        limits: config.limits,
+        permissions,
+        metrics_period: config.metrics_period,
+        bitrate: config.bitrate,
+        busload_period: config.busload.period,
+        busload_threshold: config.busload.threshold,
+        messages: config.messages,
+        transmit: config.transmit,
+        alarms: config.alarms,
+        virtuals: config.virtuals,
+        history: config.history,
+        dbc: config.dbc.map(to_static_str),
+    })
 }